@@ -1,10 +1,13 @@
+use std::collections::HashSet;
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{channel, Sender};
 
-use log::{debug, info};
+use log::{debug, error, info, warn};
 use crate::coin::db::BlockDatabase;
-use crate::coin::node::blockchain::block::Block;
-use crate::coin::node::blockchain::blockchain::{Blockchain, validate_chain};
+use crate::coin::node::blockchain::block::{Block, BlockHeader};
+use crate::coin::node::blockchain::blockchain::{Blockchain, ChainUpdate, validate_chain};
+use crate::coin::node::blockchain::merkle::MerkleProof;
+use crate::coin::node::blockchain::validation::BlockValidationError;
 use crate::coin::node::blockchain::transaction::SerializedTransaction;
 use crate::coin::node::node_message::TransactionMessage;
 use crate::coin::server::server::Server;
@@ -42,17 +45,113 @@ impl AppState {
         info!("Insert block into DB");
         self.database.lock().expect("Can't lock mutex of DB").insert_block(block)
     }
-    pub fn add_block(&self, block:Block, is_force:bool){
-        self.insert_block_into_db(&block);
-        if is_force{
-            self.blockchain.lock().unwrap().add_force_block(block);
-        }else {
-            self.blockchain.lock().unwrap().add_block(block).expect("Error add block to chain");
+    /// Передаёт блок цепочке и сохраняет изменения основной цепочки в БД.
+    /// При реорганизации снятые блоки удаляются из БД, а их транзакции возвращаются в очередь.
+    /// После изменения основной цепочки очередь транзакций перепроверяется.
+    /// Блоки-сироты, дождавшиеся родителя, подключаются следом.
+    pub fn add_block(&self, block:Block) -> Result<ChainUpdate, BlockValidationError> {
+        let hash = block.get_hash();
+        let update = self.connect_block(block)?;
+        if !matches!(update, ChainUpdate::Orphan { .. }) {
+            self.connect_orphans(hash);
         }
+        Ok(update)
     }
 
+    fn connect_orphans(&self, parent_hash:String) {
+        let mut parents = vec![parent_hash];
+        while let Some(parent_hash) = parents.pop() {
+            let orphans = self.blockchain.lock().expect("Error lock blockchain node").take_orphans(&parent_hash);
+            for orphan in orphans {
+                let hash = orphan.get_hash();
+                match self.connect_block(orphan) {
+                    Ok(_) => parents.push(hash),
+                    Err(e) => warn!("Orphan block {} rejected: {}", hash, e),
+                }
+            }
+        }
+    }
+
+    fn connect_block(&self, block:Block) -> Result<ChainUpdate, BlockValidationError> {
+        let update = self.blockchain.lock().expect("Error lock blockchain node").add_block(block)?;
+        match &update {
+            ChainUpdate::Extended(block) => {
+                if let Err(e) = self.insert_block_into_db(block) {
+                    error!("Failed to insert block {} into DB: {}", block.get_id(), e);
+                }
+                self.notify_chain_updated();
+            }
+            ChainUpdate::Reorganized { disconnected, connected } => {
+                self.persist_reorganization(disconnected, connected);
+                self.requeue_transactions(disconnected, connected);
+                self.notify_chain_updated();
+            }
+            ChainUpdate::SideBranch => {
+                debug!("Block stored in side branch");
+            }
+            ChainUpdate::Orphan { missing_parent } => {
+                debug!("Block waits for parent {}", missing_parent);
+            }
+        }
+        Ok(update)
+    }
+
+    fn persist_reorganization(&self, disconnected:&[Block], connected:&[Block]) {
+        let database = self.database.lock().expect("Can't lock mutex of DB");
+        if let Some(first) = disconnected.first().or(connected.first())
+            && let Err(e) = database.delete_blocks_from(first.get_id()) {
+            error!("Failed to roll back blocks from {}: {}", first.get_id(), e);
+        }
+        for block in connected {
+            if let Err(e) = database.insert_block(block) {
+                error!("Failed to insert block {} into DB: {}", block.get_id(), e);
+            }
+        }
+    }
+
+    /// Транзакции снятых блоков, не попавшие в новую ветку, снова ждут включения в блок
+    fn requeue_transactions(&self, disconnected:&[Block], connected:&[Block]) {
+        let confirmed: HashSet<String> = connected
+            .iter()
+            .flat_map(|block| block.get_transactions())
+            .map(|transaction| transaction.get_txid())
+            .collect();
+
+        for transaction in disconnected.iter().flat_map(|block| block.get_transactions()) {
+            if transaction.is_coinbase() || confirmed.contains(&transaction.get_txid()) {
+                continue;
+            }
+            self.add_transaction(transaction.clone());
+        }
+    }
+
+    /// Очередь транзакций убирает попавшие в блок и ставшие недействительными
+    fn notify_chain_updated(&self) {
+        if let Err(e) = self.transaction_tx.send(TransactionMessage::ChainUpdated()) {
+            warn!("Failed to notify transaction queue: {}", e);
+        }
+    }
+
+    /// Проверяет полученную цепочку и добавляет её неизвестные блоки.
+    /// Если цепочка несёт больше работы, узел переключается на неё.
     pub fn check_chain(&self, chain:Vec<Block>){
-        validate_chain(&chain);
+        let params = self.blockchain.lock().expect("Error lock blockchain node").get_params().clone();
+        if !validate_chain(&chain, &params) {
+            warn!("Received chain is invalid, ignored");
+            return;
+        }
+        for block in chain {
+            let is_known = self.blockchain.lock().expect("Error lock blockchain node")
+                .get_block_by_hash(&block.get_hash())
+                .is_some();
+            if is_known {
+                continue;
+            }
+            if let Err(e) = self.add_block(block) {
+                warn!("Block from received chain rejected: {}", e);
+                break;
+            }
+        }
     }
 
     pub fn get_from_first_block(&self) -> Vec<Block> {
@@ -67,12 +166,25 @@ impl AppState {
         self.blockchain.lock().expect("Error lock blockchain node").get_blocks_before(date_time)
     }
 
+    pub fn get_genesis_hash(&self) -> String {
+        self.blockchain.lock().expect("Error lock blockchain node").get_genesis_hash()
+    }
+
+    pub fn get_block_by_hash(&self, hash: &str) -> Option<Block> {
+        self.blockchain.lock().expect("Error lock blockchain node").get_block_by_hash(hash)
+    }
+
+    pub fn get_merkle_proof(&self, transaction_hash: &str) -> Option<(BlockHeader, MerkleProof)> {
+        self.blockchain.lock().expect("Error lock blockchain node").get_merkle_proof(transaction_hash)
+    }
+
     pub fn add_transaction(&self, transaction:SerializedTransaction){
-        self.transaction_tx.send(TransactionMessage::AddTransaction(transaction)).expect("send transaction to channel");
+        self.transaction_tx.send(TransactionMessage::AddTransaction(Box::new(transaction))).expect("send transaction to channel");
     }
 
     pub fn connect(&self, addr:String){
         debug!("send request to server for connect: {}", addr);
-        self.server.connect(format!("{}:7878", addr)).unwrap();
+        let port = self.blockchain.lock().expect("Error lock blockchain node").get_params().default_port;
+        self.server.connect(format!("{}:{}", addr, port)).unwrap();
     }
 }
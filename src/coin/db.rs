@@ -1,8 +1,54 @@
 use log::{debug, error}; // Добавлен импорт error для логирования ошибок
-use rusqlite::{params, Connection, Result}; // Result здесь это rusqlite::Result
+use rusqlite::{params, Connection, OptionalExtension, Result}; // Result здесь это rusqlite::Result
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use crate::coin::node::blockchain::address;
+use crate::coin::node::blockchain::amount::Amount;
 use crate::coin::node::blockchain::block::Block;
+use crate::coin::node::blockchain::crypto::PublicKey;
+use crate::coin::node::blockchain::blockchain::TransactionLookup;
+use crate::coin::node::blockchain::difficulty;
 use crate::coin::node::blockchain::transaction::SerializedTransaction; // Убедитесь, что этот импорт есть, если он нужен для Block
 
+/// Текущая версия схемы БД (хранится в PRAGMA user_version)
+const SCHEMA_VERSION: i64 = 1;
+
+/// Транзакция в исходном формате: счета записаны RSA-ключами без метки алгоритма,
+/// сумма — в монетах числом с плавающей точкой, комиссии и номера нет
+#[derive(Deserialize)]
+struct LegacyTransaction {
+    sender: String,
+    buyer: String,
+    seller: String,
+    message: String,
+    transfer: f64,
+    signature: String,
+}
+
+impl LegacyTransaction {
+    fn upgrade(self) -> Option<SerializedTransaction> {
+        let public_key = PublicKey::decode_stored(&self.sender).ok().map(|key| key.encode());
+        let mut transaction = SerializedTransaction::new(
+            key_to_address(self.sender),
+            key_to_address(self.seller),
+            key_to_address(self.buyer),
+            self.message,
+            Amount::from_coins_f64(self.transfer)?,
+        );
+        transaction.public_key = public_key;
+        transaction.signature = self.signature;
+        Some(transaction)
+    }
+}
+
+/// Адрес счёта, записанного ключом; остальные счета не меняются
+fn key_to_address(account: String) -> String {
+    match PublicKey::decode_stored(&account) {
+        Ok(key) => address::from_public_key(&key),
+        Err(_) => account,
+    }
+}
+
 // --- Структура BlockDatabase ---
 pub struct BlockDatabase {
     conn: Connection,
@@ -15,6 +61,7 @@ impl BlockDatabase {
         let conn = Connection::open(path)?;
         let db = Self { conn };
         db.create_table()?;
+        db.migrate()?;
         Ok(db)
     }
 
@@ -30,6 +77,81 @@ impl BlockDatabase {
             )",
             [],
         )?;
+        // Индекс транзакций: идентификатор транзакции -> ID содержащего её блока
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS transactions (
+                txid TEXT PRIMARY KEY,
+                block_id INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Обновляет схему БД до SCHEMA_VERSION
+    fn migrate(&self) -> Result<()> {
+        let version: i64 = self.conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        if version < 1 {
+            // Цель сложности блока; старые блоки добывались с префиксом "000"
+            self.conn.execute(
+                &format!(
+                    "ALTER TABLE blocks ADD COLUMN target INTEGER NOT NULL DEFAULT {}",
+                    difficulty::INITIAL_TARGET as i64
+                ),
+                [],
+            )?;
+            // Суммы хранятся целым числом базовых единиц вместо f64, счета — адресами
+            self.reencode_transactions(LegacyTransaction::upgrade)?;
+            // Индекс транзакций по идентификаторам, вычисленным уже по новому формату
+            self.index_stored_transactions()?;
+        }
+
+        if version < SCHEMA_VERSION {
+            debug!("Database schema migrated from version {} to {}", version, SCHEMA_VERSION);
+            self.conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        }
+        Ok(())
+    }
+
+    /// Переводит транзакции всех блоков из формата `T` прежней схемы в текущий.
+    /// Строки, которые не удаётся перевести, оставляются как есть и будут отброшены при загрузке цепочки.
+    fn reencode_transactions<T: DeserializeOwned>(&self, upgrade: fn(T) -> Option<SerializedTransaction>) -> Result<()> {
+        let rows: Vec<(i64, Vec<u8>)> = {
+            let mut stmt = self.conn.prepare("SELECT id, transactions FROM blocks")?;
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        let db_transaction = self.conn.unchecked_transaction()?;
+        for (id, blob) in rows {
+            let upgraded = bincode::deserialize::<Vec<T>>(&blob)
+                .ok()
+                .and_then(|stored| stored.into_iter().map(upgrade).collect::<Option<Vec<_>>>());
+            match upgraded.map(|transactions| bincode::serialize(&transactions)) {
+                Some(Ok(data)) => {
+                    db_transaction.execute("UPDATE blocks SET transactions = ?1 WHERE id = ?2", params![data, id])?;
+                }
+                _ => error!("Can't migrate transactions of block ID {}", id),
+            }
+        }
+        db_transaction.commit()
+    }
+
+    /// Заново заполняет индекс транзакций по уже сохранённым блокам
+    fn index_stored_transactions(&self) -> Result<()> {
+        self.conn.execute("DELETE FROM transactions", [])?;
+        for block in self.get_all_blocks()? {
+            self.index_transactions(&block)?;
+        }
+        Ok(())
+    }
+
+    fn index_transactions(&self, block: &Block) -> Result<()> {
+        let mut stmt = self.conn.prepare("INSERT OR REPLACE INTO transactions (txid, block_id) VALUES (?1, ?2)")?;
+        for transaction in block.get_transactions() {
+            stmt.execute(params![transaction.get_txid(), block.get_id() as i64])?;
+        }
         Ok(())
     }
 
@@ -68,14 +190,15 @@ impl BlockDatabase {
         // ошибки выполнения SQL (например, ошибка диска, нарушение ограничений UNIQUE)
         // и вернет rusqlite::Error в случае неудачи.
         let affected_rows = self.conn.execute(
-            "INSERT INTO blocks (id, time_create, transactions, previous_hash, nonce)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
+            "INSERT INTO blocks (id, time_create, transactions, previous_hash, nonce, target)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
             params![
                 block.get_id() as i64,     // ID блока
                 block.get_datetime(),      // Время создания
                 tx_data,                   // Сериализованные транзакции (BLOB)
                 block.get_previous_hash(), // Хеш предыдущего блока
-                block.get_nonce()          // Nonce
+                block.get_nonce(),         // Nonce
+                block.get_target() as i64  // Цель сложности (битовое представление u64)
             ],
         )?; // Если execute вернет Err, '?' прервет выполнение и вернет эту ошибку
 
@@ -88,15 +211,45 @@ impl BlockDatabase {
             debug!("Block ID {} successfully inserted, affected rows: {}", block.get_id(), affected_rows);
         }
 
+        // 3. Добавляем транзакции блока в индекс для поиска по идентификатору
+        self.index_transactions(block)?;
 
-        // 3. Если оба шага (сериализация и выполнение SQL) прошли успешно, возвращаем Ok
+        // 4. Если все шаги прошли успешно, возвращаем Ok
         Ok(())
     }
 
+    /// Удаляет блоки с высотой `id` и выше (откат основной цепочки при реорганизации)
+    pub fn delete_blocks_from(&self, id: usize) -> Result<()> {
+        let affected_rows = self.conn.execute("DELETE FROM blocks WHERE id >= ?1", params![id as i64])?;
+        debug!("Deleted {} blocks starting from ID {}", affected_rows, id);
+        self.conn.execute("DELETE FROM transactions WHERE block_id >= ?1", params![id as i64])?;
+        Ok(())
+    }
+
+    /// Ищет сохранённую транзакцию по идентификатору.
+    /// Возвращает `None`, если транзакция не входит ни в один сохранённый блок.
+    pub fn find_transaction(&self, txid: &str) -> Result<Option<TransactionLookup>> {
+        let block_id: Option<i64> = self.conn
+            .query_row("SELECT block_id FROM transactions WHERE txid = ?1", params![txid], |row| row.get(0))
+            .optional()?;
+        let Some(block_id) = block_id else {
+            return Ok(None);
+        };
+
+        let block = self.get_block(block_id as usize)?;
+        let tip: i64 = self.conn.query_row("SELECT MAX(id) FROM blocks", [], |row| row.get(0))?;
+        let transaction = block.get_transactions().iter().find(|transaction| transaction.get_txid() == txid).cloned();
+        Ok(transaction.map(|transaction| TransactionLookup {
+            transaction,
+            block,
+            confirmations: (tip - block_id + 1) as usize,
+        }))
+    }
+
     /// Загружает блок по ID
     pub fn get_block(&self, id: usize) -> Result<Block> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, time_create, transactions, previous_hash, nonce FROM blocks WHERE id = ?1"
+            "SELECT id, time_create, transactions, previous_hash, nonce, target FROM blocks WHERE id = ?1"
         )?;
         let mut rows = stmt.query(params![id as i64])?;
 
@@ -122,6 +275,7 @@ impl BlockDatabase {
                 transactions,
                 row.get(3)?,
                 row.get(4)?,
+                row.get::<_, i64>(5)? as u64,
             ))
         } else {
             Err(rusqlite::Error::QueryReturnedNoRows)
@@ -151,4 +305,95 @@ impl BlockDatabase {
         Ok(blocks)
     }
 
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct StoredLegacyTransaction {
+        sender: String,
+        buyer: String,
+        seller: String,
+        message: String,
+        transfer: f64,
+        signature: String,
+    }
+
+    #[test]
+    fn test_migrates_baseline_rows() {
+        use base64::Engine;
+        use base64::engine::general_purpose::STANDARD_NO_PAD;
+        use rsa::pkcs1::EncodeRsaPublicKey;
+        use crate::coin::node::blockchain::crypto::{PrivateKey, SignatureAlgorithm, Signer};
+
+        let path = std::env::temp_dir().join(format!("migrate_baseline_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let key = PrivateKey::generate(SignatureAlgorithm::Rsa).get_public_key();
+        let PublicKey::Rsa(rsa_key) = &key else { unreachable!() };
+        let legacy_key = STANDARD_NO_PAD.encode(rsa_key.to_pkcs1_der().unwrap().as_bytes());
+        let legacy = vec![StoredLegacyTransaction {
+            sender: legacy_key.clone(),
+            buyer: legacy_key,
+            seller: "bob".to_string(),
+            message: "msg".to_string(),
+            transfer: 0.1 + 0.2,
+            signature: "signature".to_string(),
+        }];
+
+        // Исходная схема: без цели сложности, индекса транзакций и версии
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE blocks (id INTEGER PRIMARY KEY, time_create INTEGER NOT NULL, transactions BLOB NOT NULL,
+             previous_hash TEXT NOT NULL, nonce INTEGER NOT NULL);"
+        ).unwrap();
+        conn.execute("INSERT INTO blocks VALUES (1, 0, ?1, 'prev', 0)", params![bincode::serialize(&legacy).unwrap()]).unwrap();
+        drop(conn);
+
+        let db = BlockDatabase::new(path.to_str().unwrap()).unwrap();
+        let blocks = db.get_all_blocks().unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].get_target(), difficulty::INITIAL_TARGET);
+        let transaction = &blocks[0].get_transactions()[0];
+        assert_eq!(transaction.sender, address::from_public_key(&key));
+        assert_eq!(transaction.buyer, transaction.sender);
+        assert_eq!(transaction.seller, "bob");
+        assert_eq!(transaction.public_key, Some(key.encode()));
+        assert_eq!(transaction.get_transfer(), "0.3".parse().unwrap());
+        assert_eq!(transaction.get_fee(), Amount::ZERO);
+        assert_eq!(transaction.signature, "signature");
+        assert!(db.find_transaction(&transaction.get_txid()).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_find_transaction_by_txid() {
+        let path = std::env::temp_dir().join(format!("find_transaction_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db = BlockDatabase::new(path.to_str().unwrap()).unwrap();
+
+        let payment = SerializedTransaction::new(
+            "alice".to_string(),
+            "bob".to_string(),
+            "bob".to_string(),
+            "msg".to_string(),
+            Amount::from_coins(1),
+        );
+        let txid = payment.get_txid();
+        db.insert_block(&Block::force_new(1, 0, vec![], "prev".to_string(), 0, 1)).unwrap();
+        db.insert_block(&Block::force_new(2, 0, vec![payment], "h1".to_string(), 0, 1)).unwrap();
+        db.insert_block(&Block::force_new(3, 0, vec![], "h2".to_string(), 0, 1)).unwrap();
+
+        let lookup = db.find_transaction(&txid).unwrap().expect("Транзакция сохранена");
+        assert_eq!(lookup.block.get_id(), 2);
+        assert_eq!(lookup.confirmations, 2);
+        assert!(db.find_transaction("00").unwrap().is_none());
+
+        // После отката блока транзакция больше не находится
+        db.delete_blocks_from(2).unwrap();
+        assert!(db.find_transaction(&txid).unwrap().is_none());
+        let _ = std::fs::remove_file(&path);
+    }
+}
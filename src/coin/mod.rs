@@ -2,3 +2,4 @@ pub mod server;
 pub mod node;
 pub mod app_state;
 pub mod db;
+pub mod params;
@@ -0,0 +1,209 @@
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::coin::node::blockchain::crypto::PublicKey;
+use crate::coin::node::blockchain::multisig::{is_multisig_address, MULTISIG_PREFIX};
+use crate::coin::node::blockchain::script::{is_script_address, SCRIPT_PREFIX};
+
+/// Версия адреса ключа, первый байт перед хешем
+pub const KEY_ADDRESS_VERSION: u8 = 0;
+/// Размер хеша ключа в адресе, байт
+pub const KEY_HASH_SIZE: usize = 20;
+/// Версия адреса multisig-счёта
+pub const MULTISIG_ADDRESS_VERSION: u8 = 5;
+/// Версия адреса скрипт-счёта
+pub const SCRIPT_ADDRESS_VERSION: u8 = 6;
+/// Размер хеша описания multisig- или скрипт-счёта в адресе, байт
+pub const ACCOUNT_HASH_SIZE: usize = 32;
+/// Размер контрольной суммы адреса, байт
+const CHECKSUM_SIZE: usize = 4;
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum AddressError {
+    #[error("Недопустимый символ Base58: {0}")]
+    InvalidCharacter(char),
+    #[error("Неверная длина адреса {0}")]
+    InvalidLength(String),
+    #[error("Неизвестная версия адреса: {0}")]
+    UnknownVersion(u8),
+    #[error("Контрольная сумма адреса {0} не сходится, адрес введён с ошибкой")]
+    InvalidChecksum(String),
+}
+
+/// Адрес ключа в Base58Check: версия, первые 20 байт SHA-256 канонической записи ключа
+/// и 4 байта двойного SHA-256 от них как контрольная сумма.
+/// Сам ключ раскрывается только в транзакции, которая тратит средства с адреса
+pub fn from_public_key(key: &PublicKey) -> String {
+    encode_check(KEY_ADDRESS_VERSION, &Sha256::digest(key.encode().as_bytes())[..KEY_HASH_SIZE])
+}
+
+/// Адрес multisig-счёта: префикс и Base58Check хеша описания счёта
+pub fn multisig_address(hash: &[u8]) -> String {
+    format!("{}{}", MULTISIG_PREFIX, encode_check(MULTISIG_ADDRESS_VERSION, hash))
+}
+
+/// Адрес скрипт-счёта: префикс и Base58Check хеша запирающего скрипта
+pub fn script_address(hash: &[u8]) -> String {
+    format!("{}{}", SCRIPT_PREFIX, encode_check(SCRIPT_ADDRESS_VERSION, hash))
+}
+
+/// Хеш ключа из адреса; проверяет версию и контрольную сумму
+pub fn decode_key_hash(address: &str) -> Result<[u8; KEY_HASH_SIZE], AddressError> {
+    let hash = decode_check(address, KEY_ADDRESS_VERSION, KEY_HASH_SIZE)?;
+    Ok(hash.try_into().expect("длина проверена в decode_check"))
+}
+
+/// Счёт, на который можно перевести средства: адрес ключа, multisig-счёта или скрипт-счёта.
+/// У всех видов адресов проверяются версия и контрольная сумма, поэтому опечатка не уведёт средства
+/// на счёт, с которого никто не сможет их потратить
+pub fn check_account(account: &str) -> Result<(), AddressError> {
+    if is_multisig_address(account) {
+        decode_check(&account[MULTISIG_PREFIX.len()..], MULTISIG_ADDRESS_VERSION, ACCOUNT_HASH_SIZE).map(|_| ())
+    } else if is_script_address(account) {
+        decode_check(&account[SCRIPT_PREFIX.len()..], SCRIPT_ADDRESS_VERSION, ACCOUNT_HASH_SIZE).map(|_| ())
+    } else {
+        decode_key_hash(account).map(|_| ())
+    }
+}
+
+/// Base58Check: версия, данные и контрольная сумма
+fn encode_check(version: u8, payload: &[u8]) -> String {
+    let mut data = vec![version];
+    data.extend_from_slice(payload);
+    data.extend_from_slice(&checksum(&data));
+    base58_encode(&data)
+}
+
+/// Данные из Base58Check; проверяет длину, контрольную сумму и версию
+fn decode_check(address: &str, version: u8, size: usize) -> Result<Vec<u8>, AddressError> {
+    let bytes = base58_decode(address)?;
+    if bytes.len() != 1 + size + CHECKSUM_SIZE {
+        return Err(AddressError::InvalidLength(address.to_string()));
+    }
+    let (data, sum) = bytes.split_at(1 + size);
+    if checksum(data) != sum {
+        return Err(AddressError::InvalidChecksum(address.to_string()));
+    }
+    if data[0] != version {
+        return Err(AddressError::UnknownVersion(data[0]));
+    }
+    Ok(data[1..].to_vec())
+}
+
+/// Первые 4 байта двойного SHA-256
+fn checksum(data: &[u8]) -> [u8; CHECKSUM_SIZE] {
+    let hash = Sha256::digest(Sha256::digest(data));
+    hash[..CHECKSUM_SIZE].try_into().unwrap()
+}
+
+/// Base58 с алфавитом Bitcoin; ведущие нулевые байты записываются символом `1`
+fn base58_encode(bytes: &[u8]) -> String {
+    let zeros = bytes.iter().take_while(|&&byte| byte == 0).count();
+    // Цифры по основанию 58, младшая первой
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in &bytes[zeros..] {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    let mut encoded = "1".repeat(zeros);
+    encoded.extend(digits.iter().rev().map(|&digit| BASE58_ALPHABET[digit as usize] as char));
+    encoded
+}
+
+fn base58_decode(encoded: &str) -> Result<Vec<u8>, AddressError> {
+    let zeros = encoded.chars().take_while(|&c| c == '1').count();
+    // Байты числа, младший первым
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in encoded.chars().skip(zeros) {
+        let mut carry = BASE58_ALPHABET
+            .iter()
+            .position(|&symbol| symbol as char == c)
+            .ok_or(AddressError::InvalidCharacter(c))? as u32;
+        for byte in bytes.iter_mut() {
+            carry += (*byte as u32) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+
+    let mut decoded = vec![0; zeros];
+    decoded.extend(bytes.iter().rev());
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coin::node::blockchain::crypto::{PrivateKey, SignatureAlgorithm, Signer};
+
+    #[test]
+    fn test_base58_round_trip() {
+        assert_eq!(base58_encode(b"hello world"), "StV1DL6CwTryKyV");
+        assert_eq!(base58_encode(&[0, 0, 1]), "112");
+        for bytes in [&b""[..], &[0, 0, 1], b"hello world", &[255; 25]] {
+            assert_eq!(base58_decode(&base58_encode(bytes)).unwrap(), bytes);
+        }
+        assert_eq!(base58_decode("0OIl"), Err(AddressError::InvalidCharacter('0')));
+    }
+
+    #[test]
+    fn test_key_address_detects_typos() {
+        let key = PrivateKey::generate(SignatureAlgorithm::Ed25519).get_public_key();
+        let address = from_public_key(&key);
+        assert!(address.len() < key.encode().len());
+        assert_eq!(decode_key_hash(&address).unwrap(), Sha256::digest(key.encode().as_bytes())[..KEY_HASH_SIZE]);
+        assert_eq!(check_account(&address), Ok(()));
+
+        // Замена любого символа ломает контрольную сумму
+        for position in 0..address.len() {
+            let mut typo: Vec<char> = address.chars().collect();
+            typo[position] = if typo[position] == 'z' { 'y' } else { 'z' };
+            let typo: String = typo.into_iter().collect();
+            assert!(decode_key_hash(&typo).is_err(), "опечатка в позиции {} не замечена", position);
+        }
+        assert!(matches!(check_account(&key.encode()), Err(AddressError::InvalidCharacter(_))));
+    }
+
+    #[test]
+    fn test_check_account_accepts_multisig_and_script() {
+        let hash = [0xab; ACCOUNT_HASH_SIZE];
+        assert_eq!(check_account(&multisig_address(&hash)), Ok(()));
+        assert_eq!(check_account(&script_address(&hash)), Ok(()));
+        // Версия адреса привязана к префиксу
+        let script = script_address(&hash);
+        let swapped = format!("{}{}", MULTISIG_PREFIX, &script[SCRIPT_PREFIX.len()..]);
+        assert_eq!(check_account(&swapped), Err(AddressError::UnknownVersion(SCRIPT_ADDRESS_VERSION)));
+        assert!(matches!(check_account("multisig:abc"), Err(AddressError::InvalidLength(_))));
+        // Прежняя запись хеша шестнадцатеричными цифрами без контрольной суммы не принимается
+        assert!(matches!(check_account(&format!("{}{}", SCRIPT_PREFIX, "ab".repeat(32))), Err(AddressError::InvalidLength(_))));
+    }
+
+    #[test]
+    fn test_multisig_and_script_addresses_detect_typos() {
+        let hash = [0x5c; ACCOUNT_HASH_SIZE];
+        for (prefix, address) in [(MULTISIG_PREFIX, multisig_address(&hash)), (SCRIPT_PREFIX, script_address(&hash))] {
+            let encoded: Vec<char> = address[prefix.len()..].chars().collect();
+            for position in 0..encoded.len() {
+                let mut typo = encoded.clone();
+                typo[position] = if typo[position] == 'z' { 'y' } else { 'z' };
+                let typo = format!("{}{}", prefix, typo.into_iter().collect::<String>());
+                assert!(check_account(&typo).is_err(), "опечатка в позиции {} адреса {} не замечена", position, address);
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt;
+use std::iter::Sum;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Число знаков после запятой в десятичной записи суммы
+pub const DECIMALS: u32 = 8;
+/// Количество базовых единиц в одной монете
+pub const COIN: u64 = 10u64.pow(DECIMALS);
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum AmountError {
+    #[error("Пустая сумма")]
+    Empty,
+    #[error("Некорректная запись суммы: {0}")]
+    InvalidFormat(String),
+    #[error("Больше {decimals} знаков после запятой: {0}", decimals = DECIMALS)]
+    TooPrecise(String),
+    #[error("Сумма слишком велика: {0}")]
+    Overflow(String),
+}
+
+/// Неотрицательная сумма в базовых единицах (1 монета = `COIN` единиц).
+/// Хранится и сериализуется целым числом, поэтому балансы не накапливают ошибок округления.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Amount(u64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+
+    pub const fn from_base_units(units: u64) -> Amount {
+        Amount(units)
+    }
+
+    pub const fn from_coins(coins: u64) -> Amount {
+        Amount(coins * COIN)
+    }
+
+    /// Перевод суммы в монетах из старого формата с плавающей точкой.
+    /// Округляет до базовой единицы; отрицательные, нечисловые и слишком большие значения отклоняются.
+    pub fn from_coins_f64(coins: f64) -> Option<Amount> {
+        let units = (coins * COIN as f64).round();
+        if !units.is_finite() || units < 0f64 || units >= u64::MAX as f64 {
+            return None;
+        }
+        Some(Amount(units as u64))
+    }
+
+    pub fn get_base_units(&self) -> u64 {
+        self.0
+    }
+
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0.checked_add(other.0).map(Amount)
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        self.0.checked_sub(other.0).map(Amount)
+    }
+
+    pub fn saturating_add(self, other: Amount) -> Amount {
+        Amount(self.0.saturating_add(other.0))
+    }
+
+    pub fn saturating_sub(self, other: Amount) -> Amount {
+        Amount(self.0.saturating_sub(other.0))
+    }
+
+    /// Сумма последовательности; `None` при переполнении
+    pub fn checked_sum<I: IntoIterator<Item = Amount>>(amounts: I) -> Option<Amount> {
+        amounts.into_iter().try_fold(Amount::ZERO, Amount::checked_add)
+    }
+}
+
+/// Суммирование с насыщением: используется там, где переполнение затем отклоняет другая проверка
+impl Sum for Amount {
+    fn sum<I: Iterator<Item = Amount>>(iter: I) -> Amount {
+        iter.fold(Amount::ZERO, Amount::saturating_add)
+    }
+}
+
+/// Десятичная запись в монетах без лишних нулей: `12.5`, `0.00000001`, `3`
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let whole = self.0 / COIN;
+        let fraction = self.0 % COIN;
+        if fraction == 0 {
+            return write!(f, "{}", whole);
+        }
+        let fraction = format!("{:0width$}", fraction, width = DECIMALS as usize);
+        write!(f, "{}.{}", whole, fraction.trim_end_matches('0'))
+    }
+}
+
+impl FromStr for Amount {
+    type Err = AmountError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(AmountError::Empty);
+        }
+
+        let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+        let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) || (text.contains('.') && fraction.is_empty()) {
+            return Err(AmountError::InvalidFormat(text.to_string()));
+        }
+        if fraction.len() > DECIMALS as usize {
+            return Err(AmountError::TooPrecise(text.to_string()));
+        }
+
+        let overflow = || AmountError::Overflow(text.to_string());
+        let whole: u64 = whole.parse().map_err(|_| overflow())?;
+        let fraction: u64 = if fraction.is_empty() {
+            0
+        } else {
+            format!("{:0<width$}", fraction, width = DECIMALS as usize).parse().map_err(|_| overflow())?
+        };
+
+        whole
+            .checked_mul(COIN)
+            .and_then(|units| units.checked_add(fraction))
+            .map(Amount)
+            .ok_or_else(overflow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_and_parse_roundtrip() {
+        let cases = [
+            ("0", Amount::ZERO),
+            ("3", Amount::from_coins(3)),
+            ("12.5", Amount::from_base_units(12 * COIN + COIN / 2)),
+            ("0.00000001", Amount::from_base_units(1)),
+        ];
+        for (text, amount) in cases {
+            assert_eq!(text.parse::<Amount>(), Ok(amount));
+            assert_eq!(amount.to_string(), text);
+        }
+        assert_eq!("1.50".parse::<Amount>(), Ok(Amount::from_base_units(150_000_000)));
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_input() {
+        assert_eq!("".parse::<Amount>(), Err(AmountError::Empty));
+        for text in ["-1", "1.", ".5", "1,5", "abc", "1.2.3"] {
+            assert!(matches!(text.parse::<Amount>(), Err(AmountError::InvalidFormat(_))), "{}", text);
+        }
+        assert!(matches!("0.000000001".parse::<Amount>(), Err(AmountError::TooPrecise(_))));
+        assert!(matches!("184467440738".parse::<Amount>(), Err(AmountError::Overflow(_))));
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let one = Amount::from_coins(1);
+        let max = Amount::from_base_units(u64::MAX);
+        assert_eq!(one.checked_add(one), Some(Amount::from_coins(2)));
+        assert_eq!(Amount::ZERO.checked_sub(one), None);
+        assert_eq!(max.checked_add(Amount::from_base_units(1)), None);
+        assert_eq!(Amount::checked_sum([max, one]), None);
+        assert_eq!([max, one].into_iter().sum::<Amount>(), max);
+    }
+
+    #[test]
+    fn test_from_coins_f64() {
+        assert_eq!(Amount::from_coins_f64(0.1 + 0.2), Some(Amount::from_base_units(30_000_000)));
+        assert_eq!(Amount::from_coins_f64(-1.0), None);
+        assert_eq!(Amount::from_coins_f64(f64::NAN), None);
+    }
+}
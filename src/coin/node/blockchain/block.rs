@@ -5,24 +5,79 @@ use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha512};
 
-use crate::coin::node::blockchain::transaction::SerializedTransaction;
+use crate::coin::node::blockchain::difficulty;
+use crate::coin::node::blockchain::merkle::{self, MerkleProof};
+use crate::coin::node::blockchain::transaction::{write_bytes, SerializedTransaction};
+
+/// Версия формата заголовка блока
+pub const BLOCK_VERSION: u32 = 1;
+
+/// Заголовок блока: все поля, влияющие на консенсус. Хеш блока — это хеш заголовка.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlockHeader {
+    version: u32,
+    height: usize,
+    time_create: i64,
+    previous_hash: String,
+    transactions_root: String,
+    target: u64,
+    nonce: u64,
+}
+
+impl BlockHeader {
+    /// Каноническое байтовое представление заголовка:
+    /// целые числа в big-endian, строки с префиксом длины (u32, big-endian)
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(48 + self.previous_hash.len() + self.transactions_root.len());
+        bytes.extend_from_slice(&self.version.to_be_bytes());
+        bytes.extend_from_slice(&(self.height as u64).to_be_bytes());
+        bytes.extend_from_slice(&self.time_create.to_be_bytes());
+        write_bytes(&mut bytes, self.previous_hash.as_bytes());
+        write_bytes(&mut bytes, self.transactions_root.as_bytes());
+        bytes.extend_from_slice(&self.target.to_be_bytes());
+        bytes.extend_from_slice(&self.nonce.to_be_bytes());
+        bytes
+    }
+
+    fn hash_bytes(&self) -> Vec<u8> {
+        let mut hasher = Sha512::new();
+        hasher.update(self.encode());
+        hasher.finalize().to_vec()
+    }
+
+    pub fn get_hash(&self) -> String {
+        merkle::to_hex(&self.hash_bytes())
+    }
+
+    /// Проверяет доказательство включения транзакции по одному заголовку, без тела блока
+    pub fn verify_transaction(&self, transaction_hash: &str, proof: &MerkleProof) -> bool {
+        merkle::verify_proof(transaction_hash, proof, &self.transactions_root)
+    }
+}
+
+fn transaction_hashes(transactions: &[SerializedTransaction]) -> Vec<String> {
+    transactions.iter().map(|transaction| transaction.get_txid()).collect()
+}
+
+/// Корень дерева Меркла по хешам транзакций блока
+pub fn transactions_root(transactions: &[SerializedTransaction]) -> String {
+    merkle::merkle_root(&transaction_hashes(transactions))
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Block{
-    id:usize,
-    time_create: i64,
+    header: BlockHeader,
     transactions: Vec<SerializedTransaction>,
-    previous_hash: String,
-    nonce: u64
 }
 impl Block{
     pub fn new(
         id:usize,
         transactions: Vec<SerializedTransaction>,
         previous_hash:String,
-        nonce:u64
+        nonce:u64,
+        target:u64,
     ) -> Block{
-        Block{ id, time_create: Utc::now().timestamp(), transactions, previous_hash, nonce}
+        Block::force_new(id, Utc::now().timestamp(), transactions, previous_hash, nonce, target)
     }
 
     pub fn force_new(
@@ -30,22 +85,56 @@ impl Block{
          time_create: i64,
          transactions: Vec<SerializedTransaction>,
          previous_hash:String,
-         nonce:u64
+         nonce:u64,
+         target:u64,
     ) -> Block{
-        Block{ id, time_create, transactions, previous_hash, nonce}
+        let header = BlockHeader {
+            version: BLOCK_VERSION,
+            height: id,
+            time_create,
+            previous_hash,
+            transactions_root: transactions_root(&transactions),
+            target,
+            nonce,
+        };
+        Block{ header, transactions }
     }
 
     pub fn get_hash(&self) ->String{
-        let mut hasher = Sha512::new();
-        hasher.update(format!("{}_{:?}_{}/{}", self.id, self.transactions, self.previous_hash, self.nonce ));
+        self.header.get_hash()
+    }
+
+    pub fn get_header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    /// Доказательство включения транзакции с хешем `transaction_hash` в этот блок
+    pub fn merkle_proof(&self, transaction_hash: &str) -> Option<MerkleProof> {
+        let hashes = transaction_hashes(&self.transactions);
+        let index = hashes.iter().position(|hash| hash == transaction_hash)?;
+        merkle::build_proof(&hashes, index)
+    }
+
+    /// Проверка доказательства работы относительно цели, записанной в блоке
+    pub fn meets_target(&self) -> bool {
+        difficulty::hash_meets_target(&self.header.hash_bytes(), self.header.target)
+    }
 
-        let result = hasher.finalize();
-        // Преобразование результата хэширования в строку
-        format!("{:x}", result)
+    /// Совпадает ли корень транзакций в заголовке с фактическими транзакциями
+    pub fn has_valid_transactions_root(&self) -> bool {
+        self.header.transactions_root == transactions_root(&self.transactions)
     }
 
     pub fn get_nonce(&self)->u64{
-        self.nonce
+        self.header.nonce
+    }
+
+    pub fn set_nonce(&mut self, nonce: u64) {
+        self.header.nonce = nonce;
+    }
+
+    pub fn get_target(&self) -> u64 {
+        self.header.target
     }
 
     pub fn to_json(&self) -> String{
@@ -53,19 +142,19 @@ impl Block{
     }
 
     pub fn get_previous_hash(&self) -> String {
-        self.previous_hash.clone()
+        self.header.previous_hash.clone()
     }
 
     pub fn get_id(&self) -> usize{
-        self.id
+        self.header.height
     }
 
     pub fn get_datetime(&self) -> i64 {
-        self.time_create
+        self.header.time_create
     }
 
     pub fn set_previous_hash(&mut self, last_hash:String){
-        self.previous_hash = last_hash;
+        self.header.previous_hash = last_hash;
     }
 
     pub fn get_transactions(&self) -> &Vec<SerializedTransaction> {
@@ -75,9 +164,9 @@ impl Block{
 
 impl fmt::Display for Block{
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "id: {}\ntime_create: {}\nprevious_hash: {}\nnonce: {}",
-               self.id, self.time_create, self.previous_hash,
-               self.nonce)
+        write!(f, "id: {}\nversion: {}\ntime_create: {}\nprevious_hash: {}\ntransactions_root: {}\nnonce: {}\ntarget: {:016x}",
+               self.header.height, self.header.version, self.header.time_create, self.header.previous_hash,
+               self.header.transactions_root, self.header.nonce, self.header.target)
     }
 }
 
@@ -93,14 +182,14 @@ mod tests {
             "seller_base64".to_string(),
             "buyer_base64".to_string(),
             "Test message".to_string(),
-            123.45,
+            "123.45".parse().unwrap(),
         )
     }
 
     #[test]
     fn test_block_creation() {
         let transactions = vec![sample_transaction()];
-        let block = Block::new(1, transactions.clone(), "prev_hash".to_string(), 42);
+        let block = Block::new(1, transactions.clone(), "prev_hash".to_string(), 42, difficulty::INITIAL_TARGET);
 
         assert_eq!(block.get_id(), 1);
         assert_eq!(block.get_nonce(), 42);
@@ -111,7 +200,7 @@ mod tests {
     #[test]
     fn test_block_hash_is_deterministic() {
         let transactions = vec![sample_transaction()];
-        let block = Block::new(2, transactions, "abc123".to_string(), 999);
+        let block = Block::new(2, transactions, "abc123".to_string(), 999, difficulty::INITIAL_TARGET);
 
         let hash1 = block.get_hash();
         let hash2 = block.get_hash();
@@ -119,10 +208,82 @@ mod tests {
         assert_eq!(hash1, hash2, "Хэш должен быть одинаков при одинаковом содержимом");
     }
 
+    #[test]
+    fn test_block_meets_own_target() {
+        let mut nonce = 0;
+        let block = loop {
+            let block = Block::new(2, vec![], "abc123".to_string(), nonce, difficulty::INITIAL_TARGET);
+            if block.meets_target() {
+                break block;
+            }
+            nonce += 1;
+        };
+
+        assert!(block.get_hash().starts_with("000"));
+        // Та же работа не удовлетворяет более сложной цели
+        let harder = Block::new(2, vec![], "abc123".to_string(), nonce, 1);
+        assert!(!harder.meets_target());
+    }
+
+    #[test]
+    fn test_hash_covers_timestamp_and_target() {
+        let block = Block::force_new(2, 1_700_000_000, vec![sample_transaction()], "prev".to_string(), 7, difficulty::INITIAL_TARGET);
+
+        let other_time = Block::force_new(2, 1_700_000_001, vec![sample_transaction()], "prev".to_string(), 7, difficulty::INITIAL_TARGET);
+        assert_ne!(block.get_hash(), other_time.get_hash(), "Время создания должно входить в хеш");
+
+        let other_target = Block::force_new(2, 1_700_000_000, vec![sample_transaction()], "prev".to_string(), 7, difficulty::MAX_TARGET);
+        assert_ne!(block.get_hash(), other_target.get_hash(), "Цель должна входить в хеш");
+    }
+
+    #[test]
+    fn test_header_encoding_is_canonical() {
+        let block = Block::force_new(2, 1_700_000_000, vec![], "ab".to_string(), 7, 1);
+        let encoded = block.header.encode();
+
+        assert_eq!(&encoded[..4], &BLOCK_VERSION.to_be_bytes());
+        assert_eq!(&encoded[4..12], &2u64.to_be_bytes());
+        assert_eq!(&encoded[12..20], &1_700_000_000i64.to_be_bytes());
+        assert_eq!(&encoded[20..26], &[0, 0, 0, 2, b'a', b'b']);
+        assert_eq!(&encoded[encoded.len() - 8..], &7u64.to_be_bytes());
+    }
+
+    #[test]
+    fn test_transactions_root_detects_tampering() {
+        let block = Block::new(2, vec![sample_transaction()], "prev".to_string(), 0, difficulty::INITIAL_TARGET);
+        assert!(block.has_valid_transactions_root());
+
+        let mut json: serde_json::Value = serde_json::from_str(&block.to_json()).unwrap();
+        json["transactions"][0]["transfer"] = serde_json::json!(99_900_000_000u64);
+        let tampered: Block = serde_json::from_value(json).unwrap();
+
+        assert!(!tampered.has_valid_transactions_root());
+        assert_eq!(block.get_hash(), tampered.get_hash());
+    }
+
+    #[test]
+    fn test_merkle_proof_against_header() {
+        let mut second = sample_transaction();
+        second.message = "Second".to_string();
+        let mut third = sample_transaction();
+        third.message = "Third".to_string();
+        let block = Block::new(2, vec![sample_transaction(), second.clone(), third], "prev".to_string(), 0, difficulty::INITIAL_TARGET);
+
+        let proof = block.merkle_proof(&second.get_txid()).expect("Транзакция есть в блоке");
+        // Заголовка достаточно для проверки
+        let header = block.get_header().clone();
+        assert!(header.verify_transaction(&second.get_txid(), &proof));
+
+        let mut absent = sample_transaction();
+        absent.message = "Absent".to_string();
+        assert!(block.merkle_proof(&absent.get_txid()).is_none());
+        assert!(!header.verify_transaction(&absent.get_txid(), &proof));
+    }
+
     #[test]
     fn test_block_json_serialization() {
         let transactions = vec![sample_transaction()];
-        let block = Block::new(3, transactions, "prev".to_string(), 777);
+        let block = Block::new(3, transactions, "prev".to_string(), 777, difficulty::INITIAL_TARGET);
 
         let json = block.to_json();
         let deserialized: Block = serde_json::from_str(&json).expect("Ошибка десериализации");
@@ -134,7 +295,7 @@ mod tests {
 
     #[test]
     fn test_block_set_previous_hash() {
-        let mut block = Block::new(4, vec![], "old_hash".to_string(), 0);
+        let mut block = Block::new(4, vec![], "old_hash".to_string(), 0, difficulty::INITIAL_TARGET);
         block.set_previous_hash("new_hash".to_string());
 
         assert_eq!(block.get_previous_hash(), "new_hash");
@@ -142,7 +303,7 @@ mod tests {
 
     #[test]
     fn test_block_display() {
-        let block = Block::new(5, vec![], "hash123".to_string(), 12345);
+        let block = Block::new(5, vec![], "hash123".to_string(), 12345, difficulty::INITIAL_TARGET);
         let output = format!("{}", block);
 
         assert!(output.contains("id: 5"));
@@ -1,40 +1,228 @@
-use sha2::{Digest, Sha512};
+use std::collections::HashMap;
+
+use chrono::Utc;
+use log::{debug, info, warn};
+
+use crate::coin::node::blockchain::amount::Amount;
+use crate::coin::node::blockchain::block::{Block, BlockHeader};
+use crate::coin::node::blockchain::difficulty;
+use crate::coin::node::blockchain::ledger::Ledger;
+use crate::coin::node::blockchain::merkle::MerkleProof;
+use crate::coin::node::blockchain::orphan::{OrphanPool, MAX_ORPHANS};
+use crate::coin::node::blockchain::transaction::SerializedTransaction;
+use crate::coin::node::blockchain::validation::{self, BlockValidationError};
+use crate::coin::params::ChainParams;
+
+/// Результат добавления блока
+#[derive(Debug, Clone)]
+pub enum ChainUpdate {
+    /// Блок продолжил основную цепочку
+    Extended(Block),
+    /// Ветка с новым блоком набрала больше работы и стала основной:
+    /// `disconnected` сняты с основной цепочки, `connected` добавлены (в порядке высоты)
+    Reorganized { disconnected: Vec<Block>, connected: Vec<Block> },
+    /// Блок сохранён в боковой ветке, основная цепочка не изменилась
+    SideBranch,
+    /// Родитель блока неизвестен, блок ждёт его в пуле сирот
+    Orphan { missing_parent: String },
+}
+
+/// Известный узлу блок и суммарная работа цепочки, которую он завершает
+struct IndexEntry {
+    block: Block,
+    total_work: u128,
+}
 
-use crate::coin::node::blockchain::block::Block;
+/// Транзакция основной цепочки, блок с ней и глубина подтверждения
+/// (1 — транзакция в последнем блоке)
+#[derive(Debug, Clone)]
+pub struct TransactionLookup {
+    pub transaction: SerializedTransaction,
+    pub block: Block,
+    pub confirmations: usize,
+}
 
 pub struct Blockchain {
     pub chain: Vec<Block>,
+    ledger: Ledger,
+    // Все известные блоки основной и боковых веток по хешу
+    index: HashMap<String, IndexEntry>,
+    orphans: OrphanPool,
+    params: ChainParams,
     nonce_iteration: u64,
 }
 impl Blockchain {
     pub fn new() -> Blockchain {
+        Blockchain::with_params(ChainParams::default())
+    }
+
+    pub fn with_params(params: ChainParams) -> Blockchain {
         Blockchain {
             chain: Vec::new(),
+            ledger: Ledger::new(),
+            index: HashMap::new(),
+            orphans: OrphanPool::new(MAX_ORPHANS),
+            params,
             nonce_iteration: 0,
         }
     }
 
-    pub fn add_block(&mut self, block: Block) -> Result<Block, String> {
-        let block = block;
-        if !Blockchain::is_valid_block(&block) {
-            return Err("Hash didn't valid".to_string())
+    /// Заменяет цепочку (например, загруженную из БД) и пересобирает реестр балансов.
+    /// Цепочка всегда начинается с первого блока сети, остальные блоки проходят полную проверку.
+    /// Блоки после первого некорректного отбрасываются.
+    pub fn load_chain(&mut self, chain: Vec<Block>) -> Result<(), BlockValidationError> {
+        self.chain = Vec::with_capacity(chain.len());
+        self.ledger = Ledger::new();
+        self.index.clear();
+        self.create_first_block();
+
+        let genesis_hash = self.get_genesis_hash();
+        let now = Utc::now().timestamp();
+        for block in chain {
+            if block.get_id() == 1 {
+                if block.get_hash() != genesis_hash {
+                    return Err(BlockValidationError::GenesisMismatch { expected: genesis_hash, actual: block.get_hash() });
+                }
+                continue;
+            }
+            validation::validate_block(&block, &self.chain, &self.params, now)?;
+            self.ledger.apply_block(&block)?;
+            self.push_to_chain(block);
+        }
+        Ok(())
+    }
+
+    /// Добавляет блок в основную цепочку или в боковую ветку.
+    /// Если ветка блока набирает больше суммарной работы, чем основная, цепочка перестраивается на неё.
+    pub fn add_block(&mut self, block: Block) -> Result<ChainUpdate, BlockValidationError> {
+        // Заголовок проверяется до всего остального, чтобы не хранить сирот без работы
+        validation::check_header(&block, Utc::now().timestamp())?;
+        if self.chain.is_empty() {
+            return Err(BlockValidationError::EmptyChain);
         }
-        if let Ok(last_block) = self.get_last_block() {
-            if block.get_previous_hash() == last_block.get_hash() {
-                self.chain.push(block.clone());
-                Ok(block)
-            } else {
-                Err("Хеши не совпадают".to_string())
+
+        let hash = block.get_hash();
+        if self.index.contains_key(&hash) {
+            return Err(BlockValidationError::AlreadyKnown(hash));
+        }
+        let parent_work = match self.index.get(&block.get_previous_hash()) {
+            Some(parent) => parent.total_work,
+            None => {
+                // Цель сироты нельзя сверить с его предками, поэтому она не должна быть легче
+                // текущей цели цепочки: иначе пул сирот заполняется блоками почти без работы
+                let required = self.get_next_target();
+                if block.get_target() > required {
+                    return Err(BlockValidationError::OrphanTargetTooEasy { required, actual: block.get_target() });
+                }
+                let missing_parent = block.get_previous_hash();
+                if !self.orphans.insert(block) {
+                    return Err(BlockValidationError::AlreadyKnown(hash));
+                }
+                debug!("Block {} waits for parent {}, orphans: {}", hash, missing_parent, self.orphans.len());
+                return Ok(ChainUpdate::Orphan { missing_parent });
             }
-        } else {
-            Err("chain is empty".to_string())
+        };
+
+        let ancestors = self.get_branch(&block.get_previous_hash());
+        validation::check_context(&block, &ancestors, &self.params)?;
+        validation::check_transactions(&block, &self.params)?;
+
+        let total_work = parent_work + difficulty::block_work(block.get_target());
+        if block.get_previous_hash() == self.chain.last().map(|tip| tip.get_hash()).unwrap_or_default() {
+            self.ledger.apply_block(&block)?;
+            self.push_to_chain(block.clone());
+            return Ok(ChainUpdate::Extended(block));
+        }
+
+        if total_work <= self.get_total_work() {
+            self.index.insert(hash, IndexEntry { block, total_work });
+            return Ok(ChainUpdate::SideBranch);
         }
+
+        let mut branch = ancestors;
+        branch.push(block.clone());
+        let (disconnected, connected) = self.reorganize(branch)?;
+        self.index.insert(hash, IndexEntry { block, total_work });
+        info!(
+            "Chain reorganized: {} blocks disconnected, {} connected",
+            disconnected.len(),
+            connected.len()
+        );
+        Ok(ChainUpdate::Reorganized { disconnected, connected })
     }
 
-    pub fn add_force_block(&mut self, block: Block) {
+    /// Добавляет блок на вершину основной цепочки и в индекс
+    fn push_to_chain(&mut self, block: Block) {
+        let total_work = self.get_total_work() + difficulty::block_work(block.get_target());
+        self.index.insert(block.get_hash(), IndexEntry { block: block.clone(), total_work });
         self.chain.push(block);
     }
 
+    /// Переключает основную цепочку на `branch`, заново применяя транзакции начиная с точки расхождения.
+    /// Если ветка не проходит проверку реестра, цепочка остаётся прежней.
+    fn reorganize(&mut self, branch: Vec<Block>) -> Result<(Vec<Block>, Vec<Block>), BlockValidationError> {
+        let fork_point = self
+            .chain
+            .iter()
+            .zip(branch.iter())
+            .take_while(|(current, candidate)| current.get_hash() == candidate.get_hash())
+            .count();
+
+        let mut ledger = Ledger::new();
+        for (index, block) in branch.iter().enumerate() {
+            if index == 0 {
+                ledger.apply_genesis(block)?;
+            } else {
+                ledger.apply_block(block)?;
+            }
+        }
+
+        let disconnected = self.chain.split_off(fork_point);
+        let connected = branch[fork_point..].to_vec();
+        self.chain.extend(connected.iter().cloned());
+        self.ledger = ledger;
+        Ok((disconnected, connected))
+    }
+
+    /// Блоки от первого известного предка до блока с хешем `hash` включительно
+    fn get_branch(&self, hash: &str) -> Vec<Block> {
+        let mut branch = Vec::new();
+        let mut current = hash.to_string();
+        while let Some(entry) = self.index.get(&current) {
+            current = entry.block.get_previous_hash();
+            branch.push(entry.block.clone());
+        }
+        branch.reverse();
+        branch
+    }
+
+    /// Суммарная работа основной цепочки
+    pub fn get_total_work(&self) -> u128 {
+        self.chain
+            .last()
+            .and_then(|tip| self.index.get(&tip.get_hash()))
+            .map(|entry| entry.total_work)
+            .unwrap_or(0)
+    }
+
+    /// Забирает блоки-сироты, ожидавшие блок `parent_hash`
+    pub fn take_orphans(&mut self, parent_hash: &str) -> Vec<Block> {
+        self.orphans.take_children(parent_hash)
+    }
+
+    /// Блок основной или боковой ветки по хешу
+    pub fn get_block_by_hash(&self, hash: &str) -> Option<Block> {
+        self.index.get(hash).map(|entry| entry.block.clone())
+    }
+
+    pub fn get_ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    pub fn get_balance(&self, account: &str) -> Amount {
+        self.ledger.get_balance(account)
+    }
+
     pub fn get_last_block(&self) -> Result<Block, &'static str> {
         if let Some(block) = self.chain.last() {
             Ok(block.clone())
@@ -43,15 +231,36 @@ impl Blockchain {
         }
     }
 
+    /// Добавляет первый блок сети, описанный `GenesisConfig`, в пустую цепочку
     pub fn create_first_block(&mut self) {
-        let word = "First block";
-        let mut hasher = Sha512::new();
-        hasher.update(word);
-        let result = hasher.finalize();
-        let hex_string = format!("{:x}", result);
+        if !self.chain.is_empty() {
+            warn!("First block already exists");
+            return;
+        }
+        let block = self.params.genesis.build();
+        if let Err(e) = self.ledger.apply_genesis(&block) {
+            warn!("Genesis allocations are not reflected in ledger: {}", e);
+        }
+        self.push_to_chain(block);
+    }
+
+    /// Хеш первого блока сети; узлы с разными первыми блоками несовместимы
+    pub fn get_genesis_hash(&self) -> String {
+        self.params.genesis.get_hash()
+    }
+
+    pub fn get_params(&self) -> &ChainParams {
+        &self.params
+    }
+
+    /// Цель, которой должен удовлетворять следующий блок цепочки
+    pub fn get_next_target(&self) -> u64 {
+        difficulty::next_target(&self.chain, self.params.max_target)
+    }
 
-        let block = Block::new(1, Vec::new(), hex_string, 0);
-        self.add_force_block(block);
+    /// Высота следующего блока основной цепочки
+    pub fn get_next_height(&self) -> usize {
+        self.chain.last().map_or(1, |block| block.get_id() + 1)
     }
 
     pub fn len(&self) -> usize {
@@ -59,7 +268,7 @@ impl Blockchain {
     }
 
     pub fn is_valid_block(block: &Block) -> bool {
-        block.get_hash().starts_with("000")
+        block.meets_target()
     }
 
     pub fn get_blocks_after(&self, datetime: i64) -> Vec<Block> {
@@ -78,6 +287,29 @@ impl Blockchain {
             .collect()
     }
 
+    /// Заголовок блока с транзакцией и доказательство её включения
+    pub fn get_merkle_proof(&self, transaction_hash: &str) -> Option<(BlockHeader, MerkleProof)> {
+        self.chain.iter().find_map(|block| {
+            block.merkle_proof(transaction_hash)
+                .map(|proof| (block.get_header().clone(), proof))
+        })
+    }
+
+    /// Поиск транзакции основной цепочки по идентификатору; новые блоки просматриваются первыми
+    pub fn get_transaction(&self, txid: &str) -> Option<TransactionLookup> {
+        let tip = self.chain.last()?.get_id();
+        self.chain.iter().rev().find_map(|block| {
+            block.get_transactions()
+                .iter()
+                .find(|transaction| transaction.get_txid() == txid)
+                .map(|transaction| TransactionLookup {
+                    transaction: transaction.clone(),
+                    block: block.clone(),
+                    confirmations: tip - block.get_id() + 1,
+                })
+        })
+    }
+
     pub fn get_full_chain(&self) -> Vec<Block>{
         self.chain.clone()
     }
@@ -95,7 +327,8 @@ impl Blockchain {
     }
 }
 
-pub fn validate_chain(new_chain: &Vec<Block>) -> bool {
+pub fn validate_chain(new_chain: &Vec<Block>, params: &ChainParams) -> bool {
+    let now = Utc::now().timestamp();
     for i in 1..new_chain.len() {
         let current_block = &new_chain[i];
         let previous_block = &new_chain[i - 1];
@@ -105,8 +338,13 @@ pub fn validate_chain(new_chain: &Vec<Block>) -> bool {
             return false;
         }
 
-        // Дополнительная проверка хешей и PoW
-        if !Blockchain::is_valid_block(current_block) {
+        // Дополнительная проверка хешей, PoW, размера и времени
+        if validation::check_header(current_block, now).is_err() {
+            return false;
+        }
+
+        // Цель блока должна следовать правилу пересчёта сложности
+        if current_block.get_target() != difficulty::next_target(&new_chain[..i], params.max_target) {
             return false;
         }
     }
@@ -116,30 +354,88 @@ pub fn validate_chain(new_chain: &Vec<Block>) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::{Utc};
     use std::thread::sleep;
     use std::time::Duration as StdDuration;
-    use crate::coin::node::blockchain::transaction::SerializedTransaction;
+    use std::sync::OnceLock;
+    use sha2::{Digest, Sha256};
+    use crate::coin::node::blockchain::crypto::{PrivateKey, SignatureAlgorithm, Signer};
+    use crate::coin::node::blockchain::genesis::GenesisConfig;
+    use crate::coin::node::blockchain::ledger::LedgerError;
+    use crate::coin::node::blockchain::multisig::MultisigPolicy;
+    use crate::coin::node::blockchain::escrow::EscrowError;
+    use crate::coin::node::blockchain::htlc::{hashlock, HtlcError};
+    use crate::coin::node::blockchain::emission;
+    use crate::coin::node::blockchain::address;
+    use crate::coin::node::blockchain::transaction::{decode_public_key, Transaction, TransactionKind};
+
+    struct TestKeys {
+        sender_private: PrivateKey,
+        sender: String,
+        seller: String,
+    }
+
+    /// Ключи отправителя и продавца общие для всех тестов
+    fn test_keys() -> &'static TestKeys {
+        static KEYS: OnceLock<TestKeys> = OnceLock::new();
+        KEYS.get_or_init(|| {
+            let encode = |key: &PrivateKey| key.get_public_key().encode();
+            let sender_private = PrivateKey::generate(SignatureAlgorithm::Ed25519);
+            let seller_private = PrivateKey::generate(SignatureAlgorithm::Ed25519);
+            TestKeys {
+                sender: encode(&sender_private),
+                seller: encode(&seller_private),
+                sender_private,
+            }
+        })
+    }
+
+    fn sender_key() -> String {
+        test_keys().sender.clone()
+    }
+
+    fn seller_key() -> String {
+        test_keys().seller.clone()
+    }
+
+    fn sender_address() -> String {
+        address::from_public_key(&decode_public_key(&test_keys().sender).unwrap())
+    }
 
-    // Функция для создания тестовой транзакции.
+    fn seller_address() -> String {
+        address::from_public_key(&decode_public_key(&test_keys().seller).unwrap())
+    }
+
+    // Функция для создания тестовой подписанной транзакции.
     fn sample_transactions() -> Vec<SerializedTransaction> {
-        vec![
-            SerializedTransaction::new(
-                "sender_base64".to_string(),
-                "seller_base64".to_string(),
-                "buyer_base64".to_string(),
-                "Test message".to_string(),
-                123.45,
-            )
-        ]
-    }
-
-    /// Функция для «майнинга» блока — подбирается значение nonce, при котором хэш блока начинается с "000".
+        let mut transaction = Transaction::new(sender_key(), seller_address(), seller_address(), "Test message".to_string(), "123.45".parse().unwrap());
+        transaction.sign(&test_keys().sender_private, ChainParams::main().chain_id);
+        vec![transaction.serialize()]
+    }
+
+    /// Добавляет coinbase-транзакцию с корректной наградой в начало списка
+    fn with_coinbase(height: usize, transactions: Vec<SerializedTransaction>) -> Vec<SerializedTransaction> {
+        with_coinbase_to("miner", height, transactions)
+    }
+
+    /// Корректный адрес награды тестового майнера `name`
+    fn miner_address(name: &str) -> String {
+        address::script_address(&Sha256::digest(name.as_bytes()))
+    }
+
+    fn with_coinbase_to(miner: &str, height: usize, transactions: Vec<SerializedTransaction>) -> Vec<SerializedTransaction> {
+        let mut block_transactions = vec![emission::coinbase_transaction(&miner_address(miner), height, &transactions)];
+        block_transactions.extend(transactions);
+        block_transactions
+    }
+
+    /// Функция для «майнинга» блока — подбирается значение nonce, при котором хэш блока удовлетворяет цели.
+    /// Время блока растёт с высотой, чтобы блоки одной секунды были позже медианы предков
     fn mine_valid_block(id: usize, transactions: Vec<SerializedTransaction>, previous_hash: String) -> Block {
+        let time = Utc::now().timestamp() + id as i64;
         let mut nonce = 0;
         loop {
-            let block = Block::new(id, transactions.clone(), previous_hash.clone(), nonce);
-            if Block::get_hash(&block).starts_with("000") {
+            let block = Block::force_new(id, time, transactions.clone(), previous_hash.clone(), nonce, difficulty::INITIAL_TARGET);
+            if block.meets_target() {
                 return block;
             }
             nonce += 1;
@@ -164,12 +460,340 @@ mod tests {
 
         let last_block = blockchain.get_last_block().unwrap();
         let prev_hash = last_block.get_hash();
-        let transactions = sample_transactions();
+        let transactions = with_coinbase(last_block.get_id() + 1, sample_transactions());
+        blockchain.ledger.credit(&sender_address(), Amount::from_coins(200)).unwrap();
         let new_block = mine_valid_block(last_block.get_id() + 1, transactions, prev_hash);
 
         let result = blockchain.add_block(new_block.clone());
         assert!(result.is_ok(), "Блок должен быть добавлен в цепочку");
         assert_eq!(blockchain.len(), 2);
+        assert_eq!(blockchain.get_balance(&seller_address()), "123.45".parse().unwrap());
+        assert_eq!(blockchain.get_balance(&miner_address("miner")), emission::block_subsidy(2));
+    }
+
+    #[test]
+    fn test_add_block_rejects_wrong_target() {
+        let mut blockchain = Blockchain::new();
+        blockchain.create_first_block();
+
+        let last_block = blockchain.get_last_block().unwrap();
+        let height = last_block.get_id() + 1;
+        let transactions = with_coinbase(height, Vec::new());
+
+        // Блок с более лёгкой целью, чем требует цепочка
+        let mut nonce = 0;
+        let easy_block = loop {
+            let block = Block::new(height, transactions.clone(), last_block.get_hash(), nonce, difficulty::MAX_TARGET);
+            if block.meets_target() {
+                break block;
+            }
+            nonce += 1;
+        };
+
+        assert!(blockchain.add_block(easy_block).is_err(), "Блок с чужой целью не должен быть добавлен");
+    }
+
+    #[test]
+    fn test_add_block_requires_coinbase() {
+        let mut blockchain = Blockchain::new();
+        blockchain.create_first_block();
+        blockchain.ledger.credit(&sender_address(), Amount::from_coins(200)).unwrap();
+
+        let last_block = blockchain.get_last_block().unwrap();
+        let new_block = mine_valid_block(last_block.get_id() + 1, sample_transactions(), last_block.get_hash());
+
+        assert!(blockchain.add_block(new_block).is_err(), "Блок без coinbase не должен быть добавлен");
+    }
+
+    #[test]
+    fn test_add_block_rejects_wrong_coinbase_amount() {
+        let mut blockchain = Blockchain::new();
+        blockchain.create_first_block();
+
+        let last_block = blockchain.get_last_block().unwrap();
+        let height = last_block.get_id() + 1;
+        let coinbase = SerializedTransaction::coinbase(miner_address("miner"), height, Amount::from_base_units(emission::block_subsidy(height).get_base_units() * 2));
+        let new_block = mine_valid_block(height, vec![coinbase], last_block.get_hash());
+
+        assert!(blockchain.add_block(new_block).is_err(), "Завышенная награда должна быть отклонена");
+        assert_eq!(blockchain.get_balance(&miner_address("miner")), Amount::ZERO);
+    }
+
+    #[test]
+    fn test_add_block_rejects_overspend() {
+        let mut blockchain = Blockchain::new();
+        blockchain.create_first_block();
+
+        let last_block = blockchain.get_last_block().unwrap();
+        // У отправителя нет средств
+        let transactions = with_coinbase(last_block.get_id() + 1, sample_transactions());
+        let new_block = mine_valid_block(last_block.get_id() + 1, transactions, last_block.get_hash());
+
+        let result = blockchain.add_block(new_block);
+        assert!(result.is_err(), "Блок с перерасходом не должен быть добавлен");
+        assert_eq!(blockchain.len(), 1);
+    }
+
+    /// Пустой блок с coinbase для `miner` поверх `parent`
+    fn mine_child(parent: &Block, miner: &str) -> Block {
+        let height = parent.get_id() + 1;
+        mine_valid_block(height, with_coinbase_to(miner, height, Vec::new()), parent.get_hash())
+    }
+
+    #[test]
+    fn test_lighter_branch_is_kept_aside() {
+        let mut blockchain = Blockchain::new();
+        blockchain.create_first_block();
+        let genesis = blockchain.get_last_block().unwrap();
+
+        let main_block = mine_child(&genesis, "miner_a");
+        assert!(matches!(blockchain.add_block(main_block.clone()), Ok(ChainUpdate::Extended(_))));
+
+        // Ветка той же длины не набирает больше работы
+        let side_block = mine_child(&genesis, "miner_b");
+        assert!(matches!(blockchain.add_block(side_block.clone()), Ok(ChainUpdate::SideBranch)));
+        assert_eq!(blockchain.get_last_block().unwrap().get_hash(), main_block.get_hash());
+        assert!(blockchain.get_block_by_hash(&side_block.get_hash()).is_some());
+        assert_eq!(blockchain.get_balance(&miner_address("miner_b")), Amount::ZERO);
+
+        assert!(blockchain.add_block(side_block).is_err(), "Повторный блок не должен быть добавлен");
+    }
+
+    #[test]
+    fn test_heavier_branch_triggers_reorganization() {
+        let mut blockchain = Blockchain::new();
+        blockchain.create_first_block();
+        let genesis = blockchain.get_last_block().unwrap();
+
+        let main_block = mine_child(&genesis, "miner_a");
+        blockchain.add_block(main_block.clone()).unwrap();
+        let work_before = blockchain.get_total_work();
+
+        let side_first = mine_child(&genesis, "miner_b");
+        let side_second = mine_child(&side_first, "miner_b");
+        blockchain.add_block(side_first.clone()).unwrap();
+
+        match blockchain.add_block(side_second.clone()) {
+            Ok(ChainUpdate::Reorganized { disconnected, connected }) => {
+                let hashes = |blocks: &[Block]| blocks.iter().map(Block::get_hash).collect::<Vec<_>>();
+                assert_eq!(hashes(&disconnected), vec![main_block.get_hash()]);
+                assert_eq!(hashes(&connected), vec![side_first.get_hash(), side_second.get_hash()]);
+            }
+            other => panic!("Ожидалась реорганизация, получено {:?}", other),
+        }
+
+        assert_eq!(blockchain.len(), 3);
+        assert_eq!(blockchain.get_last_block().unwrap().get_hash(), side_second.get_hash());
+        assert!(blockchain.get_total_work() > work_before);
+        // Реестр пересобран по новой ветке
+        assert_eq!(blockchain.get_balance(&miner_address("miner_a")), Amount::ZERO);
+        assert_eq!(blockchain.get_balance(&miner_address("miner_b")), emission::block_subsidy(2).checked_add(emission::block_subsidy(3)).unwrap());
+    }
+
+    #[test]
+    fn test_orphan_connected_after_parent() {
+        let mut blockchain = Blockchain::new();
+        blockchain.create_first_block();
+
+        let genesis = blockchain.get_last_block().unwrap();
+        let parent = mine_child(&genesis, "miner");
+        let orphan = mine_child(&parent, "miner");
+
+        match blockchain.add_block(orphan.clone()) {
+            Ok(ChainUpdate::Orphan { missing_parent }) => assert_eq!(missing_parent, parent.get_hash()),
+            other => panic!("Блок без родителя должен ждать в пуле сирот, получено {:?}", other),
+        }
+        assert_eq!(blockchain.len(), 1);
+
+        assert!(matches!(blockchain.add_block(parent.clone()), Ok(ChainUpdate::Extended(_))));
+        let orphans = blockchain.take_orphans(&parent.get_hash());
+        assert_eq!(orphans.len(), 1);
+        assert!(matches!(blockchain.add_block(orphans[0].clone()), Ok(ChainUpdate::Extended(_))));
+        assert_eq!(blockchain.get_last_block().unwrap().get_hash(), orphan.get_hash());
+    }
+
+    #[test]
+    fn test_orphan_with_easy_target_rejected() {
+        let mut blockchain = Blockchain::new();
+        blockchain.create_first_block();
+
+        // Блок с самой лёгкой целью и неизвестным родителем не попадает в пул сирот
+        let transactions = with_coinbase(3, Vec::new());
+        let orphan = (0..)
+            .map(|nonce| Block::force_new(3, Utc::now().timestamp(), transactions.clone(), "unknown".to_string(), nonce, difficulty::MAX_TARGET))
+            .find(Block::meets_target)
+            .unwrap();
+
+        assert!(matches!(
+            blockchain.add_block(orphan),
+            Err(BlockValidationError::OrphanTargetTooEasy { actual: difficulty::MAX_TARGET, .. })
+        ));
+        assert!(blockchain.take_orphans("unknown").is_empty());
+    }
+
+    #[test]
+    fn test_load_chain_rejects_foreign_genesis() {
+        let foreign = ChainParams::test().genesis;
+
+        let mut blockchain = Blockchain::new();
+        let result = blockchain.load_chain(vec![foreign.build()]);
+
+        assert!(matches!(result, Err(BlockValidationError::GenesisMismatch { .. })));
+        assert_eq!(blockchain.len(), 1);
+        assert_eq!(blockchain.get_last_block().unwrap().get_hash(), blockchain.get_genesis_hash());
+    }
+
+    #[test]
+    fn test_genesis_allocations_can_be_spent() {
+        let genesis = GenesisConfig { allocations: vec![(sender_address(), Amount::from_coins(500))], ..GenesisConfig::default() };
+        let mut blockchain = Blockchain::with_params(ChainParams { genesis, ..ChainParams::main() });
+        blockchain.create_first_block();
+        assert_eq!(blockchain.get_balance(&sender_address()), Amount::from_coins(500));
+
+        let last_block = blockchain.get_last_block().unwrap();
+        let transactions = with_coinbase(2, sample_transactions());
+        let new_block = mine_valid_block(2, transactions, last_block.get_hash());
+
+        assert!(blockchain.add_block(new_block).is_ok(), "Выделенные средства должны быть доступны");
+        assert_eq!(blockchain.get_balance(&sender_address()), "376.55".parse().unwrap());
+    }
+
+    #[test]
+    fn test_spend_from_multisig_account() {
+        let mut blockchain = Blockchain::new();
+        blockchain.create_first_block();
+        let policy = MultisigPolicy::new(1, vec![sender_key(), seller_key()]).unwrap();
+        blockchain.ledger.credit(&policy.get_address(), Amount::from_coins(10)).unwrap();
+
+        let mut transaction = Transaction::new_multisig(policy.clone(), seller_address(), seller_address(), "Treasury".to_string(), Amount::from_coins(4)).unwrap();
+        transaction.sign(&test_keys().sender_private, blockchain.get_params().chain_id);
+
+        let last_block = blockchain.get_last_block().unwrap();
+        let block = mine_valid_block(2, with_coinbase(2, vec![transaction.serialize()]), last_block.get_hash());
+        blockchain.add_block(block).unwrap();
+
+        assert_eq!(blockchain.get_balance(&policy.get_address()), Amount::from_coins(6));
+        assert_eq!(blockchain.get_balance(&seller_address()), Amount::from_coins(4));
+        assert_eq!(blockchain.get_ledger().get_nonce(&policy.get_address()), 1);
+    }
+
+    #[test]
+    fn test_escrow_transitions_checked_in_blocks() {
+        let mut blockchain = Blockchain::new();
+        blockchain.create_first_block();
+        blockchain.ledger.credit(&sender_address(), Amount::from_coins(50)).unwrap();
+        let chain_id = blockchain.get_params().chain_id;
+        let escrow_transaction = |kind: TransactionKind, nonce: u64| {
+            let mut transaction = Transaction::new(sender_key(), seller_address(), sender_address(), "Escrow".to_string(), Amount::from_coins(20));
+            transaction.set_kind(kind);
+            transaction.set_nonce(nonce);
+            transaction.sign(&test_keys().sender_private, chain_id);
+            transaction.serialize()
+        };
+
+        let lock = escrow_transaction(TransactionKind::EscrowLock { timeout: 100 }, 0);
+        let escrow_id = lock.get_txid();
+        let genesis = blockchain.get_last_block().unwrap();
+        let locked = mine_valid_block(2, with_coinbase(2, vec![lock]), genesis.get_hash());
+        blockchain.add_block(locked.clone()).unwrap();
+        assert_eq!(blockchain.get_balance(&sender_address()), Amount::from_coins(30));
+
+        // Забрать средства до срока нельзя
+        let reclaim = escrow_transaction(TransactionKind::EscrowReclaim { escrow_id: escrow_id.clone() }, 1);
+        let early = mine_valid_block(3, with_coinbase(3, vec![reclaim]), locked.get_hash());
+        assert!(matches!(
+            blockchain.add_block(early),
+            Err(BlockValidationError::Ledger(LedgerError::Escrow(EscrowError::NotExpired { timeout: 100, .. })))
+        ));
+
+        let release = escrow_transaction(TransactionKind::EscrowRelease { escrow_id }, 1);
+        let released = mine_valid_block(3, with_coinbase(3, vec![release]), locked.get_hash());
+        blockchain.add_block(released).unwrap();
+        assert_eq!(blockchain.get_balance(&seller_address()), Amount::from_coins(20));
+    }
+
+    #[test]
+    fn test_htlc_hashlock_and_timelock_checked_in_blocks() {
+        let mut blockchain = Blockchain::new();
+        blockchain.create_first_block();
+        blockchain.ledger.credit(&sender_address(), Amount::from_coins(50)).unwrap();
+        let chain_id = blockchain.get_params().chain_id;
+        let htlc_transaction = |kind: TransactionKind, nonce: u64| {
+            let mut transaction = Transaction::new(sender_key(), seller_address(), sender_address(), "Swap".to_string(), Amount::from_coins(20));
+            transaction.set_kind(kind);
+            transaction.set_nonce(nonce);
+            transaction.sign(&test_keys().sender_private, chain_id);
+            transaction.serialize()
+        };
+
+        let lock = htlc_transaction(TransactionKind::HtlcLock { hashlock: hashlock(b"secret"), timeout: 4 }, 0);
+        let htlc_id = lock.get_txid();
+        let genesis = blockchain.get_last_block().unwrap();
+        let locked = mine_valid_block(2, with_coinbase(2, vec![lock]), genesis.get_hash());
+        blockchain.add_block(locked.clone()).unwrap();
+        assert_eq!(blockchain.get_balance(&sender_address()), Amount::from_coins(30));
+
+        // До срока вернуть средства нельзя
+        let refund = htlc_transaction(TransactionKind::HtlcRefund { htlc_id: htlc_id.clone() }, 1);
+        let early = mine_valid_block(3, with_coinbase(3, vec![refund.clone()]), locked.get_hash());
+        assert!(matches!(
+            blockchain.add_block(early),
+            Err(BlockValidationError::Ledger(LedgerError::Htlc(HtlcError::NotExpired { timeout: 4, .. })))
+        ));
+
+        let empty = mine_valid_block(3, with_coinbase(3, vec![]), locked.get_hash());
+        blockchain.add_block(empty.clone()).unwrap();
+        let refunded = mine_valid_block(4, with_coinbase(4, vec![refund]), empty.get_hash());
+        blockchain.add_block(refunded).unwrap();
+        assert_eq!(blockchain.get_balance(&sender_address()), Amount::from_coins(50));
+    }
+
+    #[test]
+    fn test_regtest_block_needs_no_work() {
+        let mut blockchain = Blockchain::with_params(ChainParams::regtest());
+        blockchain.create_first_block();
+
+        let last_block = blockchain.get_last_block().unwrap();
+        let block = Block::new(2, with_coinbase(2, Vec::new()), last_block.get_hash(), 0, blockchain.get_next_target());
+
+        assert!(matches!(blockchain.add_block(block), Ok(ChainUpdate::Extended(_))), "В regtest блок принимается без перебора nonce");
+    }
+
+    #[test]
+    fn test_load_chain_stops_at_overspending_block() {
+        let mut blockchain = Blockchain::new();
+        blockchain.create_first_block();
+        blockchain.ledger.credit(&sender_address(), Amount::from_coins(200)).unwrap();
+
+        let last_block = blockchain.get_last_block().unwrap();
+        let transactions = with_coinbase(last_block.get_id() + 1, sample_transactions());
+        let new_block = mine_valid_block(last_block.get_id() + 1, transactions, last_block.get_hash());
+        let chain = vec![last_block, new_block];
+
+        // Без начального баланса второй блок не применяется и отбрасывается
+        let mut restored = Blockchain::new();
+        assert!(restored.load_chain(chain).is_err());
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored.get_balance(&seller_address()), Amount::ZERO);
+    }
+
+    #[test]
+    fn test_replayed_transaction_rejected() {
+        let mut blockchain = Blockchain::new();
+        blockchain.create_first_block();
+        blockchain.ledger.credit(&sender_address(), Amount::from_coins(500)).unwrap();
+
+        let last_block = blockchain.get_last_block().unwrap();
+        let first = mine_valid_block(2, with_coinbase(2, sample_transactions()), last_block.get_hash());
+        assert!(blockchain.add_block(first.clone()).is_ok());
+
+        // Та же подписанная транзакция в следующем блоке
+        let replay = mine_valid_block(3, with_coinbase(3, sample_transactions()), first.get_hash());
+        assert!(matches!(
+            blockchain.add_block(replay),
+            Err(BlockValidationError::Ledger(LedgerError::NonceTooLow { expected: 1, actual: 0, .. }))
+        ));
+        assert_eq!(blockchain.get_balance(&seller_address()), "123.45".parse().unwrap());
     }
 
     #[test]
@@ -180,12 +804,12 @@ mod tests {
         let last_block = blockchain.get_last_block().unwrap();
         let transactions = sample_transactions();
 
-        // Создадим блок с nonce, который, вероятно, не даст валидного хэша (хэш не начинается с "000")
-        let mut invalid_block = Block::new(last_block.get_id() + 1, transactions.clone(), last_block.get_hash(), 0);
+        // Создадим блок с nonce, который, вероятно, не даст валидного хэша (хэш не удовлетворяет цели)
+        let mut invalid_block = Block::new(last_block.get_id() + 1, transactions.clone(), last_block.get_hash(), 0, difficulty::INITIAL_TARGET);
         // Если случайно получилось валидное значение, форсированно изменим nonce, чтобы хэш не удовлетворял условию
-        if Block::get_hash(&invalid_block).starts_with("000") {
-            invalid_block = Block::new(last_block.get_id() + 1, transactions, last_block.get_hash(), 9999);
-            assert!(!Block::get_hash(&invalid_block).starts_with("000"));
+        if invalid_block.meets_target() {
+            invalid_block = Block::new(last_block.get_id() + 1, transactions, last_block.get_hash(), 9999, difficulty::INITIAL_TARGET);
+            assert!(!invalid_block.meets_target());
         }
 
         let result = blockchain.add_block(invalid_block);
@@ -202,8 +826,7 @@ mod tests {
         sleep(StdDuration::from_secs(1));
 
         let last_block = blockchain.get_last_block().unwrap();
-        let new_block = mine_valid_block(last_block.get_id() + 1, sample_transactions(), last_block.get_hash());
-        blockchain.add_force_block(new_block.clone());
+        blockchain.add_block(mine_child(&last_block, "miner")).unwrap();
         let new_block_time = blockchain.chain.last().unwrap().get_datetime();
 
         // Получаем блоки, созданные после времени первого блока
@@ -225,8 +848,7 @@ mod tests {
         // Добавляем ещё несколько блоков в цепочку
         for _ in 2..6 {
             let last_block = blockchain.get_last_block().unwrap();
-            let new_block = mine_valid_block(last_block.get_id() + 1, sample_transactions(), last_block.get_hash());
-            blockchain.add_force_block(new_block);
+            blockchain.add_block(mine_child(&last_block, "miner")).unwrap();
         }
 
         let n = 3;
@@ -234,6 +856,47 @@ mod tests {
         assert_eq!(last_n.len(), n, "Функция должна вернуть ровно {} блоков", n);
     }
 
+    #[test]
+    fn test_get_merkle_proof() {
+        let mut blockchain = Blockchain::new();
+        blockchain.create_first_block();
+        blockchain.ledger.credit(&sender_address(), Amount::from_coins(200)).unwrap();
+
+        let last_block = blockchain.get_last_block().unwrap();
+        let transactions = with_coinbase(last_block.get_id() + 1, sample_transactions());
+        let transaction_hash = transactions[1].get_txid();
+        let new_block = mine_valid_block(last_block.get_id() + 1, transactions, last_block.get_hash());
+        blockchain.add_block(new_block.clone()).unwrap();
+
+        let (header, proof) = blockchain.get_merkle_proof(&transaction_hash).expect("Транзакция в цепочке");
+        assert_eq!(header.get_hash(), new_block.get_hash());
+        assert!(header.verify_transaction(&transaction_hash, &proof));
+
+        assert!(blockchain.get_merkle_proof("00").is_none());
+    }
+
+    #[test]
+    fn test_get_transaction_reports_confirmations() {
+        let mut blockchain = Blockchain::new();
+        blockchain.create_first_block();
+        blockchain.ledger.credit(&sender_address(), Amount::from_coins(200)).unwrap();
+
+        let genesis = blockchain.get_last_block().unwrap();
+        let transactions = with_coinbase(2, sample_transactions());
+        let txid = transactions[1].get_txid();
+        let block = mine_valid_block(2, transactions, genesis.get_hash());
+        blockchain.add_block(block.clone()).unwrap();
+
+        let lookup = blockchain.get_transaction(&txid).expect("Транзакция в цепочке");
+        assert_eq!(lookup.block.get_hash(), block.get_hash());
+        assert_eq!(lookup.transaction.get_txid(), txid);
+        assert_eq!(lookup.confirmations, 1);
+
+        blockchain.add_block(mine_child(&block, "miner")).unwrap();
+        assert_eq!(blockchain.get_transaction(&txid).unwrap().confirmations, 2);
+        assert!(blockchain.get_transaction("00").is_none());
+    }
+
     #[test]
     fn test_clear_nonce() {
         let mut blockchain = Blockchain::new();
@@ -246,20 +909,19 @@ mod tests {
         let mut blockchain = Blockchain::new();
         blockchain.create_first_block();
 
-        // Построим валидную цепочку с использованием add_force_block
+        // Построим валидную цепочку из проверенных блоков
         for _ in 0..3 {
             let last_block = blockchain.get_last_block().unwrap();
-            let new_block = mine_valid_block(last_block.get_id() + 1, sample_transactions(), last_block.get_hash());
-            blockchain.add_force_block(new_block);
+            blockchain.add_block(mine_child(&last_block, "miner")).unwrap();
         }
         // Валидная цепочка должна пройти проверку
-        assert!(validate_chain(&blockchain.chain), "Цепочка должна быть валидной");
+        assert!(validate_chain(&blockchain.chain, blockchain.get_params()), "Цепочка должна быть валидной");
 
         // Нарушим цепочку: изменим поле previous_hash одного из блоков
         let mut invalid_chain = blockchain.chain.clone();
         if let Some(block) = invalid_chain.get_mut(2) {
             block.set_previous_hash("fake_hash".to_string());
         }
-        assert!(!validate_chain(&invalid_chain), "Цепочка с нарушенными ссылками должна быть невалидной");
+        assert!(!validate_chain(&invalid_chain, blockchain.get_params()), "Цепочка с нарушенными ссылками должна быть невалидной");
     }
 }
\ No newline at end of file
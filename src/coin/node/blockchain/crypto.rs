@@ -0,0 +1,391 @@
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use rand::rngs::OsRng;
+use rsa::{PaddingScheme, RsaPrivateKey, RsaPublicKey};
+use rsa::pkcs1::{DecodeRsaPublicKey, EncodeRsaPublicKey};
+use rsa::pkcs8::{DecodePrivateKey, EncodePrivateKey};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// Размер RSA-ключа новых кошельков, бит
+pub const RSA_KEY_BITS: usize = 2048;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum KeyError {
+    #[error("Неизвестный алгоритм подписи: {0}")]
+    UnknownAlgorithm(String),
+    #[error("Ключ или подпись без метки алгоритма: {0}")]
+    MissingTag(String),
+    #[error("Некорректный ключ {algorithm}: {reason}")]
+    InvalidKey { algorithm: SignatureAlgorithm, reason: String },
+    #[error("Ключ записан не в каноническом виде: {0}")]
+    NonCanonical(String),
+}
+
+/// Алгоритм подписи. Его метка стоит перед ключом и подписью: `<метка>:<base64>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SignatureAlgorithm {
+    /// RSA, PKCS#1 v1.5 с SHA-256; ключ — PKCS#1 DER
+    Rsa,
+    /// Ed25519; ключ — 32 байта
+    #[default]
+    Ed25519,
+    /// ECDSA на secp256k1 с SHA-256; ключ — сжатая точка SEC1, 33 байта
+    Secp256k1,
+}
+
+impl SignatureAlgorithm {
+    pub fn get_tag(&self) -> &'static str {
+        match self {
+            SignatureAlgorithm::Rsa => "rsa",
+            SignatureAlgorithm::Ed25519 => "ed25519",
+            SignatureAlgorithm::Secp256k1 => "secp256k1",
+        }
+    }
+}
+
+impl FromStr for SignatureAlgorithm {
+    type Err = KeyError;
+
+    fn from_str(tag: &str) -> Result<Self, Self::Err> {
+        match tag.trim().to_lowercase().as_str() {
+            "rsa" => Ok(SignatureAlgorithm::Rsa),
+            "ed25519" => Ok(SignatureAlgorithm::Ed25519),
+            "secp256k1" => Ok(SignatureAlgorithm::Secp256k1),
+            _ => Err(KeyError::UnknownAlgorithm(tag.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for SignatureAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.get_tag())
+    }
+}
+
+/// Подписывающий ключ
+pub trait Signer {
+    fn get_algorithm(&self) -> SignatureAlgorithm;
+
+    fn get_public_key(&self) -> PublicKey;
+
+    /// Подпись данных без метки алгоритма
+    fn sign_raw(&self, payload: &[u8]) -> Vec<u8>;
+
+    /// Подпись данных с меткой алгоритма
+    fn sign(&self, payload: &[u8]) -> String {
+        encode_tagged(self.get_algorithm(), &self.sign_raw(payload))
+    }
+}
+
+/// Ключ, проверяющий подписи
+pub trait Verifier {
+    fn get_algorithm(&self) -> SignatureAlgorithm;
+
+    /// Проверка подписи без метки алгоритма
+    fn verify_raw(&self, payload: &[u8], signature: &[u8]) -> bool;
+
+    /// Проверка подписи с меткой; метка должна совпадать с алгоритмом ключа
+    fn verify(&self, payload: &[u8], signature: &str) -> bool {
+        match decode_tagged(signature) {
+            Ok((algorithm, bytes)) => algorithm == self.get_algorithm() && self.verify_raw(payload, &bytes),
+            Err(_) => false,
+        }
+    }
+}
+
+impl Signer for RsaPrivateKey {
+    fn get_algorithm(&self) -> SignatureAlgorithm {
+        SignatureAlgorithm::Rsa
+    }
+
+    fn get_public_key(&self) -> PublicKey {
+        PublicKey::Rsa(self.to_public_key())
+    }
+
+    fn sign_raw(&self, payload: &[u8]) -> Vec<u8> {
+        let padding = PaddingScheme::new_pkcs1v15_sign::<Sha256>();
+        RsaPrivateKey::sign(self, padding, &Sha256::digest(payload)).expect("Не удалось подписать сообщение")
+    }
+}
+
+impl Verifier for RsaPublicKey {
+    fn get_algorithm(&self) -> SignatureAlgorithm {
+        SignatureAlgorithm::Rsa
+    }
+
+    fn verify_raw(&self, payload: &[u8], signature: &[u8]) -> bool {
+        let padding = PaddingScheme::new_pkcs1v15_sign::<Sha256>();
+        rsa::PublicKey::verify(self, padding, &Sha256::digest(payload), signature).is_ok()
+    }
+}
+
+impl Signer for ed25519_dalek::SigningKey {
+    fn get_algorithm(&self) -> SignatureAlgorithm {
+        SignatureAlgorithm::Ed25519
+    }
+
+    fn get_public_key(&self) -> PublicKey {
+        PublicKey::Ed25519(self.verifying_key())
+    }
+
+    fn sign_raw(&self, payload: &[u8]) -> Vec<u8> {
+        ed25519_dalek::Signer::sign(self, payload).to_bytes().to_vec()
+    }
+}
+
+impl Verifier for ed25519_dalek::VerifyingKey {
+    fn get_algorithm(&self) -> SignatureAlgorithm {
+        SignatureAlgorithm::Ed25519
+    }
+
+    fn verify_raw(&self, payload: &[u8], signature: &[u8]) -> bool {
+        // Строгая проверка отклоняет изменённые, но формально верные подписи
+        ed25519_dalek::Signature::from_slice(signature).is_ok_and(|signature| self.verify_strict(payload, &signature).is_ok())
+    }
+}
+
+impl Signer for k256::ecdsa::SigningKey {
+    fn get_algorithm(&self) -> SignatureAlgorithm {
+        SignatureAlgorithm::Secp256k1
+    }
+
+    fn get_public_key(&self) -> PublicKey {
+        PublicKey::Secp256k1(*self.verifying_key())
+    }
+
+    fn sign_raw(&self, payload: &[u8]) -> Vec<u8> {
+        let signature: k256::ecdsa::Signature = k256::ecdsa::signature::Signer::sign(self, payload);
+        signature.to_bytes().to_vec()
+    }
+}
+
+impl Verifier for k256::ecdsa::VerifyingKey {
+    fn get_algorithm(&self) -> SignatureAlgorithm {
+        SignatureAlgorithm::Secp256k1
+    }
+
+    fn verify_raw(&self, payload: &[u8], signature: &[u8]) -> bool {
+        // Подписи с большим s не принимаются, поэтому у сообщения одна подпись на ключ и nonce
+        k256::ecdsa::Signature::from_slice(signature)
+            .is_ok_and(|signature| signature.normalize_s().is_none() && k256::ecdsa::signature::Verifier::verify(self, payload, &signature).is_ok())
+    }
+}
+
+/// Публичный ключ одного из поддерживаемых алгоритмов
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublicKey {
+    Rsa(RsaPublicKey),
+    Ed25519(ed25519_dalek::VerifyingKey),
+    Secp256k1(k256::ecdsa::VerifyingKey),
+}
+
+impl PublicKey {
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            PublicKey::Rsa(key) => key.to_pkcs1_der().unwrap().as_bytes().to_vec(),
+            PublicKey::Ed25519(key) => key.to_bytes().to_vec(),
+            PublicKey::Secp256k1(key) => key.to_encoded_point(true).as_bytes().to_vec(),
+        }
+    }
+
+    /// Каноническое представление: `<метка алгоритма>:<base64 ключа>`
+    pub fn encode(&self) -> String {
+        encode_tagged(Verifier::get_algorithm(self), &self.to_bytes())
+    }
+
+    /// Ключ из канонического представления. Другие записи того же ключа не принимаются,
+    /// иначе у одного ключа было бы несколько счетов в реестре
+    pub fn decode(encoded: &str) -> Result<PublicKey, KeyError> {
+        let (algorithm, bytes) = decode_tagged(encoded)?;
+        let invalid = |reason: String| KeyError::InvalidKey { algorithm, reason };
+        let key = match algorithm {
+            SignatureAlgorithm::Rsa => RsaPublicKey::from_pkcs1_der(&bytes).map(PublicKey::Rsa).map_err(|e| invalid(e.to_string()))?,
+            SignatureAlgorithm::Ed25519 => {
+                let bytes: [u8; 32] = bytes.try_into().map_err(|_| invalid("ожидалось 32 байта".to_string()))?;
+                ed25519_dalek::VerifyingKey::from_bytes(&bytes).map(PublicKey::Ed25519).map_err(|e| invalid(e.to_string()))?
+            }
+            SignatureAlgorithm::Secp256k1 => {
+                k256::ecdsa::VerifyingKey::from_sec1_bytes(&bytes).map(PublicKey::Secp256k1).map_err(|e| invalid(e.to_string()))?
+            }
+        };
+        if key.encode() != encoded.trim() {
+            return Err(KeyError::NonCanonical(encoded.to_string()));
+        }
+        Ok(key)
+    }
+
+    /// Ключ из сохранённой записи: каноническое представление или, в старых записях, RSA без метки алгоритма
+    pub fn decode_stored(stored: &str) -> Result<PublicKey, KeyError> {
+        PublicKey::decode(stored).or_else(|e| {
+            STANDARD_NO_PAD
+                .decode(stored.trim())
+                .ok()
+                .and_then(|der| RsaPublicKey::from_pkcs1_der(&der).ok())
+                .map(PublicKey::Rsa)
+                .ok_or(e)
+        })
+    }
+}
+
+impl Verifier for PublicKey {
+    fn get_algorithm(&self) -> SignatureAlgorithm {
+        match self {
+            PublicKey::Rsa(key) => key.get_algorithm(),
+            PublicKey::Ed25519(key) => key.get_algorithm(),
+            PublicKey::Secp256k1(key) => key.get_algorithm(),
+        }
+    }
+
+    fn verify_raw(&self, payload: &[u8], signature: &[u8]) -> bool {
+        match self {
+            PublicKey::Rsa(key) => key.verify_raw(payload, signature),
+            PublicKey::Ed25519(key) => key.verify_raw(payload, signature),
+            PublicKey::Secp256k1(key) => key.verify_raw(payload, signature),
+        }
+    }
+}
+
+/// Закрытый ключ одного из поддерживаемых алгоритмов
+#[derive(Debug, Clone)]
+pub enum PrivateKey {
+    Rsa(RsaPrivateKey),
+    Ed25519(ed25519_dalek::SigningKey),
+    Secp256k1(k256::ecdsa::SigningKey),
+}
+
+impl PrivateKey {
+    pub fn generate(algorithm: SignatureAlgorithm) -> PrivateKey {
+        match algorithm {
+            SignatureAlgorithm::Rsa => PrivateKey::Rsa(RsaPrivateKey::new(&mut OsRng, RSA_KEY_BITS).expect("Не удалось сгенерировать ключ")),
+            SignatureAlgorithm::Ed25519 => PrivateKey::Ed25519(ed25519_dalek::SigningKey::generate(&mut OsRng)),
+            SignatureAlgorithm::Secp256k1 => PrivateKey::Secp256k1(k256::ecdsa::SigningKey::random(&mut OsRng)),
+        }
+    }
+
+    /// Представление для хранения: `<метка алгоритма>:<base64>`;
+    /// RSA — PKCS#8 DER, Ed25519 — 32 байта seed, secp256k1 — 32 байта скаляра
+    pub fn encode(&self) -> String {
+        let bytes = match self {
+            PrivateKey::Rsa(key) => key.to_pkcs8_der().unwrap().as_bytes().to_vec(),
+            PrivateKey::Ed25519(key) => key.to_bytes().to_vec(),
+            PrivateKey::Secp256k1(key) => key.to_bytes().to_vec(),
+        };
+        encode_tagged(Signer::get_algorithm(self), &bytes)
+    }
+
+    pub fn decode(encoded: &str) -> Result<PrivateKey, KeyError> {
+        let (algorithm, bytes) = decode_tagged(encoded)?;
+        let invalid = |reason: String| KeyError::InvalidKey { algorithm, reason };
+        match algorithm {
+            SignatureAlgorithm::Rsa => RsaPrivateKey::from_pkcs8_der(&bytes).map(PrivateKey::Rsa).map_err(|e| invalid(e.to_string())),
+            SignatureAlgorithm::Ed25519 => {
+                let bytes: [u8; 32] = bytes.try_into().map_err(|_| invalid("ожидалось 32 байта".to_string()))?;
+                Ok(PrivateKey::Ed25519(ed25519_dalek::SigningKey::from_bytes(&bytes)))
+            }
+            SignatureAlgorithm::Secp256k1 => {
+                k256::ecdsa::SigningKey::from_slice(&bytes).map(PrivateKey::Secp256k1).map_err(|e| invalid(e.to_string()))
+            }
+        }
+    }
+}
+
+impl Signer for PrivateKey {
+    fn get_algorithm(&self) -> SignatureAlgorithm {
+        match self {
+            PrivateKey::Rsa(key) => key.get_algorithm(),
+            PrivateKey::Ed25519(key) => key.get_algorithm(),
+            PrivateKey::Secp256k1(key) => key.get_algorithm(),
+        }
+    }
+
+    fn get_public_key(&self) -> PublicKey {
+        match self {
+            PrivateKey::Rsa(key) => key.get_public_key(),
+            PrivateKey::Ed25519(key) => key.get_public_key(),
+            PrivateKey::Secp256k1(key) => key.get_public_key(),
+        }
+    }
+
+    fn sign_raw(&self, payload: &[u8]) -> Vec<u8> {
+        match self {
+            PrivateKey::Rsa(key) => key.sign_raw(payload),
+            PrivateKey::Ed25519(key) => key.sign_raw(payload),
+            PrivateKey::Secp256k1(key) => key.sign_raw(payload),
+        }
+    }
+}
+
+fn encode_tagged(algorithm: SignatureAlgorithm, bytes: &[u8]) -> String {
+    format!("{}:{}", algorithm.get_tag(), STANDARD_NO_PAD.encode(bytes))
+}
+
+fn decode_tagged(encoded: &str) -> Result<(SignatureAlgorithm, Vec<u8>), KeyError> {
+    let (tag, data) = encoded.trim().split_once(':').ok_or_else(|| KeyError::MissingTag(encoded.to_string()))?;
+    let algorithm: SignatureAlgorithm = tag.parse()?;
+    let bytes = STANDARD_NO_PAD
+        .decode(data)
+        .map_err(|e| KeyError::InvalidKey { algorithm, reason: e.to_string() })?;
+    Ok((algorithm, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALGORITHMS: [SignatureAlgorithm; 3] = [SignatureAlgorithm::Rsa, SignatureAlgorithm::Ed25519, SignatureAlgorithm::Secp256k1];
+
+    #[test]
+    fn test_sign_and_verify_with_every_algorithm() {
+        for algorithm in ALGORITHMS {
+            let private_key = PrivateKey::generate(algorithm);
+            let public_key = private_key.get_public_key();
+            let signature = private_key.sign(b"payload");
+
+            assert!(signature.starts_with(&format!("{}:", algorithm.get_tag())));
+            assert!(public_key.verify(b"payload", &signature), "{}", algorithm);
+            assert!(!public_key.verify(b"other payload", &signature), "{}", algorithm);
+
+            let other = PrivateKey::generate(algorithm).get_public_key();
+            assert!(!other.verify(b"payload", &signature), "{}", algorithm);
+        }
+    }
+
+    #[test]
+    fn test_keys_roundtrip_through_encoding() {
+        for algorithm in ALGORITHMS {
+            let private_key = PrivateKey::generate(algorithm);
+            let public_key = private_key.get_public_key();
+
+            let encoded = public_key.encode();
+            assert!(encoded.starts_with(&format!("{}:", algorithm.get_tag())));
+            assert_eq!(PublicKey::decode(&encoded), Ok(public_key.clone()));
+
+            let restored = PrivateKey::decode(&private_key.encode()).unwrap();
+            assert_eq!(restored.get_public_key(), public_key);
+        }
+    }
+
+    #[test]
+    fn test_signature_tag_must_match_key() {
+        let ed25519 = PrivateKey::generate(SignatureAlgorithm::Ed25519);
+        let signature = ed25519.sign(b"payload");
+        let (_, bytes) = signature.split_once(':').unwrap();
+
+        assert!(!ed25519.get_public_key().verify(b"payload", bytes), "Подпись без метки не принимается");
+        assert!(!ed25519.get_public_key().verify(b"payload", &format!("secp256k1:{}", bytes)));
+        assert!(matches!(PublicKey::decode("dsa:AAAA"), Err(KeyError::UnknownAlgorithm(_))));
+        assert!(matches!(PublicKey::decode("AAAA"), Err(KeyError::MissingTag(_))));
+
+        // Несжатая точка — та же, но другая запись ключа secp256k1
+        let PublicKey::Secp256k1(key) = PrivateKey::generate(SignatureAlgorithm::Secp256k1).get_public_key() else {
+            unreachable!()
+        };
+        let uncompressed = format!("secp256k1:{}", STANDARD_NO_PAD.encode(key.to_encoded_point(false).as_bytes()));
+        assert!(matches!(PublicKey::decode(&uncompressed), Err(KeyError::NonCanonical(_))));
+    }
+}
@@ -0,0 +1,103 @@
+use crate::coin::node::blockchain::block::Block;
+
+/// Цель первого блока. Хеш меньше 2^52 в старших 64 битах соответствует прежнему префиксу "000".
+pub const INITIAL_TARGET: u64 = 1 << 52;
+/// Самая лёгкая допустимая цель основной сети
+pub const MAX_TARGET: u64 = 1 << 60;
+/// Желаемое время между блоками, секунды
+pub const TARGET_BLOCK_TIME: i64 = 10;
+/// Через сколько блоков пересчитывается цель
+pub const RETARGET_INTERVAL: usize = 10;
+/// Во сколько раз цель может измениться за один пересчёт
+const MAX_ADJUSTMENT: i64 = 4;
+
+/// Хеш блока удовлетворяет цели, если его старшие 64 бита меньше `target`.
+/// Это равносильно сравнению всего хеша с числом `target * 2^(N - 64)`.
+pub fn hash_meets_target(hash: &[u8], target: u64) -> bool {
+    let mut prefix = [0u8; 8];
+    prefix.copy_from_slice(&hash[..8]);
+    u64::from_be_bytes(prefix) < target
+}
+
+/// Работа, в среднем затраченная на блок с целью `target`: 2^64 / target
+pub fn block_work(target: u64) -> u128 {
+    (1u128 << 64) / target.max(1) as u128
+}
+
+/// Цель для блока, следующего за последним блоком `chain`.
+/// Каждые `RETARGET_INTERVAL` блоков цель масштабируется отношением фактического
+/// времени создания последних блоков к ожидаемому, но не становится легче `max_target`.
+pub fn next_target(chain: &[Block], max_target: u64) -> u64 {
+    let last_block = match chain.last() {
+        Some(block) => block,
+        None => return INITIAL_TARGET,
+    };
+
+    if chain.len() <= RETARGET_INTERVAL || last_block.get_id() % RETARGET_INTERVAL != 0 {
+        return last_block.get_target();
+    }
+
+    let first_block = &chain[chain.len() - 1 - RETARGET_INTERVAL];
+    let expected = TARGET_BLOCK_TIME * RETARGET_INTERVAL as i64;
+    let actual = (last_block.get_datetime() - first_block.get_datetime())
+        .clamp(expected / MAX_ADJUSTMENT, expected * MAX_ADJUSTMENT);
+
+    let target = last_block.get_target() as u128 * actual as u128 / expected as u128;
+    target.clamp(1, max_target as u128) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain_with_spacing(len: usize, spacing: i64, target: u64) -> Vec<Block> {
+        (1..=len)
+            .map(|id| Block::force_new(id, 1_700_000_000 + id as i64 * spacing, Vec::new(), "prev".to_string(), 0, target))
+            .collect()
+    }
+
+    #[test]
+    fn test_hash_meets_target() {
+        let mut hash = [0xffu8; 64];
+        assert!(!hash_meets_target(&hash, INITIAL_TARGET));
+
+        hash[..8].copy_from_slice(&(INITIAL_TARGET - 1).to_be_bytes());
+        assert!(hash_meets_target(&hash, INITIAL_TARGET));
+
+        hash[..8].copy_from_slice(&INITIAL_TARGET.to_be_bytes());
+        assert!(!hash_meets_target(&hash, INITIAL_TARGET));
+    }
+
+    #[test]
+    fn test_block_work_grows_with_difficulty() {
+        assert_eq!(block_work(INITIAL_TARGET), 1 << 12);
+        assert!(block_work(INITIAL_TARGET / 2) > block_work(INITIAL_TARGET));
+        assert_eq!(block_work(0), 1 << 64);
+    }
+
+    #[test]
+    fn test_empty_chain_uses_initial_target() {
+        assert_eq!(next_target(&[], MAX_TARGET), INITIAL_TARGET);
+    }
+
+    #[test]
+    fn test_target_kept_between_retargets() {
+        let chain = chain_with_spacing(RETARGET_INTERVAL + 3, 1, 12345);
+        assert_eq!(next_target(&chain, MAX_TARGET), 12345);
+    }
+
+    #[test]
+    fn test_fast_blocks_make_target_harder() {
+        let chain = chain_with_spacing(RETARGET_INTERVAL * 2, TARGET_BLOCK_TIME / 2, INITIAL_TARGET);
+        assert_eq!(next_target(&chain, MAX_TARGET), INITIAL_TARGET / 2);
+    }
+
+    #[test]
+    fn test_slow_blocks_make_target_easier_with_clamp() {
+        let chain = chain_with_spacing(RETARGET_INTERVAL * 2, TARGET_BLOCK_TIME * 100, INITIAL_TARGET);
+        assert_eq!(next_target(&chain, MAX_TARGET), INITIAL_TARGET * MAX_ADJUSTMENT as u64);
+
+        let chain = chain_with_spacing(RETARGET_INTERVAL * 2, TARGET_BLOCK_TIME * 100, MAX_TARGET);
+        assert_eq!(next_target(&chain, MAX_TARGET), MAX_TARGET);
+    }
+}
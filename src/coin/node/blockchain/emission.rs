@@ -0,0 +1,82 @@
+use crate::coin::node::blockchain::amount::Amount;
+use crate::coin::node::blockchain::transaction::SerializedTransaction;
+
+/// Награда за первый добытый блок
+pub const INITIAL_SUBSIDY: Amount = Amount::from_coins(50);
+/// Через сколько блоков награда уменьшается вдвое
+pub const HALVING_INTERVAL: usize = 1000;
+/// После стольких уменьшений награда становится нулевой
+const MAX_HALVINGS: usize = 64;
+/// Вес, который шаблон блока оставляет под coinbase-транзакцию
+pub const COINBASE_RESERVED_WEIGHT: usize = 1_000;
+
+/// Награда за блок на высоте `height` без учёта комиссий
+pub fn block_subsidy(height: usize) -> Amount {
+    let halvings = height / HALVING_INTERVAL;
+    if halvings >= MAX_HALVINGS {
+        return Amount::ZERO;
+    }
+    Amount::from_base_units(INITIAL_SUBSIDY.get_base_units() >> halvings)
+}
+
+/// Сумма комиссий транзакций блока (coinbase не учитывается).
+/// Насыщается при переполнении: такие комиссии отправители всё равно не смогут оплатить.
+pub fn total_fees(transactions: &[SerializedTransaction]) -> Amount {
+    transactions
+        .iter()
+        .filter(|transaction| !transaction.is_coinbase())
+        .map(|transaction| transaction.get_fee())
+        .sum()
+}
+
+/// Сумма, которую coinbase-транзакция блока обязана выплатить майнеру
+pub fn coinbase_amount(height: usize, transactions: &[SerializedTransaction]) -> Amount {
+    block_subsidy(height).saturating_add(total_fees(transactions))
+}
+
+/// Создаёт coinbase-транзакцию для блока на высоте `height`
+pub fn coinbase_transaction(
+    miner_address: &str,
+    height: usize,
+    transactions: &[SerializedTransaction],
+) -> SerializedTransaction {
+    SerializedTransaction::coinbase(miner_address.to_string(), height, coinbase_amount(height, transactions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coin::node::blockchain::address;
+
+    #[test]
+    fn test_subsidy_halves_on_schedule() {
+        assert_eq!(block_subsidy(2), INITIAL_SUBSIDY);
+        assert_eq!(block_subsidy(HALVING_INTERVAL - 1), INITIAL_SUBSIDY);
+        assert_eq!(block_subsidy(HALVING_INTERVAL), Amount::from_coins(25));
+        assert_eq!(block_subsidy(HALVING_INTERVAL * 3), "6.25".parse().unwrap());
+        assert_eq!(block_subsidy(HALVING_INTERVAL * MAX_HALVINGS), Amount::ZERO);
+    }
+
+    #[test]
+    fn test_coinbase_amount_includes_fees() {
+        let mut first = SerializedTransaction::new("a".into(), "b".into(), "c".into(), "msg".into(), Amount::from_coins(10));
+        first.fee = "0.5".parse().unwrap();
+        let mut second = SerializedTransaction::new("a".into(), "b".into(), "c".into(), "msg".into(), Amount::from_coins(20));
+        second.fee = "1.5".parse().unwrap();
+
+        assert_eq!(coinbase_amount(2, &[first, second]), Amount::from_coins(52));
+    }
+
+    #[test]
+    fn test_coinbase_transaction() {
+        let coinbase = coinbase_transaction("miner", 5, &[]);
+
+        assert!(coinbase.is_coinbase());
+        assert_eq!(coinbase.seller, "miner");
+        assert_eq!(coinbase.get_transfer(), INITIAL_SUBSIDY);
+
+        // Даже с самым длинным адресом и высотой coinbase укладывается в оставленный под неё вес
+        let longest = coinbase_transaction(&address::multisig_address(&[0xff; address::ACCOUNT_HASH_SIZE]), usize::MAX, &[]);
+        assert!(longest.get_size() <= COINBASE_RESERVED_WEIGHT);
+    }
+}
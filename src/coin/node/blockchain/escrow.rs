@@ -0,0 +1,77 @@
+use thiserror::Error;
+
+use crate::coin::node::blockchain::amount::Amount;
+use crate::coin::node::blockchain::transaction::{lock_reached, SerializedTransaction, TransactionKind};
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum EscrowError {
+    #[error("Депонирование открывает только покупатель")]
+    NotBuyer,
+    #[error("Нельзя депонировать нулевую сумму")]
+    EmptyAmount,
+    #[error("Депонирование {0} не найдено или уже закрыто")]
+    Unknown(String),
+    #[error("Это действие с депонированием {0} доступно другой стороне")]
+    NotAuthorized(String),
+    #[error("Покупатель, продавец или сумма не совпадают с депонированием {0}")]
+    Mismatch(String),
+    #[error("Срок депонирования {id} наступает в {timeout}")]
+    NotExpired { id: String, timeout: u64 },
+}
+
+/// Средства покупателя, заблокированные для продавца транзакцией `EscrowLock`.
+/// Дальше депонирование закрывается одним из способов:
+/// покупатель подтверждает сделку (`EscrowRelease`) — средства получает продавец;
+/// продавец отказывается (`EscrowRefund`) или покупатель забирает средства
+/// после срока `timeout` (`EscrowReclaim`) — средства возвращаются покупателю.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Escrow {
+    pub buyer: String,
+    pub seller: String,
+    pub amount: Amount,
+    // Высота блока или время Unix, как у окна действия транзакции
+    pub timeout: u64,
+}
+
+impl Escrow {
+    /// Депонирование, открываемое транзакцией `lock`
+    pub fn open(lock: &SerializedTransaction, timeout: u64) -> Result<Escrow, EscrowError> {
+        if lock.sender != lock.buyer {
+            return Err(EscrowError::NotBuyer);
+        }
+        if lock.get_transfer() == Amount::ZERO {
+            return Err(EscrowError::EmptyAmount);
+        }
+        Ok(Escrow {
+            buyer: lock.buyer.clone(),
+            seller: lock.seller.clone(),
+            amount: lock.get_transfer(),
+            timeout,
+        })
+    }
+
+    /// Проверяет закрытие депонирования `id` транзакцией `transaction` в блоке с высотой `height`
+    /// и временем `time`. Транзакция должна повторять покупателя, продавца и сумму депонирования.
+    /// Возвращает счёт, которому достаются средства.
+    pub fn settle(&self, id: &str, transaction: &SerializedTransaction, height: u64, time: i64) -> Result<String, EscrowError> {
+        if transaction.buyer != self.buyer || transaction.seller != self.seller || transaction.get_transfer() != self.amount {
+            return Err(EscrowError::Mismatch(id.to_string()));
+        }
+
+        let (signer, recipient) = match transaction.kind {
+            TransactionKind::EscrowRelease { .. } => (&self.buyer, &self.seller),
+            TransactionKind::EscrowRefund { .. } => (&self.seller, &self.buyer),
+            TransactionKind::EscrowReclaim { .. } => {
+                if !lock_reached(self.timeout, height, time) {
+                    return Err(EscrowError::NotExpired { id: id.to_string(), timeout: self.timeout });
+                }
+                (&self.buyer, &self.buyer)
+            }
+            _ => return Err(EscrowError::Unknown(id.to_string())),
+        };
+        if transaction.sender != *signer {
+            return Err(EscrowError::NotAuthorized(id.to_string()));
+        }
+        Ok(recipient.clone())
+    }
+}
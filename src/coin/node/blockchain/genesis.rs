@@ -0,0 +1,76 @@
+use sha2::{Digest, Sha512};
+
+use crate::coin::node::blockchain::amount::Amount;
+use crate::coin::node::blockchain::block::Block;
+use crate::coin::node::blockchain::difficulty;
+use crate::coin::node::blockchain::transaction::SerializedTransaction;
+
+/// Описание первого блока сети. Все узлы с одинаковым описанием получают один и тот же блок.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenesisConfig {
+    /// Время создания блока, unix-секунды
+    pub timestamp: i64,
+    /// Начальные балансы: адрес счёта (см. `address::check_account`) и сумма
+    pub allocations: Vec<(String, Amount)>,
+    /// Сообщение, хеш которого записывается вместо хеша предыдущего блока
+    pub message: String,
+    /// Цель первого блока, от неё отсчитывается сложность сети
+    pub target: u64,
+}
+
+impl Default for GenesisConfig {
+    fn default() -> Self {
+        GenesisConfig {
+            // 2025-01-01 00:00:00 UTC
+            timestamp: 1_735_689_600,
+            allocations: Vec::new(),
+            message: "First block".to_string(),
+            target: difficulty::INITIAL_TARGET,
+        }
+    }
+}
+
+impl GenesisConfig {
+    /// Первый блок: выделения оформлены coinbase-транзакциями, PoW для него не требуется
+    pub fn build(&self) -> Block {
+        let transactions = self
+            .allocations
+            .iter()
+            .map(|(address, amount)| SerializedTransaction::coinbase(address.clone(), 1, *amount))
+            .collect();
+
+        let previous_hash = format!("{:x}", Sha512::digest(self.message.as_bytes()));
+        Block::force_new(1, self.timestamp, transactions, previous_hash, 0, self.target)
+    }
+
+    pub fn get_hash(&self) -> String {
+        self.build().get_hash()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coin::node::blockchain::address;
+    use crate::coin::node::blockchain::crypto::{PrivateKey, SignatureAlgorithm, Signer};
+
+    #[test]
+    fn test_genesis_is_deterministic() {
+        let config = GenesisConfig::default();
+        assert_eq!(config.build().get_hash(), config.build().get_hash());
+        assert_eq!(config.build().get_datetime(), config.timestamp);
+    }
+
+    #[test]
+    fn test_genesis_hash_covers_config() {
+        let config = GenesisConfig::default();
+
+        let other_message = GenesisConfig { message: "Other network".to_string(), ..config.clone() };
+        assert_ne!(config.get_hash(), other_message.get_hash());
+
+        let alice = address::from_public_key(&PrivateKey::generate(SignatureAlgorithm::Ed25519).get_public_key());
+        let with_allocation = GenesisConfig { allocations: vec![(alice, Amount::from_coins(100))], ..config.clone() };
+        assert_ne!(config.get_hash(), with_allocation.get_hash());
+        assert_eq!(with_allocation.build().get_transactions().len(), 1);
+    }
+}
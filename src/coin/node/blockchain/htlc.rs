@@ -0,0 +1,98 @@
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::coin::node::blockchain::amount::Amount;
+use crate::coin::node::blockchain::merkle;
+use crate::coin::node::blockchain::transaction::{lock_reached, SerializedTransaction, TransactionKind};
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum HtlcError {
+    #[error("Хешлок должен быть SHA-256 в hex: {0}")]
+    InvalidHashlock(String),
+    #[error("Нельзя заблокировать нулевую сумму")]
+    EmptyAmount,
+    #[error("Контракт {0} не найден или уже закрыт")]
+    Unknown(String),
+    #[error("Это действие с контрактом {0} доступно другой стороне")]
+    NotAuthorized(String),
+    #[error("Получатель или сумма не совпадают с контрактом {0}")]
+    Mismatch(String),
+    #[error("Прообраз не подходит к хешлоку контракта {0}")]
+    WrongPreimage(String),
+    #[error("Срок контракта {id} истёк в {timeout}, получить средства по прообразу нельзя")]
+    Expired { id: String, timeout: u64 },
+    #[error("Срок контракта {id} наступает в {timeout}")]
+    NotExpired { id: String, timeout: u64 },
+}
+
+/// Хешлок для прообраза: SHA-256 в hex
+pub fn hashlock(preimage: &[u8]) -> String {
+    merkle::to_hex(&Sha256::digest(preimage))
+}
+
+/// Контракт с хеш- и тайм-локом (HTLC), открытый транзакцией `HtlcLock`.
+/// До срока `timeout` продавец забирает средства, раскрыв прообраз хешлока (`HtlcClaim`),
+/// после срока отправитель возвращает их себе (`HtlcRefund`).
+/// Раскрытый прообраз позволяет второй стороне обмена забрать встречный контракт в другой цепочке
+/// с тем же хешлоком, поэтому срок встречного контракта должен быть короче.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Htlc {
+    pub sender: String,
+    pub recipient: String,
+    pub amount: Amount,
+    pub hashlock: String,
+    // Высота блока или время Unix, как у окна действия транзакции
+    pub timeout: u64,
+}
+
+impl Htlc {
+    /// Контракт, открываемый транзакцией `lock`
+    pub fn open(lock: &SerializedTransaction, hashlock: &str, timeout: u64) -> Result<Htlc, HtlcError> {
+        if merkle::from_hex(hashlock).is_none_or(|hash| hash.len() != 32) || hashlock != hashlock.to_lowercase() {
+            return Err(HtlcError::InvalidHashlock(hashlock.to_string()));
+        }
+        if lock.get_transfer() == Amount::ZERO {
+            return Err(HtlcError::EmptyAmount);
+        }
+        Ok(Htlc {
+            sender: lock.get_sender(),
+            recipient: lock.seller.clone(),
+            amount: lock.get_transfer(),
+            hashlock: hashlock.to_string(),
+            timeout,
+        })
+    }
+
+    /// Проверяет закрытие контракта `id` транзакцией `transaction` в блоке с высотой `height`
+    /// и временем `time`. Транзакция должна повторять получателя и сумму контракта.
+    /// Возвращает счёт, которому достаются средства.
+    pub fn settle(&self, id: &str, transaction: &SerializedTransaction, height: u64, time: i64) -> Result<String, HtlcError> {
+        if transaction.seller != self.recipient || transaction.get_transfer() != self.amount {
+            return Err(HtlcError::Mismatch(id.to_string()));
+        }
+
+        let expired = lock_reached(self.timeout, height, time);
+        let (signer, recipient) = match &transaction.kind {
+            TransactionKind::HtlcClaim { preimage, .. } => {
+                if expired {
+                    return Err(HtlcError::Expired { id: id.to_string(), timeout: self.timeout });
+                }
+                if merkle::from_hex(preimage).is_none_or(|preimage| hashlock(&preimage) != self.hashlock) {
+                    return Err(HtlcError::WrongPreimage(id.to_string()));
+                }
+                (&self.recipient, &self.recipient)
+            }
+            TransactionKind::HtlcRefund { .. } => {
+                if !expired {
+                    return Err(HtlcError::NotExpired { id: id.to_string(), timeout: self.timeout });
+                }
+                (&self.sender, &self.sender)
+            }
+            _ => return Err(HtlcError::Unknown(id.to_string())),
+        };
+        if transaction.get_sender() != *signer {
+            return Err(HtlcError::NotAuthorized(id.to_string()));
+        }
+        Ok(recipient.clone())
+    }
+}
@@ -0,0 +1,554 @@
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::coin::node::blockchain::amount::Amount;
+use crate::coin::node::blockchain::block::Block;
+use crate::coin::node::blockchain::escrow::{Escrow, EscrowError};
+use crate::coin::node::blockchain::htlc::{Htlc, HtlcError};
+use crate::coin::node::blockchain::transaction::{SerializedTransaction, TransactionKind};
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum LedgerError {
+    #[error("Недостаточно средств у {account}: баланс {balance}, требуется {required}")]
+    InsufficientFunds { account: String, balance: Amount, required: Amount },
+    #[error("Переполнение суммы")]
+    AmountOverflow,
+    #[error("Coinbase-транзакция допустима только первой в блоке")]
+    UnexpectedCoinbase,
+    #[error("Повтор транзакции {account}: номер {actual} уже использован, ожидался {expected}")]
+    NonceTooLow { account: String, expected: u64, actual: u64 },
+    #[error("Пропуск номера транзакции {account}: получен {actual}, ожидался {expected}")]
+    NonceTooHigh { account: String, expected: u64, actual: u64 },
+    #[error("Несколько получателей допустимы только у перевода: {0}")]
+    UnexpectedOutputs(String),
+    #[error("{0}")]
+    Escrow(#[from] EscrowError),
+    #[error("{0}")]
+    Htlc(#[from] HtlcError),
+}
+
+/// Балансы счетов, вычисленные по блокам цепочки.
+/// Отправитель транзакции списывает `transfer` и комиссию, продавец (`seller`) получает `transfer`,
+/// дополнительные получатели перевода — свои суммы,
+/// комиссии достаются майнеру через coinbase-транзакцию.
+/// Транзакции отправителя нумеруются подряд с нуля, поэтому каждую можно применить только один раз.
+/// Депонированные средства и средства HTLC-контрактов не принадлежат ни одному счёту, пока их не закроют.
+#[derive(Debug, Clone, Default)]
+pub struct Ledger {
+    balances: HashMap<String, Amount>,
+    // Номер следующей транзакции каждого отправителя
+    nonces: HashMap<String, u64>,
+    // Открытые депонирования по идентификатору транзакции EscrowLock
+    escrows: HashMap<String, Escrow>,
+    // Открытые HTLC-контракты по идентификатору транзакции HtlcLock
+    htlcs: HashMap<String, Htlc>,
+}
+
+impl Ledger {
+    pub fn new() -> Ledger {
+        Ledger { balances: HashMap::new(), nonces: HashMap::new(), escrows: HashMap::new(), htlcs: HashMap::new() }
+    }
+
+    pub fn get_balance(&self, account: &str) -> Amount {
+        self.balances.get(account).copied().unwrap_or(Amount::ZERO)
+    }
+
+    pub fn credit(&mut self, account: &str, amount: Amount) -> Result<(), LedgerError> {
+        let balance = self.get_balance(account).checked_add(amount).ok_or(LedgerError::AmountOverflow)?;
+        self.balances.insert(account.to_string(), balance);
+        Ok(())
+    }
+
+    /// Номер, который должна нести следующая транзакция отправителя
+    pub fn get_nonce(&self, account: &str) -> u64 {
+        self.nonces.get(account).copied().unwrap_or(0)
+    }
+
+    pub fn get_escrow(&self, escrow_id: &str) -> Option<&Escrow> {
+        self.escrows.get(escrow_id)
+    }
+
+    pub fn get_htlc(&self, htlc_id: &str) -> Option<&Htlc> {
+        self.htlcs.get(htlc_id)
+    }
+
+    /// Проверяет, что транзакцию можно применить в блоке с высотой `height` и временем `time`,
+    /// не изменяя балансы
+    pub fn check_transaction(&self, transaction: &SerializedTransaction, height: u64, time: i64) -> Result<(), LedgerError> {
+        self.check_spend(transaction, &[], height, time)
+    }
+
+    /// Проверка с учётом транзакций отправителя, уже ожидающих в очереди:
+    /// их суммы зарезервированы, а номер новой транзакции должен идти следом за ними
+    pub fn check_spend(
+        &self,
+        transaction: &SerializedTransaction,
+        pending: &[SerializedTransaction],
+        height: u64,
+        time: i64,
+    ) -> Result<(), LedgerError> {
+        if transaction.is_coinbase() {
+            return Err(LedgerError::UnexpectedCoinbase);
+        }
+        if !transaction.outputs.is_empty() && transaction.kind != TransactionKind::Transfer {
+            return Err(LedgerError::UnexpectedOutputs(transaction.get_txid()));
+        }
+
+        let sender = transaction.get_sender();
+        let pending: Vec<&SerializedTransaction> = pending.iter().filter(|t| t.sender == sender).collect();
+
+        let expected = self.get_nonce(&sender) + pending.len() as u64;
+        let actual = transaction.get_nonce();
+        if actual < expected {
+            return Err(LedgerError::NonceTooLow { account: sender, expected, actual });
+        }
+        if actual > expected {
+            return Err(LedgerError::NonceTooHigh { account: sender, expected, actual });
+        }
+
+        let reserved = pending
+            .iter()
+            .map(|t| t.get_debit())
+            .collect::<Option<Vec<Amount>>>()
+            .and_then(Amount::checked_sum)
+            .ok_or(LedgerError::AmountOverflow)?;
+        let required = transaction.get_debit().ok_or(LedgerError::AmountOverflow)?;
+        let balance = self.get_balance(&sender).saturating_sub(reserved);
+        if balance < required {
+            return Err(LedgerError::InsufficientFunds {
+                account: sender,
+                balance,
+                required,
+            });
+        }
+
+        self.check_settlement(transaction, height, time).map(|_| ())
+    }
+
+    /// Проверка открытия или закрытия депонирования либо HTLC-контракта.
+    /// Для закрывающей транзакции возвращает счёт, которому достаются средства, и их сумму
+    fn check_settlement(&self, transaction: &SerializedTransaction, height: u64, time: i64) -> Result<Option<(String, Amount)>, LedgerError> {
+        match &transaction.kind {
+            TransactionKind::Transfer => Ok(None),
+            TransactionKind::EscrowLock { timeout } => Escrow::open(transaction, *timeout).map(|_| None).map_err(Into::into),
+            TransactionKind::HtlcLock { hashlock, timeout } => {
+                Htlc::open(transaction, hashlock, *timeout).map(|_| None).map_err(Into::into)
+            }
+            kind => {
+                if let Some(htlc_id) = kind.get_htlc_id() {
+                    let htlc = self.get_htlc(htlc_id).ok_or_else(|| HtlcError::Unknown(htlc_id.to_string()))?;
+                    let recipient = htlc.settle(htlc_id, transaction, height, time)?;
+                    return Ok(Some((recipient, htlc.amount)));
+                }
+                let escrow_id = kind.get_escrow_id().unwrap_or_default();
+                let escrow = self.get_escrow(escrow_id).ok_or_else(|| EscrowError::Unknown(escrow_id.to_string()))?;
+                let recipient = escrow.settle(escrow_id, transaction, height, time)?;
+                Ok(Some((recipient, escrow.amount)))
+            }
+        }
+    }
+
+    /// Применяет транзакцию блока с высотой `height` и временем `time`
+    pub fn apply_transaction(&mut self, transaction: &SerializedTransaction, height: u64, time: i64) -> Result<(), LedgerError> {
+        self.check_transaction(transaction, height, time)?;
+
+        // check_transaction уже убедился, что баланса хватает и сумма не переполняется
+        let sender = transaction.get_sender();
+        let remaining = transaction
+            .get_debit()
+            .and_then(|required| self.get_balance(&sender).checked_sub(required))
+            .ok_or(LedgerError::AmountOverflow)?;
+        self.balances.insert(sender, remaining);
+
+        match &transaction.kind {
+            TransactionKind::Transfer => {
+                for output in transaction.get_outputs() {
+                    self.credit(&output.recipient, output.amount)?;
+                }
+            }
+            TransactionKind::EscrowLock { timeout } => {
+                let escrow = Escrow::open(transaction, *timeout)?;
+                self.escrows.insert(transaction.get_txid(), escrow);
+            }
+            TransactionKind::HtlcLock { hashlock, timeout } => {
+                let htlc = Htlc::open(transaction, hashlock, *timeout)?;
+                self.htlcs.insert(transaction.get_txid(), htlc);
+            }
+            kind => {
+                if let Some((recipient, amount)) = self.check_settlement(transaction, height, time)? {
+                    if let Some(htlc_id) = kind.get_htlc_id() {
+                        self.htlcs.remove(htlc_id);
+                    } else {
+                        self.escrows.remove(kind.get_escrow_id().unwrap_or_default());
+                    }
+                    self.credit(&recipient, amount)?;
+                }
+            }
+        }
+        *self.nonces.entry(transaction.get_sender()).or_insert(0) += 1;
+        Ok(())
+    }
+
+    /// Зачисляет награду майнеру. Сумму проверяет `Blockchain` по графику эмиссии.
+    pub fn apply_coinbase(&mut self, coinbase: &SerializedTransaction) -> Result<(), LedgerError> {
+        self.credit(&coinbase.seller, coinbase.get_transfer())
+    }
+
+    /// Зачисляет начальные выделения первого блока
+    pub fn apply_genesis(&mut self, genesis: &Block) -> Result<(), LedgerError> {
+        let mut updated = self.clone();
+        for allocation in genesis.get_transactions() {
+            updated.apply_coinbase(allocation)?;
+        }
+        *self = updated;
+        Ok(())
+    }
+
+    /// Применяет все транзакции блока. Если хотя бы одна не проходит,
+    /// реестр остаётся без изменений.
+    pub fn apply_block(&mut self, block: &Block) -> Result<(), LedgerError> {
+        let mut updated = self.clone();
+        let (height, time) = (block.get_id() as u64, block.get_datetime());
+        for (index, transaction) in block.get_transactions().iter().enumerate() {
+            if index == 0 && transaction.is_coinbase() {
+                updated.apply_coinbase(transaction)?;
+            } else {
+                updated.apply_transaction(transaction, height, time)?;
+            }
+        }
+        *self = updated;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coin::node::blockchain::difficulty::INITIAL_TARGET;
+    use crate::coin::node::blockchain::htlc::hashlock;
+    use crate::coin::node::blockchain::merkle;
+    use crate::coin::node::blockchain::transaction::TransactionOutput;
+
+    fn transaction(sender: &str, seller: &str, coins: u64) -> SerializedTransaction {
+        SerializedTransaction::new(
+            sender.to_string(),
+            seller.to_string(),
+            "buyer".to_string(),
+            "Test message".to_string(),
+            Amount::from_coins(coins),
+        )
+    }
+
+    #[test]
+    fn test_transfer_moves_balance() {
+        let mut ledger = Ledger::new();
+        ledger.credit("alice", Amount::from_coins(100)).unwrap();
+
+        ledger.apply_transaction(&transaction("alice", "bob", 40), 2, 0).unwrap();
+
+        assert_eq!(ledger.get_balance("alice"), Amount::from_coins(60));
+        assert_eq!(ledger.get_balance("bob"), Amount::from_coins(40));
+    }
+
+    #[test]
+    fn test_transfer_to_several_recipients() {
+        let mut ledger = Ledger::new();
+        ledger.credit("alice", Amount::from_coins(100)).unwrap();
+
+        let mut payroll = transaction("alice", "bob", 40);
+        payroll.fee = Amount::from_coins(1);
+        for (recipient, coins) in [("carol", 30), ("dave", 20)] {
+            payroll.outputs.push(TransactionOutput { recipient: recipient.to_string(), amount: Amount::from_coins(coins) });
+        }
+        // Баланса хватает на каждого получателя по отдельности, но не на всех вместе
+        let mut overspend = payroll.clone();
+        overspend.outputs[1].amount = Amount::from_coins(30);
+        assert!(matches!(
+            ledger.apply_transaction(&overspend, 2, 0),
+            Err(LedgerError::InsufficientFunds { required, .. }) if required == Amount::from_coins(101)
+        ));
+
+        ledger.apply_transaction(&payroll, 2, 0).unwrap();
+        assert_eq!(ledger.get_balance("alice"), Amount::from_coins(9));
+        assert_eq!(ledger.get_balance("bob"), Amount::from_coins(40));
+        assert_eq!(ledger.get_balance("carol"), Amount::from_coins(30));
+        assert_eq!(ledger.get_balance("dave"), Amount::from_coins(20));
+
+        let mut escrow = transaction("alice", "bob", 1);
+        escrow.nonce = 1;
+        escrow.kind = TransactionKind::EscrowLock { timeout: 10 };
+        escrow.outputs = payroll.outputs.clone();
+        assert!(matches!(ledger.check_transaction(&escrow, 3, 0), Err(LedgerError::UnexpectedOutputs(_))));
+    }
+
+    #[test]
+    fn test_overspend_rejected() {
+        let mut ledger = Ledger::new();
+        ledger.credit("alice", Amount::from_coins(10)).unwrap();
+
+        let result = ledger.apply_transaction(&transaction("alice", "bob", 40), 2, 0);
+
+        assert!(matches!(result, Err(LedgerError::InsufficientFunds { .. })));
+        assert_eq!(ledger.get_balance("alice"), Amount::from_coins(10));
+        assert_eq!(ledger.get_balance("bob"), Amount::from_coins(0));
+    }
+
+    #[test]
+    fn test_overflowing_amounts_rejected() {
+        let mut ledger = Ledger::new();
+        let max = Amount::from_base_units(u64::MAX);
+        ledger.credit("alice", max).unwrap();
+        assert_eq!(ledger.credit("alice", Amount::from_base_units(1)), Err(LedgerError::AmountOverflow));
+
+        let mut tx = transaction("alice", "bob", 1);
+        tx.fee = max;
+        assert_eq!(ledger.apply_transaction(&tx, 2, 0), Err(LedgerError::AmountOverflow));
+        assert_eq!(ledger.get_balance("alice"), max);
+    }
+
+    #[test]
+    fn test_apply_block_is_atomic() {
+        let mut ledger = Ledger::new();
+        ledger.credit("alice", Amount::from_coins(50)).unwrap();
+
+        // Вторая транзакция тратит больше, чем осталось после первой
+        let block = Block::new(
+            2,
+            vec![transaction("alice", "bob", 30), transaction("alice", "carol", 30)],
+            "prev".to_string(),
+            0,
+            INITIAL_TARGET,
+        );
+
+        assert!(ledger.apply_block(&block).is_err());
+        assert_eq!(ledger.get_balance("alice"), Amount::from_coins(50));
+        assert_eq!(ledger.get_balance("bob"), Amount::from_coins(0));
+    }
+
+    #[test]
+    fn test_fee_is_charged_to_sender() {
+        let mut ledger = Ledger::new();
+        ledger.credit("alice", Amount::from_coins(41)).unwrap();
+
+        let mut tx = transaction("alice", "bob", 40);
+        tx.fee = Amount::from_coins(2);
+        assert!(ledger.apply_transaction(&tx, 2, 0).is_err());
+
+        tx.fee = Amount::from_coins(1);
+        ledger.apply_transaction(&tx, 2, 0).unwrap();
+        assert_eq!(ledger.get_balance("alice"), Amount::from_coins(0));
+        assert_eq!(ledger.get_balance("bob"), Amount::from_coins(40));
+    }
+
+    #[test]
+    fn test_coinbase_only_first_in_block() {
+        let coinbase = SerializedTransaction::coinbase("miner".to_string(), 2, Amount::from_coins(50));
+
+        let mut ledger = Ledger::new();
+        let block = Block::new(2, vec![coinbase.clone()], "prev".to_string(), 0, INITIAL_TARGET);
+        ledger.apply_block(&block).unwrap();
+        assert_eq!(ledger.get_balance("miner"), Amount::from_coins(50));
+
+        let block = Block::new(3, vec![transaction("miner", "bob", 1), coinbase], "prev".to_string(), 0, INITIAL_TARGET);
+        assert_eq!(ledger.apply_block(&block), Err(LedgerError::UnexpectedCoinbase));
+    }
+
+    #[test]
+    fn test_genesis_allocations_credited() {
+        let genesis = Block::new(
+            1,
+            vec![
+                SerializedTransaction::coinbase("alice".to_string(), 1, Amount::from_coins(100)),
+                SerializedTransaction::coinbase("bob".to_string(), 1, Amount::from_coins(25)),
+            ],
+            "prev".to_string(),
+            0,
+            INITIAL_TARGET,
+        );
+
+        let mut ledger = Ledger::new();
+        ledger.apply_genesis(&genesis).unwrap();
+        assert_eq!(ledger.get_balance("alice"), Amount::from_coins(100));
+        assert_eq!(ledger.get_balance("bob"), Amount::from_coins(25));
+    }
+
+    #[test]
+    fn test_check_spend_counts_reserved() {
+        let mut ledger = Ledger::new();
+        ledger.credit("alice", Amount::from_coins(50)).unwrap();
+
+        let queued = transaction("alice", "bob", 30);
+        let mut tx = transaction("alice", "bob", 30);
+        tx.nonce = 1;
+        assert!(ledger.check_spend(&queued, &[], 2, 0).is_ok());
+        assert!(matches!(
+            ledger.check_spend(&tx, &[queued], 2, 0),
+            Err(LedgerError::InsufficientFunds { .. })
+        ));
+    }
+
+    #[test]
+    fn test_replayed_transaction_rejected() {
+        let mut ledger = Ledger::new();
+        ledger.credit("alice", Amount::from_coins(100)).unwrap();
+
+        let tx = transaction("alice", "bob", 10);
+        ledger.apply_transaction(&tx, 2, 0).unwrap();
+        assert_eq!(ledger.get_nonce("alice"), 1);
+
+        assert_eq!(
+            ledger.apply_transaction(&tx, 2, 0),
+            Err(LedgerError::NonceTooLow { account: "alice".to_string(), expected: 1, actual: 0 })
+        );
+        assert_eq!(ledger.get_balance("bob"), Amount::from_coins(10));
+    }
+
+    #[test]
+    fn test_nonce_gap_rejected() {
+        let mut ledger = Ledger::new();
+        ledger.credit("alice", Amount::from_coins(100)).unwrap();
+
+        let pending = vec![transaction("alice", "bob", 10)];
+        let mut tx = transaction("alice", "bob", 10);
+        tx.nonce = 2;
+        assert!(matches!(ledger.check_spend(&tx, &pending, 2, 0), Err(LedgerError::NonceTooHigh { expected: 1, .. })));
+
+        tx.nonce = 1;
+        assert!(ledger.check_spend(&tx, &pending, 2, 0).is_ok());
+    }
+
+    fn escrow_action(sender: &str, kind: TransactionKind, nonce: u64) -> SerializedTransaction {
+        let mut tx = transaction(sender, "seller", 10);
+        tx.buyer = "buyer".to_string();
+        tx.kind = kind;
+        tx.nonce = nonce;
+        tx
+    }
+
+    fn locked_ledger() -> (Ledger, String) {
+        let mut ledger = Ledger::new();
+        ledger.credit("buyer", Amount::from_coins(15)).unwrap();
+        ledger.credit("seller", Amount::from_coins(1)).unwrap();
+        let lock = escrow_action("buyer", TransactionKind::EscrowLock { timeout: 10 }, 0);
+        ledger.apply_transaction(&lock, 2, 0).unwrap();
+        (ledger, lock.get_txid())
+    }
+
+    #[test]
+    fn test_escrow_release_pays_seller() {
+        let (mut ledger, escrow_id) = locked_ledger();
+        assert_eq!(ledger.get_balance("buyer"), Amount::from_coins(5));
+        assert_eq!(ledger.get_balance("seller"), Amount::from_coins(1));
+        assert_eq!(ledger.get_escrow(&escrow_id).unwrap().amount, Amount::from_coins(10));
+
+        // Закрыть депонирование может только покупатель
+        let by_seller = escrow_action("seller", TransactionKind::EscrowRelease { escrow_id: escrow_id.clone() }, 0);
+        assert_eq!(ledger.apply_transaction(&by_seller, 3, 0), Err(LedgerError::Escrow(EscrowError::NotAuthorized(escrow_id.clone()))));
+
+        let release = escrow_action("buyer", TransactionKind::EscrowRelease { escrow_id: escrow_id.clone() }, 1);
+        ledger.apply_transaction(&release, 3, 0).unwrap();
+        assert_eq!(ledger.get_balance("seller"), Amount::from_coins(11));
+        assert!(ledger.get_escrow(&escrow_id).is_none());
+
+        // Повторно закрыть депонирование нельзя
+        let again = escrow_action("buyer", TransactionKind::EscrowRelease { escrow_id: escrow_id.clone() }, 2);
+        assert_eq!(ledger.apply_transaction(&again, 4, 0), Err(LedgerError::Escrow(EscrowError::Unknown(escrow_id))));
+    }
+
+    #[test]
+    fn test_escrow_refund_and_reclaim_return_funds() {
+        let (mut refunded, escrow_id) = locked_ledger();
+        let mut mismatch = escrow_action("seller", TransactionKind::EscrowRefund { escrow_id: escrow_id.clone() }, 0);
+        mismatch.transfer = Amount::from_coins(9);
+        assert_eq!(refunded.apply_transaction(&mismatch, 3, 0), Err(LedgerError::Escrow(EscrowError::Mismatch(escrow_id.clone()))));
+
+        let refund = escrow_action("seller", TransactionKind::EscrowRefund { escrow_id: escrow_id.clone() }, 0);
+        refunded.apply_transaction(&refund, 3, 0).unwrap();
+        assert_eq!(refunded.get_balance("buyer"), Amount::from_coins(15));
+
+        let (mut reclaimed, escrow_id) = locked_ledger();
+        let reclaim = escrow_action("buyer", TransactionKind::EscrowReclaim { escrow_id: escrow_id.clone() }, 1);
+        assert_eq!(
+            reclaimed.apply_transaction(&reclaim, 9, 0),
+            Err(LedgerError::Escrow(EscrowError::NotExpired { id: escrow_id, timeout: 10 }))
+        );
+        reclaimed.apply_transaction(&reclaim, 10, 0).unwrap();
+        assert_eq!(reclaimed.get_balance("buyer"), Amount::from_coins(15));
+    }
+
+    #[test]
+    fn test_escrow_lock_requires_buyer() {
+        let mut ledger = Ledger::new();
+        ledger.credit("alice", Amount::from_coins(15)).unwrap();
+        let lock = escrow_action("alice", TransactionKind::EscrowLock { timeout: 10 }, 0);
+        assert_eq!(ledger.apply_transaction(&lock, 2, 0), Err(LedgerError::Escrow(EscrowError::NotBuyer)));
+        assert_eq!(ledger.get_balance("alice"), Amount::from_coins(15));
+    }
+
+    fn htlc_action(sender: &str, kind: TransactionKind, nonce: u64) -> SerializedTransaction {
+        let mut tx = transaction(sender, "bob", 10);
+        tx.kind = kind;
+        tx.nonce = nonce;
+        tx
+    }
+
+    fn htlc_ledger() -> (Ledger, String) {
+        let mut ledger = Ledger::new();
+        ledger.credit("alice", Amount::from_coins(15)).unwrap();
+        ledger.credit("bob", Amount::from_coins(1)).unwrap();
+        let lock = htlc_action("alice", TransactionKind::HtlcLock { hashlock: hashlock(b"secret"), timeout: 10 }, 0);
+        ledger.apply_transaction(&lock, 2, 0).unwrap();
+        (ledger, lock.get_txid())
+    }
+
+    #[test]
+    fn test_htlc_claim_requires_preimage_before_timeout() {
+        let (mut ledger, htlc_id) = htlc_ledger();
+        assert_eq!(ledger.get_balance("alice"), Amount::from_coins(5));
+        assert_eq!(ledger.get_htlc(&htlc_id).unwrap().amount, Amount::from_coins(10));
+
+        let claim = |preimage: &[u8]| {
+            htlc_action("bob", TransactionKind::HtlcClaim { htlc_id: htlc_id.clone(), preimage: merkle::to_hex(preimage) }, 0)
+        };
+        assert_eq!(ledger.apply_transaction(&claim(b"guess"), 3, 0), Err(LedgerError::Htlc(HtlcError::WrongPreimage(htlc_id.clone()))));
+        assert_eq!(
+            ledger.apply_transaction(&claim(b"secret"), 10, 0),
+            Err(LedgerError::Htlc(HtlcError::Expired { id: htlc_id.clone(), timeout: 10 }))
+        );
+
+        ledger.apply_transaction(&claim(b"secret"), 9, 0).unwrap();
+        assert_eq!(ledger.get_balance("bob"), Amount::from_coins(11));
+        assert!(ledger.get_htlc(&htlc_id).is_none());
+
+        // Вернуть уже полученные средства нельзя
+        let refund = htlc_action("alice", TransactionKind::HtlcRefund { htlc_id: htlc_id.clone() }, 1);
+        assert_eq!(ledger.apply_transaction(&refund, 11, 0), Err(LedgerError::Htlc(HtlcError::Unknown(htlc_id))));
+    }
+
+    #[test]
+    fn test_htlc_refund_after_timeout() {
+        let (mut ledger, htlc_id) = htlc_ledger();
+        let refund = htlc_action("alice", TransactionKind::HtlcRefund { htlc_id: htlc_id.clone() }, 1);
+        assert_eq!(
+            ledger.apply_transaction(&refund, 9, 0),
+            Err(LedgerError::Htlc(HtlcError::NotExpired { id: htlc_id.clone(), timeout: 10 }))
+        );
+
+        // Вернуть средства может только отправитель
+        let by_bob = htlc_action("bob", TransactionKind::HtlcRefund { htlc_id: htlc_id.clone() }, 0);
+        assert_eq!(ledger.apply_transaction(&by_bob, 10, 0), Err(LedgerError::Htlc(HtlcError::NotAuthorized(htlc_id))));
+
+        ledger.apply_transaction(&refund, 10, 0).unwrap();
+        assert_eq!(ledger.get_balance("alice"), Amount::from_coins(15));
+    }
+
+    #[test]
+    fn test_htlc_lock_requires_valid_hashlock() {
+        let mut ledger = Ledger::new();
+        ledger.credit("alice", Amount::from_coins(15)).unwrap();
+        let lock = htlc_action("alice", TransactionKind::HtlcLock { hashlock: "abcd".to_string(), timeout: 10 }, 0);
+        assert_eq!(ledger.apply_transaction(&lock, 2, 0), Err(LedgerError::Htlc(HtlcError::InvalidHashlock("abcd".to_string()))));
+        assert_eq!(ledger.get_balance("alice"), Amount::from_coins(15));
+    }
+}
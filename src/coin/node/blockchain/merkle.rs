@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// Префиксы разделяют хеши листьев и внутренних узлов,
+// чтобы внутренний узел нельзя было выдать за транзакцию
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+type Hash = [u8; 32];
+
+/// Шаг доказательства: хеш соседнего узла и его сторона
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProofStep {
+    pub hash: String,
+    pub is_left: bool,
+}
+
+/// Доказательство включения транзакции в дерево Меркла блока
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MerkleProof {
+    pub index: usize,
+    pub steps: Vec<ProofStep>,
+}
+
+fn hash_leaf(leaf: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(leaf);
+    hasher.finalize().into()
+}
+
+fn hash_node(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Следующий уровень дерева. Непарный последний узел переносится наверх без изменений.
+fn next_level(level: &[Hash]) -> Vec<Hash> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => hash_node(left, right),
+            [single] => *single,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+fn parse_hash(hex: &str) -> Option<Hash> {
+    from_hex(hex)?.try_into().ok()
+}
+
+fn parse_leaves(leaves: &[String]) -> Option<Vec<Hash>> {
+    leaves.iter().map(|leaf| parse_hash(leaf).map(|hash| hash_leaf(&hash))).collect()
+}
+
+/// Корень дерева Меркла по hex-хешам транзакций. Для пустого списка — SHA-256 пустой строки.
+pub fn merkle_root(leaves: &[String]) -> String {
+    let mut level = match parse_leaves(leaves) {
+        Some(level) => level,
+        None => return String::new(),
+    };
+    if level.is_empty() {
+        return to_hex(&Sha256::digest([]));
+    }
+
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    to_hex(&level[0])
+}
+
+/// Строит доказательство включения листа с номером `index`
+pub fn build_proof(leaves: &[String], index: usize) -> Option<MerkleProof> {
+    let mut level = parse_leaves(leaves)?;
+    if index >= level.len() {
+        return None;
+    }
+
+    let mut steps = Vec::new();
+    let mut position = index;
+    while level.len() > 1 {
+        let sibling = position ^ 1;
+        if sibling < level.len() {
+            steps.push(ProofStep { hash: to_hex(&level[sibling]), is_left: sibling < position });
+        }
+        level = next_level(&level);
+        position /= 2;
+    }
+    Some(MerkleProof { index, steps })
+}
+
+/// Проверяет, что транзакция с хешем `leaf` входит в дерево с корнем `root`
+pub fn verify_proof(leaf: &str, proof: &MerkleProof, root: &str) -> bool {
+    let mut current = match parse_hash(leaf) {
+        Some(hash) => hash_leaf(&hash),
+        None => return false,
+    };
+
+    for step in &proof.steps {
+        let sibling = match parse_hash(&step.hash) {
+            Some(hash) => hash,
+            None => return false,
+        };
+        current = if step.is_left {
+            hash_node(&sibling, &current)
+        } else {
+            hash_node(&current, &sibling)
+        };
+    }
+    to_hex(&current) == root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(count: usize) -> Vec<String> {
+        (0..count).map(|i| to_hex(&Sha256::digest(format!("tx{}", i)))).collect()
+    }
+
+    #[test]
+    fn test_hex_roundtrip() {
+        let bytes = vec![0x00, 0x7f, 0xff];
+        assert_eq!(to_hex(&bytes), "007fff");
+        assert_eq!(from_hex("007fff"), Some(bytes));
+        assert_eq!(from_hex("0g"), None);
+        assert_eq!(from_hex("abc"), None);
+    }
+
+    #[test]
+    fn test_root_depends_on_order() {
+        let mut items = leaves(4);
+        let root = merkle_root(&items);
+        items.swap(0, 1);
+        assert_ne!(root, merkle_root(&items));
+    }
+
+    #[test]
+    fn test_single_leaf_root() {
+        let items = leaves(1);
+        let proof = build_proof(&items, 0).unwrap();
+
+        assert!(proof.steps.is_empty());
+        assert!(verify_proof(&items[0], &proof, &merkle_root(&items)));
+    }
+
+    #[test]
+    fn test_proofs_for_every_leaf() {
+        for count in 1..=9 {
+            let items = leaves(count);
+            let root = merkle_root(&items);
+            for index in 0..count {
+                let proof = build_proof(&items, index).unwrap();
+                assert!(verify_proof(&items[index], &proof, &root), "count {} index {}", count, index);
+            }
+        }
+    }
+
+    #[test]
+    fn test_proof_rejects_other_leaf_and_root() {
+        let items = leaves(5);
+        let root = merkle_root(&items);
+        let proof = build_proof(&items, 2).unwrap();
+
+        assert!(!verify_proof(&items[3], &proof, &root));
+        assert!(!verify_proof(&items[2], &proof, &merkle_root(&leaves(6))));
+        assert!(build_proof(&items, 5).is_none());
+    }
+}
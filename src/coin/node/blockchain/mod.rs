@@ -1,4 +1,18 @@
+pub mod amount;
 pub mod block;
 pub mod transaction;
 pub mod wallet;
 pub mod blockchain;
+pub mod ledger;
+pub mod emission;
+pub mod difficulty;
+pub mod merkle;
+pub mod orphan;
+pub mod validation;
+pub mod multisig;
+pub mod escrow;
+pub mod htlc;
+pub mod script;
+pub mod crypto;
+pub mod address;
+pub mod genesis;
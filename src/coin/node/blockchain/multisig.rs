@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::coin::node::blockchain::crypto::Verifier;
+use crate::coin::node::blockchain::address;
+use crate::coin::node::blockchain::transaction::{decode_public_key, write_bytes};
+
+/// Префикс адреса multisig-счёта в реестре
+pub const MULTISIG_PREFIX: &str = "multisig:";
+/// Наибольшее число ключей multisig-счёта
+pub const MAX_MULTISIG_KEYS: usize = 15;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum MultisigError {
+    #[error("Порог {threshold} должен быть от 1 до числа ключей {keys}")]
+    InvalidThreshold { threshold: usize, keys: usize },
+    #[error("Слишком много ключей: {count}, допустимо не больше {max}")]
+    TooManyKeys { count: usize, max: usize },
+    #[error("Ключи multisig-счёта должны быть различны и упорядочены")]
+    NonCanonicalKeys,
+    #[error("Некорректный ключ multisig-счёта: {0}")]
+    InvalidKey(String),
+    #[error("Ключ {0} не входит в multisig-счёт")]
+    UnknownKey(String),
+    #[error("Подпись ключа {0} не подходит к транзакции")]
+    InvalidSignature(String),
+    #[error("Транзакция тратит не с multisig-счёта")]
+    NotMultisig,
+}
+
+/// Multisig-счёт: публичные ключи участников (с меткой алгоритма, алгоритмы можно смешивать)
+/// и число подписей, достаточное для траты.
+/// Ключи хранятся отсортированными, поэтому один и тот же набор всегда даёт один адрес.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MultisigPolicy {
+    threshold: u32,
+    keys: Vec<String>,
+}
+
+/// Подпись транзакции одним из участников multisig-счёта
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PartialSignature {
+    pub key: String,
+    pub signature: String,
+}
+
+impl MultisigPolicy {
+    pub fn new(threshold: usize, keys: Vec<String>) -> Result<MultisigPolicy, MultisigError> {
+        let mut keys: Vec<String> = keys.into_iter().map(|key| key.trim().to_string()).collect();
+        keys.sort();
+        keys.dedup();
+        let threshold = u32::try_from(threshold)
+            .map_err(|_| MultisigError::InvalidThreshold { threshold, keys: keys.len() })?;
+
+        let policy = MultisigPolicy { threshold, keys };
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    /// Проверка счёта, полученного из сети: число ключей, порог, порядок ключей и их формат
+    pub fn validate(&self) -> Result<(), MultisigError> {
+        let count = self.keys.len();
+        if count > MAX_MULTISIG_KEYS {
+            return Err(MultisigError::TooManyKeys { count, max: MAX_MULTISIG_KEYS });
+        }
+        if self.threshold == 0 || self.get_threshold() > count {
+            return Err(MultisigError::InvalidThreshold { threshold: self.get_threshold(), keys: count });
+        }
+        if self.keys.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(MultisigError::NonCanonicalKeys);
+        }
+        for key in &self.keys {
+            decode_public_key(key).map_err(|_| MultisigError::InvalidKey(key.clone()))?;
+        }
+        Ok(())
+    }
+
+    pub fn get_threshold(&self) -> usize {
+        self.threshold as usize
+    }
+
+    pub fn get_keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.keys.binary_search_by(|probe| probe.as_str().cmp(key)).is_ok()
+    }
+
+    /// Каноническое представление: порог, число ключей (u32, big-endian) и ключи с префиксом длины
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.threshold.to_be_bytes());
+        bytes.extend_from_slice(&(self.keys.len() as u32).to_be_bytes());
+        for key in &self.keys {
+            write_bytes(&mut bytes, key.as_bytes());
+        }
+        bytes
+    }
+
+    /// Адрес счёта в реестре: префикс и Base58Check от SHA-256 канонического представления
+    pub fn get_address(&self) -> String {
+        address::multisig_address(&Sha256::digest(self.encode()))
+    }
+
+    /// Подпись участника `partial` верна для данных `payload`
+    pub fn check_signature(&self, payload: &[u8], partial: &PartialSignature) -> Result<(), MultisigError> {
+        if !self.contains_key(&partial.key) {
+            return Err(MultisigError::UnknownKey(partial.key.clone()));
+        }
+        let key = decode_public_key(&partial.key).map_err(|_| MultisigError::InvalidKey(partial.key.clone()))?;
+        if !key.verify(payload, &partial.signature) {
+            return Err(MultisigError::InvalidSignature(partial.key.clone()));
+        }
+        Ok(())
+    }
+
+    /// Хватает ли верных подписей для траты; каждый участник учитывается один раз
+    pub fn verify(&self, payload: &[u8], signatures: &[PartialSignature]) -> bool {
+        let mut signers: Vec<&str> = signatures
+            .iter()
+            .filter(|partial| self.check_signature(payload, partial).is_ok())
+            .map(|partial| partial.key.as_str())
+            .collect();
+        signers.sort_unstable();
+        signers.dedup();
+        signers.len() >= self.get_threshold()
+    }
+}
+
+/// Принадлежит ли адрес multisig-счёту
+pub fn is_multisig_address(account: &str) -> bool {
+    account.starts_with(MULTISIG_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coin::node::blockchain::crypto::{PrivateKey, SignatureAlgorithm, Signer};
+
+    fn generate_key() -> (PrivateKey, String) {
+        let private_key = PrivateKey::generate(SignatureAlgorithm::Ed25519);
+        let public_key = private_key.get_public_key().encode();
+        (private_key, public_key)
+    }
+
+    #[test]
+    fn test_address_does_not_depend_on_key_order() {
+        let keys: Vec<String> = (0..3).map(|_| generate_key().1).collect();
+        let reversed: Vec<String> = keys.iter().rev().cloned().collect();
+
+        let policy = MultisigPolicy::new(2, keys.clone()).unwrap();
+        assert_eq!(policy.get_address(), MultisigPolicy::new(2, reversed).unwrap().get_address());
+        assert_ne!(policy.get_address(), MultisigPolicy::new(3, keys).unwrap().get_address());
+        assert!(is_multisig_address(&policy.get_address()));
+    }
+
+    #[test]
+    fn test_rejects_invalid_policy() {
+        let key = generate_key().1;
+        assert_eq!(
+            MultisigPolicy::new(2, vec![key.clone(), key.clone()]),
+            Err(MultisigError::InvalidThreshold { threshold: 2, keys: 1 })
+        );
+        assert!(matches!(MultisigPolicy::new(0, vec![key.clone()]), Err(MultisigError::InvalidThreshold { .. })));
+        assert!(matches!(MultisigPolicy::new(1, vec!["not a key".to_string()]), Err(MultisigError::InvalidKey(_))));
+
+        let unordered = MultisigPolicy { threshold: 1, keys: vec![key.clone(), key] };
+        assert_eq!(unordered.validate(), Err(MultisigError::NonCanonicalKeys));
+    }
+
+    #[test]
+    fn test_verify_counts_each_signer_once() {
+        let (first_private, first) = generate_key();
+        let (second_private, second) = generate_key();
+        let (outsider_private, outsider) = generate_key();
+        let policy = MultisigPolicy::new(2, vec![first.clone(), second.clone()]).unwrap();
+        let payload = b"payload";
+
+        let first_signature = PartialSignature { key: first, signature: first_private.sign(payload) };
+        let outsider_signature = PartialSignature { key: outsider.clone(), signature: outsider_private.sign(payload) };
+        assert!(!policy.verify(payload, &[first_signature.clone(), first_signature.clone()]));
+        assert!(!policy.verify(payload, &[first_signature.clone(), outsider_signature.clone()]));
+        assert_eq!(policy.check_signature(payload, &outsider_signature), Err(MultisigError::UnknownKey(outsider)));
+
+        let second_signature = PartialSignature { key: second, signature: second_private.sign(payload) };
+        assert!(policy.verify(payload, &[first_signature.clone(), second_signature.clone()]));
+        assert!(!policy.verify(b"other payload", &[first_signature, second_signature]));
+    }
+}
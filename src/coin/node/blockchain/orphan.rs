@@ -0,0 +1,111 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::coin::node::blockchain::block::Block;
+
+/// Сколько блоков-сирот узел хранит одновременно
+pub const MAX_ORPHANS: usize = 64;
+
+/// Блоки, пришедшие раньше своего родителя, сгруппированные по хешу недостающего родителя.
+/// При переполнении вытесняются самые старые.
+pub struct OrphanPool {
+    by_parent: HashMap<String, Vec<Block>>,
+    // Хеши сирот в порядке поступления: (родитель, блок)
+    order: VecDeque<(String, String)>,
+    limit: usize,
+}
+
+impl OrphanPool {
+    pub fn new(limit: usize) -> OrphanPool {
+        OrphanPool {
+            by_parent: HashMap::new(),
+            order: VecDeque::new(),
+            limit,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.order.iter().any(|(_, orphan)| orphan == hash)
+    }
+
+    /// Сохраняет блок до появления родителя. Возвращает `false`, если блок уже в пуле.
+    pub fn insert(&mut self, block: Block) -> bool {
+        let hash = block.get_hash();
+        if self.limit == 0 || self.contains(&hash) {
+            return false;
+        }
+        while self.order.len() >= self.limit {
+            self.evict_oldest();
+        }
+
+        let parent = block.get_previous_hash();
+        self.order.push_back((parent.clone(), hash));
+        self.by_parent.entry(parent).or_default().push(block);
+        true
+    }
+
+    /// Забирает из пула все блоки, ожидающие родителя `parent_hash`
+    pub fn take_children(&mut self, parent_hash: &str) -> Vec<Block> {
+        let children = self.by_parent.remove(parent_hash).unwrap_or_default();
+        self.order.retain(|(parent, _)| parent != parent_hash);
+        children
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some((parent, hash)) = self.order.pop_front()
+            && let Some(children) = self.by_parent.get_mut(&parent) {
+            children.retain(|block| block.get_hash() != hash);
+            if children.is_empty() {
+                self.by_parent.remove(&parent);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coin::node::blockchain::difficulty::INITIAL_TARGET;
+
+    fn orphan(id: usize, parent: &str) -> Block {
+        Block::new(id, Vec::new(), parent.to_string(), id as u64, INITIAL_TARGET)
+    }
+
+    #[test]
+    fn test_children_taken_by_parent() {
+        let mut pool = OrphanPool::new(MAX_ORPHANS);
+        assert!(pool.insert(orphan(3, "parent_a")));
+        assert!(pool.insert(orphan(4, "parent_a")));
+        assert!(pool.insert(orphan(5, "parent_b")));
+
+        let children = pool.take_children("parent_a");
+        assert_eq!(children.len(), 2);
+        assert_eq!(pool.len(), 1);
+        assert!(pool.take_children("parent_a").is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_orphan_ignored() {
+        let mut pool = OrphanPool::new(MAX_ORPHANS);
+        let block = orphan(3, "parent");
+        assert!(pool.insert(block.clone()));
+        assert!(!pool.insert(block));
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn test_oldest_orphan_evicted_when_full() {
+        let mut pool = OrphanPool::new(2);
+        let oldest = orphan(3, "parent_a");
+        pool.insert(oldest.clone());
+        pool.insert(orphan(4, "parent_b"));
+        pool.insert(orphan(5, "parent_c"));
+
+        assert_eq!(pool.len(), 2);
+        assert!(!pool.contains(&oldest.get_hash()), "Самый старый блок должен быть вытеснен");
+        assert!(pool.take_children("parent_a").is_empty());
+    }
+}
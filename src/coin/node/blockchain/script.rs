@@ -0,0 +1,458 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::coin::node::blockchain::address;
+use crate::coin::node::blockchain::transaction::{lock_reached, write_bytes};
+
+/// Префикс адреса скрипт-счёта в реестре
+pub const SCRIPT_PREFIX: &str = "script:";
+/// Газ, доступный скриптам одной транзакции
+pub const MAX_SCRIPT_GAS: u64 = 10_000;
+/// Наибольший размер скрипта в байтах канонического представления
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+/// Наибольшее число элементов на стеке
+pub const MAX_STACK_ITEMS: usize = 256;
+/// Наибольший размер элемента стека в байтах
+pub const MAX_ITEM_SIZE: usize = 1024;
+
+/// Стоимость проверки подписи
+const CHECK_SIG_GAS: u64 = 500;
+/// Стоимость хеширования
+const HASH_GAS: u64 = 20;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ScriptError {
+    #[error("Размер скрипта {size} байт превышает {max}")]
+    TooLarge { size: usize, max: usize },
+    #[error("Скрипту не хватило газа: лимит {limit}")]
+    OutOfGas { limit: u64 },
+    #[error("Недостаточно элементов на стеке")]
+    StackUnderflow,
+    #[error("На стеке больше {max} элементов")]
+    StackOverflow { max: usize },
+    #[error("Элемент стека размером {size} байт превышает {max}")]
+    ItemTooLarge { size: usize, max: usize },
+    #[error("Элемент стека не является числом")]
+    InvalidNumber,
+    #[error("Переполнение числа")]
+    NumberOverflow,
+    #[error("Непарные If/Else/EndIf")]
+    UnbalancedConditional,
+    #[error("Проверка Verify не прошла")]
+    VerifyFailed,
+    #[error("Граница {0} ещё не наступила")]
+    LockTimeNotReached(u64),
+    #[error("Скрипт остановлен операцией Return")]
+    Returned,
+    #[error("Разблокирующий скрипт может только класть данные на стек")]
+    NotPushOnly,
+    #[error("Скрипт завершился без истины на вершине стека")]
+    Failed,
+    #[error("Транзакция тратит не со скрипт-счёта")]
+    NotScript,
+}
+
+/// Операция стековой машины. Элементы стека — байты; числа — u64 в big-endian
+/// (до 8 байт, пустой элемент — ноль), истина — любой ненулевой байт.
+/// Новые операции добавляются только в конец, номер операции входит в представление скрипта
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    /// Кладёт данные на стек
+    Push(Vec<u8>),
+    /// Кладёт число
+    PushNumber(u64),
+    Dup,
+    Drop,
+    Swap,
+    /// Кладёт истину, если два верхних элемента равны
+    Equal,
+    /// Снимает элемент и останавливает скрипт, если он ложен
+    Verify,
+    EqualVerify,
+    Not,
+    /// Выполняет ветку, если снятый элемент истинен
+    If,
+    Else,
+    EndIf,
+    Add,
+    Sub,
+    /// Кладёт истину, если второй элемент меньше верхнего
+    LessThan,
+    /// Заменяет верхний элемент его SHA-256
+    Sha256,
+    /// Снимает ключ и под ним подпись (оба с меткой алгоритма), кладёт результат проверки
+    /// подписи данных транзакции
+    CheckSig,
+    /// Останавливает скрипт, если граница на вершине стека (высота или время Unix) не наступила;
+    /// элемент остаётся на стеке
+    CheckLockTime,
+    /// Кладёт высоту блока
+    Height,
+    /// Кладёт время блока
+    Time,
+    /// Останавливает скрипт с ошибкой
+    Return,
+}
+
+impl Op {
+    fn gas_cost(&self) -> u64 {
+        match self {
+            Op::Push(data) => 1 + data.len() as u64 / 32,
+            Op::Sha256 => HASH_GAS,
+            Op::CheckSig => CHECK_SIG_GAS,
+            _ => 1,
+        }
+    }
+
+    fn is_push(&self) -> bool {
+        matches!(self, Op::Push(_) | Op::PushNumber(_))
+    }
+
+    /// Каноническое представление: номер операции и данные
+    fn encode_into(&self, bytes: &mut Vec<u8>) {
+        let tag = match self {
+            Op::Push(data) => {
+                bytes.push(0);
+                write_bytes(bytes, data);
+                return;
+            }
+            Op::PushNumber(number) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&number.to_be_bytes());
+                return;
+            }
+            Op::Dup => 2,
+            Op::Drop => 3,
+            Op::Swap => 4,
+            Op::Equal => 5,
+            Op::Verify => 6,
+            Op::EqualVerify => 7,
+            Op::Not => 8,
+            Op::If => 9,
+            Op::Else => 10,
+            Op::EndIf => 11,
+            Op::Add => 12,
+            Op::Sub => 13,
+            Op::LessThan => 14,
+            Op::Sha256 => 15,
+            Op::CheckSig => 16,
+            Op::CheckLockTime => 17,
+            Op::Height => 18,
+            Op::Time => 19,
+            Op::Return => 20,
+        };
+        bytes.push(tag);
+    }
+}
+
+/// Скрипт: последовательность операций.
+/// Запирающий скрипт задаёт условие траты со скрипт-счёта, разблокирующий кладёт на стек
+/// подписи, прообразы и другие данные, которые это условие проверяет
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct Script(Vec<Op>);
+
+impl From<Vec<Op>> for Script {
+    fn from(ops: Vec<Op>) -> Script {
+        Script(ops)
+    }
+}
+
+impl Script {
+    pub fn get_ops(&self) -> &[Op] {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Каноническое представление: число операций (u32, big-endian) и операции
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(self.0.len() as u32).to_be_bytes());
+        for op in &self.0 {
+            op.encode_into(&mut bytes);
+        }
+        bytes
+    }
+
+    /// Адрес счёта, запертого этим скриптом: префикс и Base58Check от SHA-256 канонического представления
+    pub fn get_address(&self) -> String {
+        address::script_address(&Sha256::digest(self.encode()))
+    }
+}
+
+/// Принадлежит ли адрес скрипт-счёту
+pub fn is_script_address(account: &str) -> bool {
+    account.starts_with(SCRIPT_PREFIX)
+}
+
+/// Доступ скрипта к транзакции и блоку, в котором она проверяется
+pub trait ScriptHost {
+    /// Верна ли подпись `signature` данных транзакции ключом `key`
+    fn check_signature(&self, key: &[u8], signature: &[u8]) -> bool;
+    fn get_height(&self) -> u64;
+    fn get_time(&self) -> i64;
+}
+
+/// Выполняет разблокирующий скрипт, затем запирающий на том же стеке.
+/// Трата разрешена, если оба скрипта выполнились и на вершине стека истина.
+/// Возвращает израсходованный газ
+pub fn execute(unlocking: &Script, locking: &Script, host: &impl ScriptHost, gas_limit: u64) -> Result<u64, ScriptError> {
+    for script in [unlocking, locking] {
+        let size = script.encode().len();
+        if size > MAX_SCRIPT_SIZE {
+            return Err(ScriptError::TooLarge { size, max: MAX_SCRIPT_SIZE });
+        }
+    }
+    // Иначе разблокирующий скрипт мог бы обойти проверки запирающего
+    if !unlocking.get_ops().iter().all(Op::is_push) {
+        return Err(ScriptError::NotPushOnly);
+    }
+
+    let mut machine = Machine { stack: Vec::new(), gas_used: 0, gas_limit };
+    machine.run(unlocking, host)?;
+    machine.run(locking, host)?;
+    match machine.stack.last() {
+        Some(top) if is_true(top) => Ok(machine.gas_used),
+        _ => Err(ScriptError::Failed),
+    }
+}
+
+struct Machine {
+    stack: Vec<Vec<u8>>,
+    gas_used: u64,
+    gas_limit: u64,
+}
+
+impl Machine {
+    fn run(&mut self, script: &Script, host: &impl ScriptHost) -> Result<(), ScriptError> {
+        // Условия открытых If: операции выполняются, только если все они истинны
+        let mut conditions: Vec<bool> = Vec::new();
+        for op in script.get_ops() {
+            self.gas_used += op.gas_cost();
+            if self.gas_used > self.gas_limit {
+                return Err(ScriptError::OutOfGas { limit: self.gas_limit });
+            }
+
+            let executing = conditions.iter().all(|condition| *condition);
+            match op {
+                Op::If => {
+                    let condition = executing && is_true(&self.pop()?);
+                    conditions.push(condition);
+                }
+                Op::Else => {
+                    let condition = conditions.last_mut().ok_or(ScriptError::UnbalancedConditional)?;
+                    *condition = !*condition;
+                }
+                Op::EndIf => {
+                    conditions.pop().ok_or(ScriptError::UnbalancedConditional)?;
+                }
+                _ if executing => self.step(op, host)?,
+                _ => {}
+            }
+        }
+        if !conditions.is_empty() {
+            return Err(ScriptError::UnbalancedConditional);
+        }
+        Ok(())
+    }
+
+    fn step(&mut self, op: &Op, host: &impl ScriptHost) -> Result<(), ScriptError> {
+        match op {
+            Op::Push(data) => self.push(data.clone())?,
+            Op::PushNumber(number) => self.push(from_number(*number))?,
+            Op::Dup => {
+                let top = self.stack.last().cloned().ok_or(ScriptError::StackUnderflow)?;
+                self.push(top)?;
+            }
+            Op::Drop => {
+                self.pop()?;
+            }
+            Op::Swap => {
+                let top = self.pop()?;
+                let second = self.pop()?;
+                self.push(top)?;
+                self.push(second)?;
+            }
+            Op::Equal => {
+                let equal = self.pop()? == self.pop()?;
+                self.push(from_bool(equal))?;
+            }
+            Op::Verify => {
+                if !is_true(&self.pop()?) {
+                    return Err(ScriptError::VerifyFailed);
+                }
+            }
+            Op::EqualVerify => {
+                if self.pop()? != self.pop()? {
+                    return Err(ScriptError::VerifyFailed);
+                }
+            }
+            Op::Not => {
+                let value = is_true(&self.pop()?);
+                self.push(from_bool(!value))?;
+            }
+            Op::Add | Op::Sub | Op::LessThan => {
+                let right = to_number(&self.pop()?)?;
+                let left = to_number(&self.pop()?)?;
+                let result = match op {
+                    Op::Add => from_number(left.checked_add(right).ok_or(ScriptError::NumberOverflow)?),
+                    Op::Sub => from_number(left.checked_sub(right).ok_or(ScriptError::NumberOverflow)?),
+                    _ => from_bool(left < right),
+                };
+                self.push(result)?;
+            }
+            Op::Sha256 => {
+                let data = self.pop()?;
+                self.push(Sha256::digest(data).to_vec())?;
+            }
+            Op::CheckSig => {
+                let key = self.pop()?;
+                let signature = self.pop()?;
+                self.push(from_bool(host.check_signature(&key, &signature)))?;
+            }
+            Op::CheckLockTime => {
+                let lock = to_number(self.stack.last().ok_or(ScriptError::StackUnderflow)?)?;
+                if !lock_reached(lock, host.get_height(), host.get_time()) {
+                    return Err(ScriptError::LockTimeNotReached(lock));
+                }
+            }
+            Op::Height => self.push(from_number(host.get_height()))?,
+            Op::Time => self.push(from_number(u64::try_from(host.get_time()).unwrap_or(0)))?,
+            Op::Return => return Err(ScriptError::Returned),
+            Op::If | Op::Else | Op::EndIf => unreachable!("Условия обрабатывает run"),
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, item: Vec<u8>) -> Result<(), ScriptError> {
+        if item.len() > MAX_ITEM_SIZE {
+            return Err(ScriptError::ItemTooLarge { size: item.len(), max: MAX_ITEM_SIZE });
+        }
+        if self.stack.len() >= MAX_STACK_ITEMS {
+            return Err(ScriptError::StackOverflow { max: MAX_STACK_ITEMS });
+        }
+        self.stack.push(item);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<Vec<u8>, ScriptError> {
+        self.stack.pop().ok_or(ScriptError::StackUnderflow)
+    }
+}
+
+fn is_true(item: &[u8]) -> bool {
+    item.iter().any(|byte| *byte != 0)
+}
+
+fn from_bool(value: bool) -> Vec<u8> {
+    if value { vec![1] } else { Vec::new() }
+}
+
+fn to_number(item: &[u8]) -> Result<u64, ScriptError> {
+    if item.len() > 8 {
+        return Err(ScriptError::InvalidNumber);
+    }
+    Ok(item.iter().fold(0, |number, byte| (number << 8) | *byte as u64))
+}
+
+fn from_number(number: u64) -> Vec<u8> {
+    number.to_be_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestHost {
+        height: u64,
+    }
+
+    impl ScriptHost for TestHost {
+        fn check_signature(&self, key: &[u8], signature: &[u8]) -> bool {
+            key == b"key" && signature == b"signature"
+        }
+
+        fn get_height(&self) -> u64 {
+            self.height
+        }
+
+        fn get_time(&self) -> i64 {
+            0
+        }
+    }
+
+    /// Продавец тратит с прообразом, отправитель — после высоты 10
+    fn hashlock_script() -> Script {
+        Script::from(vec![
+            Op::If,
+            Op::Sha256,
+            Op::Push(Sha256::digest(b"secret").to_vec()),
+            Op::EqualVerify,
+            Op::Push(b"key".to_vec()),
+            Op::Else,
+            Op::PushNumber(10),
+            Op::CheckLockTime,
+            Op::Drop,
+            Op::Push(b"key".to_vec()),
+            Op::EndIf,
+            Op::CheckSig,
+        ])
+    }
+
+    #[test]
+    fn test_hashlock_and_timelock_branches() {
+        let host = TestHost { height: 5 };
+        let claim = Script::from(vec![Op::Push(b"signature".to_vec()), Op::Push(b"secret".to_vec()), Op::PushNumber(1)]);
+        assert!(execute(&claim, &hashlock_script(), &host, MAX_SCRIPT_GAS).is_ok());
+
+        let wrong = Script::from(vec![Op::Push(b"signature".to_vec()), Op::Push(b"guess".to_vec()), Op::PushNumber(1)]);
+        assert_eq!(execute(&wrong, &hashlock_script(), &host, MAX_SCRIPT_GAS), Err(ScriptError::VerifyFailed));
+
+        let refund = Script::from(vec![Op::Push(b"signature".to_vec()), Op::PushNumber(0)]);
+        assert_eq!(execute(&refund, &hashlock_script(), &host, MAX_SCRIPT_GAS), Err(ScriptError::LockTimeNotReached(10)));
+        assert!(execute(&refund, &hashlock_script(), &TestHost { height: 10 }, MAX_SCRIPT_GAS).is_ok());
+
+        let forged = Script::from(vec![Op::Push(b"forged".to_vec()), Op::PushNumber(0)]);
+        assert_eq!(execute(&forged, &hashlock_script(), &TestHost { height: 10 }, MAX_SCRIPT_GAS), Err(ScriptError::Failed));
+    }
+
+    #[test]
+    fn test_gas_is_metered() {
+        let host = TestHost { height: 0 };
+        let unlocking = Script::from(vec![Op::Push(b"signature".to_vec()), Op::Push(b"key".to_vec())]);
+        let locking = Script::from(vec![Op::CheckSig]);
+
+        let used = execute(&unlocking, &locking, &host, MAX_SCRIPT_GAS).unwrap();
+        assert_eq!(used, 2 + CHECK_SIG_GAS);
+        assert_eq!(execute(&unlocking, &locking, &host, used - 1), Err(ScriptError::OutOfGas { limit: used - 1 }));
+    }
+
+    #[test]
+    fn test_rejects_malformed_scripts() {
+        let host = TestHost { height: 0 };
+        let empty = Script::default();
+        let truthy = Script::from(vec![Op::PushNumber(1)]);
+
+        let unlocking = Script::from(vec![Op::PushNumber(1), Op::Return]);
+        assert_eq!(execute(&unlocking, &truthy, &host, MAX_SCRIPT_GAS), Err(ScriptError::NotPushOnly));
+        assert_eq!(execute(&empty, &Script::from(vec![Op::PushNumber(1), Op::If]), &host, MAX_SCRIPT_GAS), Err(ScriptError::UnbalancedConditional));
+        assert_eq!(execute(&empty, &Script::from(vec![Op::Dup]), &host, MAX_SCRIPT_GAS), Err(ScriptError::StackUnderflow));
+        assert_eq!(execute(&empty, &Script::from(vec![Op::PushNumber(1), Op::Return]), &host, MAX_SCRIPT_GAS), Err(ScriptError::Returned));
+        assert_eq!(
+            execute(&empty, &Script::from(vec![Op::PushNumber(0), Op::PushNumber(1), Op::Sub]), &host, MAX_SCRIPT_GAS),
+            Err(ScriptError::NumberOverflow)
+        );
+        assert_eq!(execute(&empty, &empty, &host, MAX_SCRIPT_GAS), Err(ScriptError::Failed));
+    }
+
+    #[test]
+    fn test_address_commits_to_script() {
+        let script = hashlock_script();
+        assert!(is_script_address(&script.get_address()));
+        assert_eq!(script.get_address(), hashlock_script().get_address());
+        assert_ne!(script.get_address(), Script::from(vec![Op::PushNumber(1)]).get_address());
+    }
+}
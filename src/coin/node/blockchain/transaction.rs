@@ -2,128 +2,362 @@ use std::cmp::Ordering;
 use std::fmt;
 use std::fmt::Formatter;
 
-use base64;
-use base64::Engine;
-use base64::engine::general_purpose::STANDARD_NO_PAD;
-use rsa::{PaddingScheme, PublicKey, RsaPrivateKey, RsaPublicKey};
-use rsa::pkcs1::{DecodeRsaPublicKey, EncodeRsaPublicKey, LineEnding};
-use rsa::signature::digest::Digest;
+use log::warn;
+use sha2::Digest;
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
+use thiserror::Error;
+
+use crate::coin::node::blockchain::address;
+use crate::coin::node::blockchain::amount::Amount;
+use crate::coin::node::blockchain::crypto::{PublicKey, Signer, Verifier};
+use crate::coin::node::blockchain::merkle;
+use crate::coin::node::blockchain::multisig::{is_multisig_address, MultisigError, MultisigPolicy, PartialSignature};
+use crate::coin::node::blockchain::script::{self, is_script_address, Script, ScriptError, ScriptHost, MAX_SCRIPT_GAS};
+
+/// Вид транзакции. Обычный перевод зачисляет `transfer` продавцу,
+/// остальные виды открывают и закрывают условное депонирование между `buyer` и `seller`
+/// или контракт с хеш- и тайм-локом для атомарного обмена.
+/// Новые виды добавляются только в конец, чтобы не менять представление сохранённых транзакций
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub enum TransactionKind {
+    #[default]
+    Transfer,
+    /// Покупатель блокирует `transfer` для продавца; `timeout` — высота блока или время Unix
+    EscrowLock { timeout: u64 },
+    /// Покупатель подтверждает сделку: средства депонирования уходят продавцу
+    EscrowRelease { escrow_id: String },
+    /// Продавец отказывается от сделки: средства возвращаются покупателю
+    EscrowRefund { escrow_id: String },
+    /// Покупатель забирает средства после срока депонирования
+    EscrowReclaim { escrow_id: String },
+    /// Отправитель блокирует `transfer` для продавца до раскрытия прообраза `hashlock` (SHA-256 в hex);
+    /// `timeout` — высота блока или время Unix, после которого средства можно вернуть
+    HtlcLock { hashlock: String, timeout: u64 },
+    /// Продавец забирает средства контракта, раскрывая прообраз хешлока (в hex)
+    HtlcClaim { htlc_id: String, preimage: String },
+    /// Отправитель возвращает средства контракта после срока
+    HtlcRefund { htlc_id: String },
+}
+
+impl TransactionKind {
+    /// Идентификатор депонирования (транзакции `EscrowLock`), которое закрывает транзакция
+    pub fn get_escrow_id(&self) -> Option<&str> {
+        match self {
+            TransactionKind::EscrowRelease { escrow_id }
+            | TransactionKind::EscrowRefund { escrow_id }
+            | TransactionKind::EscrowReclaim { escrow_id } => Some(escrow_id),
+            _ => None,
+        }
+    }
+
+    /// Идентификатор контракта (транзакции `HtlcLock`), который закрывает транзакция
+    pub fn get_htlc_id(&self) -> Option<&str> {
+        match self {
+            TransactionKind::HtlcClaim { htlc_id, .. } | TransactionKind::HtlcRefund { htlc_id } => Some(htlc_id),
+            _ => None,
+        }
+    }
+
+    /// Каноническое представление: номер вида и его поля
+    fn encode_into(&self, bytes: &mut Vec<u8>) {
+        match self {
+            TransactionKind::Transfer => bytes.push(0),
+            TransactionKind::EscrowLock { timeout } => {
+                bytes.push(1);
+                bytes.extend_from_slice(&timeout.to_be_bytes());
+            }
+            TransactionKind::EscrowRelease { escrow_id } => {
+                bytes.push(2);
+                write_bytes(bytes, escrow_id.as_bytes());
+            }
+            TransactionKind::EscrowRefund { escrow_id } => {
+                bytes.push(3);
+                write_bytes(bytes, escrow_id.as_bytes());
+            }
+            TransactionKind::EscrowReclaim { escrow_id } => {
+                bytes.push(4);
+                write_bytes(bytes, escrow_id.as_bytes());
+            }
+            TransactionKind::HtlcLock { hashlock, timeout } => {
+                bytes.push(5);
+                write_bytes(bytes, hashlock.as_bytes());
+                bytes.extend_from_slice(&timeout.to_be_bytes());
+            }
+            TransactionKind::HtlcClaim { htlc_id, preimage } => {
+                bytes.push(6);
+                write_bytes(bytes, htlc_id.as_bytes());
+                write_bytes(bytes, preimage.as_bytes());
+            }
+            TransactionKind::HtlcRefund { htlc_id } => {
+                bytes.push(7);
+                write_bytes(bytes, htlc_id.as_bytes());
+            }
+        }
+    }
+}
+
+/// Дополнительный получатель перевода: адрес счёта и зачисляемая сумма
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TransactionOutput {
+    pub recipient: String,
+    pub amount: Amount,
+}
+
+/// Владелец списываемых средств: один ключ, multisig-счёт или скрипт-счёт
+#[derive(Debug, Clone)]
+pub enum Spender {
+    Key(PublicKey),
+    Multisig(MultisigPolicy),
+    Script(Script),
+}
 
 #[derive(Debug, Clone)]
 pub struct Transaction {
-    sender: RsaPublicKey,
-    buyer: RsaPublicKey,
-    seller: RsaPublicKey,
+    sender: Spender,
+    // Адреса счетов покупателя и продавца
+    buyer: String,
+    seller: String,
     message: String,
-    transfer: f64,
+    transfer: Amount,
+    // Получатели перевода помимо продавца
+    outputs: Vec<TransactionOutput>,
+    fee: Amount,
+    nonce: u64,
+    valid_after: Option<u64>,
+    valid_until: Option<u64>,
+    kind: TransactionKind,
     signature: String,
+    // Подписи участников, если средства списываются с multisig-счёта
+    cosignatures: Vec<PartialSignature>,
+    // Данные для запирающего скрипта, если средства списываются со скрипт-счёта
+    unlocking_script: Script,
+}
+
+/// Метка подписываемых данных транзакции, отличает их от любых других подписей тем же ключом
+pub const SIGNING_DOMAIN: &[u8] = b"my_blockchain/transaction";
+/// Версия формата подписываемых данных
+pub const SIGNING_VERSION: u8 = 1;
+
+/// Границы окна действия транзакции меньше этого значения — высоты блоков,
+/// остальные — время в секундах Unix
+pub const LOCK_TIME_THRESHOLD: u64 = 500_000_000;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ValidityError {
+    #[error("Транзакция {txid} действует только с {valid_after}")]
+    NotYetValid { txid: String, valid_after: u64 },
+    #[error("Срок действия транзакции {txid} истёк после {valid_until}")]
+    Expired { txid: String, valid_until: u64 },
+}
+
+/// Наступила ли граница `lock` для блока с высотой `height` и временем `time`
+pub fn lock_reached(lock: u64, height: u64, time: i64) -> bool {
+    if lock < LOCK_TIME_THRESHOLD {
+        height >= lock
+    } else {
+        i64::try_from(lock).is_ok_and(|lock| time >= lock)
+    }
 }
 
 impl Transaction {
-    // Создание новой транзакции с конвертацией ключей в строковый формат
-    pub fn new(sender_base64: String, seller_base64: String, buyer_base64: String, message: String, transfer: f64) -> Transaction {
-        let sender = RsaPublicKey::from_pkcs1_der(
-            &STANDARD_NO_PAD.decode(&sender_base64).unwrap()
-        ).expect("Ошибка чтения ключа отправителя");
+    // Создание новой транзакции по ключу отправителя (`<алгоритм>:<base64>`) и адресам продавца и покупателя
+    pub fn new(sender_base64: String, seller_address: String, buyer_address: String, message: String, transfer: Amount) -> Transaction {
+        let sender = decode_public_key(&sender_base64).expect("Ошибка чтения ключа отправителя");
+        let buyer = decode_account(&buyer_address, "покупателя").expect("Ошибка чтения адреса покупателя");
+        let seller = decode_account(&seller_address, "продавца").expect("Ошибка чтения адреса продавца");
 
-        let buyer = RsaPublicKey::from_pkcs1_der(
-            &STANDARD_NO_PAD.decode(&buyer_base64).unwrap()
-        ).expect("Ошибка чтения ключа покупателя");
+        Transaction::with_spender(Spender::Key(sender), seller, buyer, message, transfer)
+    }
 
-        let seller = RsaPublicKey::from_pkcs1_der(
-            &STANDARD_NO_PAD.decode(&seller_base64).unwrap()
-        ).expect("Ошибка чтения ключа продавца");
+    /// Транзакция, списывающая средства с multisig-счёта; её подписывают участники счёта
+    pub fn new_multisig(policy: MultisigPolicy, seller_address: String, buyer_address: String, message: String, transfer: Amount) -> Result<Transaction, String> {
+        let buyer = decode_account(&buyer_address, "покупателя")?;
+        let seller = decode_account(&seller_address, "продавца")?;
+        Ok(Transaction::with_spender(Spender::Multisig(policy), seller, buyer, message, transfer))
+    }
 
+    fn with_spender(sender: Spender, seller: String, buyer: String, message: String, transfer: Amount) -> Transaction {
         Transaction {
             sender,
             buyer,
             seller,
             message,
             transfer,
+            outputs: Vec::new(),
+            fee: Amount::ZERO,
+            nonce: 0,
+            valid_after: None,
+            valid_until: None,
+            kind: TransactionKind::Transfer,
             signature: "".to_string(),
+            cosignatures: Vec::new(),
+            unlocking_script: Script::default(),
         }
     }
 
-    // Подпись транзакции с использованием приватного ключа
-    pub fn sign(&mut self, private_key: RsaPrivateKey) {
-        let sender_der = self.sender.to_pkcs1_der().unwrap();
-        let sender_base64 = STANDARD_NO_PAD.encode(sender_der.as_bytes());
-        // Собираем только данные, которые участвуют в подписи
-        let data_to_sign = format!("{}:{}:{}", sender_base64, self.message, self.transfer);
-        let message_bytes = data_to_sign.into_bytes();
+    /// Добавляет получателя перевода; одна подпись покрывает всех получателей.
+    /// Задаётся до подписи, так как входит в подписанные данные
+    pub fn add_output(&mut self, recipient: String, amount: Amount) -> Result<(), String> {
+        let recipient = decode_account(&recipient, "получателя")?;
+        self.outputs.push(TransactionOutput { recipient, amount });
+        Ok(())
+    }
 
-        // Хешируем данные
-        let mut hasher = Sha256::new();
-        hasher.update(message_bytes);
-        let hashed_message = hasher.finalize();
+    // Первая высота блока (или время), с которой транзакцию можно включить в блок
+    pub fn set_valid_after(&mut self, lock: Option<u64>) {
+        self.valid_after = lock;
+    }
 
-        // Создаем подпись
-        let padding = PaddingScheme::new_pkcs1v15_sign_raw();
-        let signature_bytes = private_key.sign(padding, &hashed_message).expect("Не удалось подписать сообщение");
+    // Последняя высота блока (или время), до которой транзакцию можно включить в блок
+    pub fn set_valid_until(&mut self, lock: Option<u64>) {
+        self.valid_until = lock;
+    }
 
-        // Кодируем подпись в Base64
-        self.signature = STANDARD_NO_PAD.encode(signature_bytes);
+    // Вид транзакции: перевод или действие с депонированием
+    pub fn set_kind(&mut self, kind: TransactionKind) {
+        self.kind = kind;
     }
 
-    // Проверка подписи
-    pub fn verify(&self) -> bool {
-        let sender_der = self.sender.to_pkcs1_der().unwrap();
-        let sender_base64 = STANDARD_NO_PAD.encode(sender_der.as_bytes());
-        // Собираем только данные, которые участвуют в подписи
-        let data_to_sign = format!("{}:{}:{}", sender_base64, self.message, self.transfer);
-        // println!("Transaction data: {}", data_to_sign);
-        let message_bytes = data_to_sign.into_bytes();
+    // Порядковый номер транзакции отправителя; защищает от повторной отправки подписанной транзакции
+    pub fn set_nonce(&mut self, nonce: u64) {
+        self.nonce = nonce;
+    }
 
-        // Хешируем данные
-        let mut hasher = Sha256::new();
-        hasher.update(message_bytes);
-        let hashed_message = hasher.finalize();
+    // Назначение комиссии майнеру; задаётся до подписи, так как входит в подписанные данные
+    pub fn set_fee(&mut self, fee: Amount) {
+        self.fee = fee;
+    }
 
-        // Декодируем подпись из Base64
-        let signature_bytes = STANDARD_NO_PAD.decode(&self.signature).expect("Ошибка декодирования подписи из Base64");
+    /// Подписываемые данные: метка, версия формата, идентификатор сети и все поля транзакции.
+    /// Числа записываются в big-endian, ключ отправителя (с меткой алгоритма), адреса и сообщение предваряются длиной,
+    /// поэтому разные транзакции не могут дать одинаковую последовательность байт.
+    /// Отправитель помечается видом: 0 — ключ, 1 — multisig-счёт (порог и ключи участников),
+    /// 2 — скрипт-счёт (запирающий скрипт). Разблокирующий скрипт несёт подписи и в данные не входит.
+    pub fn signing_payload(&self, chain_id: u32) -> Vec<u8> {
+        let mut payload = Vec::new();
+        write_bytes(&mut payload, SIGNING_DOMAIN);
+        payload.push(SIGNING_VERSION);
+        payload.extend_from_slice(&chain_id.to_be_bytes());
+
+        match &self.sender {
+            Spender::Key(key) => {
+                payload.push(0);
+                write_bytes(&mut payload, key.encode().as_bytes());
+            }
+            Spender::Multisig(policy) => {
+                payload.push(1);
+                write_bytes(&mut payload, &policy.encode());
+            }
+            Spender::Script(script) => {
+                payload.push(2);
+                write_bytes(&mut payload, &script.encode());
+            }
+        }
+        for account in [&self.buyer, &self.seller] {
+            write_bytes(&mut payload, account.as_bytes());
+        }
+        write_bytes(&mut payload, self.message.as_bytes());
+        payload.extend_from_slice(&self.transfer.get_base_units().to_be_bytes());
+        write_outputs(&mut payload, &self.outputs);
+        payload.extend_from_slice(&self.fee.get_base_units().to_be_bytes());
+        payload.extend_from_slice(&self.nonce.to_be_bytes());
+        write_lock(&mut payload, self.valid_after);
+        write_lock(&mut payload, self.valid_until);
+        self.kind.encode_into(&mut payload);
+        payload
+    }
 
-        // Проверяем подпись
-        let padding = PaddingScheme::new_pkcs1v15_sign_raw();
-        self.sender.verify(padding, &hashed_message, &signature_bytes).is_ok()
+    // Подпись транзакции закрытым ключом любого поддерживаемого алгоритма для сети `chain_id`.
+    // Для multisig-счёта добавляет подпись участника
+    pub fn sign(&mut self, signer: &impl Signer, chain_id: u32) {
+        match self.sender {
+            Spender::Key(_) => self.signature = signer.sign(&self.signing_payload(chain_id)),
+            Spender::Multisig(_) => {
+                let partial = self.sign_partial(signer, chain_id);
+                if let Err(e) = self.add_partial_signature(partial, chain_id) {
+                    warn!("{}", e);
+                }
+            }
+            Spender::Script(_) => warn!("Трата со скрипт-счёта подтверждается разблокирующим скриптом"),
+        }
     }
 
-    pub fn serialize(&self) -> SerializedTransaction {
-        let sender_der = self.sender.to_pkcs1_der().unwrap();
-        let sender_base64 = STANDARD_NO_PAD.encode(sender_der.as_bytes());
+    /// Подпись участника multisig-счёта; передаётся тому, кто собирает подписи
+    pub fn sign_partial(&self, signer: &impl Signer, chain_id: u32) -> PartialSignature {
+        PartialSignature {
+            key: signer.get_public_key().encode(),
+            signature: signer.sign(&self.signing_payload(chain_id)),
+        }
+    }
 
-        let buyer_der = self.buyer.to_pkcs1_der().unwrap();
-        let buyer_base64 = STANDARD_NO_PAD.encode(buyer_der.as_bytes());
+    /// Добавляет проверенную подпись участника; прежняя подпись того же участника заменяется
+    pub fn add_partial_signature(&mut self, partial: PartialSignature, chain_id: u32) -> Result<(), MultisigError> {
+        let Spender::Multisig(policy) = &self.sender else {
+            return Err(MultisigError::NotMultisig);
+        };
+        policy.check_signature(&self.signing_payload(chain_id), &partial)?;
+        self.cosignatures.retain(|existing| existing.key != partial.key);
+        self.cosignatures.push(partial);
+        Ok(())
+    }
 
-        let seller_der = self.buyer.to_pkcs1_der().unwrap();
-        let seller_base64 = STANDARD_NO_PAD.encode(seller_der.as_bytes());
+    // Проверка подписи для сети `chain_id`; для multisig-счёта нужно не меньше порога подписей участников.
+    // Трату со скрипт-счёта подтверждает только выполнение скриптов в блоке (execute_script)
+    pub fn verify(&self, chain_id: u32) -> bool {
+        let payload = self.signing_payload(chain_id);
+        match &self.sender {
+            Spender::Key(key) => key.verify(&payload, &self.signature),
+            Spender::Multisig(policy) => policy.verify(&payload, &self.cosignatures),
+            Spender::Script(_) => false,
+        }
+    }
+
+    /// Выполняет разблокирующий и запирающий скрипты для сети `chain_id` в блоке с высотой `height`
+    /// и временем `time`. Возвращает израсходованный газ
+    pub fn execute_script(&self, chain_id: u32, height: u64, time: i64) -> Result<u64, ScriptError> {
+        let Spender::Script(locking) = &self.sender else {
+            return Err(ScriptError::NotScript);
+        };
+        let host = TransactionHost { payload: self.signing_payload(chain_id), height, time };
+        script::execute(&self.unlocking_script, locking, &host, MAX_SCRIPT_GAS)
+    }
+
+    pub fn serialize(&self) -> SerializedTransaction {
+        let (multisig, locking_script, public_key) = match &self.sender {
+            Spender::Key(key) => (None, None, Some(key.encode())),
+            Spender::Multisig(policy) => (Some(policy.clone()), None, None),
+            Spender::Script(script) => (None, Some(script.clone()), None),
+        };
 
         SerializedTransaction {
-            sender: sender_base64,
-            seller: seller_base64,
-            buyer:buyer_base64,
+            sender: self.get_sender_address(),
+            seller: self.seller.clone(),
+            buyer: self.buyer.clone(),
             message: self.message.clone(),
             transfer: self.transfer,
+            outputs: self.outputs.clone(),
+            fee: self.fee,
+            nonce: self.nonce,
+            valid_after: self.valid_after,
+            valid_until: self.valid_until,
+            kind: self.kind.clone(),
+            multisig,
+            locking_script,
+            public_key,
             signature: self.signature.clone(),
+            cosignatures: self.cosignatures.clone(),
+            unlocking_script: self.unlocking_script.clone(),
         }
     }
 
     pub fn deserialize(serialized_transaction: SerializedTransaction) -> Result<Self, String> {
-        let sender_base64 = serialized_transaction.sender;
-        let sender = RsaPublicKey::from_pkcs1_der(
-            &STANDARD_NO_PAD.decode(&sender_base64).unwrap()
-        ).expect("Ошибка чтения ключа отправителя");
-
-        let buyer_base64 = serialized_transaction.buyer;
-        let buyer = RsaPublicKey::from_pkcs1_der(
-            &STANDARD_NO_PAD.decode(&buyer_base64).unwrap()
-        ).expect("Ошибка чтения ключа покупателя");
-
-        let seller_base64 = serialized_transaction.seller;
-        let seller = RsaPublicKey::from_pkcs1_der(
-            &STANDARD_NO_PAD.decode(&seller_base64).unwrap()
-        ).expect("Ошибка чтения ключа продавца");
+        let sender = decode_spender(&serialized_transaction)?;
+        let buyer = decode_account(&serialized_transaction.buyer, "покупателя")?;
+        let seller = decode_account(&serialized_transaction.seller, "продавца")?;
+        for output in &serialized_transaction.outputs {
+            decode_account(&output.recipient, "получателя")?;
+        }
 
         Ok(Transaction {
             sender,
@@ -131,7 +365,15 @@ impl Transaction {
             seller,
             message: serialized_transaction.message,
             transfer: serialized_transaction.transfer,
+            outputs: serialized_transaction.outputs,
+            fee: serialized_transaction.fee,
+            nonce: serialized_transaction.nonce,
+            valid_after: serialized_transaction.valid_after,
+            valid_until: serialized_transaction.valid_until,
+            kind: serialized_transaction.kind,
             signature: serialized_transaction.signature,
+            cosignatures: serialized_transaction.cosignatures,
+            unlocking_script: serialized_transaction.unlocking_script,
         })
     }
 
@@ -155,14 +397,23 @@ impl Transaction {
     }
 
 
-    // Получение публичного ключа отправителя
-    pub fn get_sender(&self) -> RsaPublicKey {
-        self.sender.clone()
+    // Получение отправителя: ключа или multisig-счёта
+    pub fn get_sender(&self) -> &Spender {
+        &self.sender
+    }
+
+    // Адрес счёта отправителя
+    pub fn get_sender_address(&self) -> String {
+        match &self.sender {
+            Spender::Key(key) => address::from_public_key(key),
+            Spender::Multisig(policy) => policy.get_address(),
+            Spender::Script(script) => script.get_address(),
+        }
     }
 
 
     // Получение суммы перевода
-    pub fn get_transfer(&self) -> f64 {
+    pub fn get_transfer(&self) -> Amount {
         self.transfer
     }
 
@@ -172,65 +423,408 @@ impl Transaction {
     }
 }
 
+/// Записывает длину (u32, big-endian) и сами байты
+pub fn write_bytes(payload: &mut Vec<u8>, bytes: &[u8]) {
+    payload.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    payload.extend_from_slice(bytes);
+}
+
+/// Получатели перевода: их число (u32, big-endian), затем адрес и сумма каждого
+fn write_outputs(payload: &mut Vec<u8>, outputs: &[TransactionOutput]) {
+    payload.extend_from_slice(&(outputs.len() as u32).to_be_bytes());
+    for output in outputs {
+        write_bytes(payload, output.recipient.as_bytes());
+        payload.extend_from_slice(&output.amount.get_base_units().to_be_bytes());
+    }
+}
+
+/// Необязательная граница окна действия: метка 0/1 и значение в big-endian
+fn write_lock(payload: &mut Vec<u8>, lock: Option<u64>) {
+    match lock {
+        Some(value) => {
+            payload.push(1);
+            payload.extend_from_slice(&value.to_be_bytes());
+        }
+        None => payload.push(0),
+    }
+}
+
+/// Публичный ключ из канонической записи `<алгоритм>:<base64>`
+pub fn decode_public_key(encoded: &str) -> Result<PublicKey, String> {
+    PublicKey::decode(encoded).map_err(|e| e.to_string())
+}
+
+/// Адрес счёта покупателя или продавца (`role`) с проверкой контрольной суммы
+fn decode_account(account: &str, role: &str) -> Result<String, String> {
+    address::check_account(account).map_err(|e| format!("Ошибка чтения адреса {}: {}", role, e))?;
+    Ok(account.to_string())
+}
+
+/// Отправитель транзакции. Адрес multisig-счёта должен совпадать с приложенным описанием счёта,
+/// адрес ключа — с раскрытым ключом отправителя;
+/// у обычной транзакции не должно быть подписей участников, у multisig — одиночной подписи
+fn decode_spender(transaction: &SerializedTransaction) -> Result<Spender, String> {
+    if transaction.public_key.is_some() && (is_script_address(&transaction.sender) || is_multisig_address(&transaction.sender)) {
+        return Err("Ключ отправителя раскрывается только при трате с адреса ключа".to_string());
+    }
+    if is_script_address(&transaction.sender) {
+        return decode_script_spender(transaction);
+    }
+    if transaction.locking_script.is_some() || !transaction.unlocking_script.is_empty() {
+        return Err("Скрипты допустимы только для скрипт-счёта".to_string());
+    }
+    if !is_multisig_address(&transaction.sender) {
+        if transaction.multisig.is_some() || !transaction.cosignatures.is_empty() {
+            return Err("Подписи участников допустимы только для multisig-счёта".to_string());
+        }
+        let public_key = transaction.public_key.as_deref().ok_or("Не раскрыт ключ отправителя")?;
+        let key = decode_public_key(public_key).map_err(|e| format!("Ошибка чтения ключа отправителя: {}", e))?;
+        if address::from_public_key(&key) != transaction.sender {
+            return Err(format!("Ключ отправителя не соответствует адресу {}", transaction.sender));
+        }
+        return Ok(Spender::Key(key));
+    }
+
+    let policy = transaction.multisig.clone().ok_or("Не приложено описание multisig-счёта")?;
+    policy.validate().map_err(|e| e.to_string())?;
+    if policy.get_address() != transaction.sender {
+        return Err(format!("Описание multisig-счёта не соответствует адресу {}", transaction.sender));
+    }
+    if !transaction.signature.is_empty() {
+        return Err("Транзакция multisig-счёта подписывается только участниками".to_string());
+    }
+    Ok(Spender::Multisig(policy))
+}
+
+/// Скрипт-счёт: адрес должен совпадать с приложенным запирающим скриптом,
+/// а подписи передаются только в разблокирующем скрипте
+fn decode_script_spender(transaction: &SerializedTransaction) -> Result<Spender, String> {
+    let script = transaction.locking_script.clone().ok_or("Не приложен запирающий скрипт")?;
+    if script.get_address() != transaction.sender {
+        return Err(format!("Запирающий скрипт не соответствует адресу {}", transaction.sender));
+    }
+    if !transaction.signature.is_empty() || transaction.multisig.is_some() || !transaction.cosignatures.is_empty() {
+        return Err("Трата со скрипт-счёта подтверждается только разблокирующим скриптом".to_string());
+    }
+    Ok(Spender::Script(script))
+}
+
+/// Доступ скрипта к подписываемым данным транзакции и к блоку, в котором она проверяется
+struct TransactionHost {
+    payload: Vec<u8>,
+    height: u64,
+    time: i64,
+}
+
+impl ScriptHost for TransactionHost {
+    fn check_signature(&self, key: &[u8], signature: &[u8]) -> bool {
+        let (Ok(key), Ok(signature)) = (std::str::from_utf8(key), std::str::from_utf8(signature)) else {
+            return false;
+        };
+        decode_public_key(key).is_ok_and(|key| key.verify(&self.payload, signature))
+    }
+
+    fn get_height(&self) -> u64 {
+        self.height
+    }
+
+    fn get_time(&self) -> i64 {
+        self.time
+    }
+}
+
 impl fmt::Display for Transaction{
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let sender_pem = self.sender.to_pkcs1_pem(LineEnding::LF).unwrap();
-
-        write!(f, "{}:{}", sender_pem, self.message)
+        write!(f, "{}:{}", self.get_sender_address(), self.message)
     }
 }
 
+/// Отправитель coinbase-транзакции, выпускающей награду майнеру
+pub const COINBASE_SENDER: &str = "coinbase";
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SerializedTransaction {
     pub sender: String,
     pub buyer: String,
     pub seller: String,
     pub message: String,
-    pub transfer: f64,
+    pub transfer: Amount,
+    // Получатели перевода помимо продавца
+    #[serde(default)]
+    pub outputs: Vec<TransactionOutput>,
+    #[serde(default)]
+    pub fee: Amount,
+    #[serde(default)]
+    pub nonce: u64,
+    #[serde(default)]
+    pub valid_after: Option<u64>,
+    #[serde(default)]
+    pub valid_until: Option<u64>,
+    #[serde(default)]
+    pub kind: TransactionKind,
+    // Описание счёта, если `sender` — адрес multisig-счёта
+    #[serde(default)]
+    pub multisig: Option<MultisigPolicy>,
+    // Запирающий скрипт, если `sender` — адрес скрипт-счёта
+    #[serde(default)]
+    pub locking_script: Option<Script>,
+    // Ключ отправителя, если `sender` — адрес ключа; раскрывается только при трате
+    #[serde(default)]
+    pub public_key: Option<String>,
     pub signature: String,
+    #[serde(default)]
+    pub cosignatures: Vec<PartialSignature>,
+    #[serde(default)]
+    pub unlocking_script: Script,
 }
 
 impl SerializedTransaction {
-    pub fn new(sender_base64: String, seller_base64: String, buyer_base64: String, message: String, transfer: f64) -> SerializedTransaction {
-        let sender_base64 = sender_base64.trim().to_string();
-        let seller_base64 = seller_base64.trim().to_string();
-        let buyer_base64 = buyer_base64.trim().to_string();
+    pub fn new(sender_address: String, seller_address: String, buyer_address: String, message: String, transfer: Amount) -> SerializedTransaction {
+        let sender_address = sender_address.trim().to_string();
+        let seller_address = seller_address.trim().to_string();
+        let buyer_address = buyer_address.trim().to_string();
 
         SerializedTransaction {
             //time
-            sender: sender_base64,
-            buyer: buyer_base64,
-            seller: seller_base64,
+            sender: sender_address,
+            buyer: buyer_address,
+            seller: seller_address,
             message,
             transfer,
+            outputs: Vec::new(),
+            fee: Amount::ZERO,
+            nonce: 0,
+            valid_after: None,
+            valid_until: None,
+            kind: TransactionKind::Transfer,
+            multisig: None,
+            locking_script: None,
+            public_key: None,
             signature: "".to_string(),
+            cosignatures: Vec::new(),
+            unlocking_script: Script::default(),
         }
     }
 
+    /// Транзакция, выпускающая награду за блок на адрес майнера
+    pub fn coinbase(miner_address: String, height: usize, amount: Amount) -> SerializedTransaction {
+        SerializedTransaction {
+            sender: COINBASE_SENDER.to_string(),
+            buyer: miner_address.clone(),
+            seller: miner_address,
+            // Высота делает coinbase-транзакции разных блоков различимыми
+            message: format!("coinbase:{}", height),
+            transfer: amount,
+            outputs: Vec::new(),
+            fee: Amount::ZERO,
+            nonce: 0,
+            valid_after: None,
+            valid_until: None,
+            kind: TransactionKind::Transfer,
+            multisig: None,
+            locking_script: None,
+            public_key: None,
+            signature: "".to_string(),
+            cosignatures: Vec::new(),
+            unlocking_script: Script::default(),
+        }
+    }
+
+    pub fn is_coinbase(&self) -> bool {
+        self.sender == COINBASE_SENDER
+    }
+
     pub fn get_sender(&self) -> String {
         self.sender.clone()
     }
 
-    pub fn get_transfer(&self) -> f64 {
+    pub fn get_seller(&self) -> &str {
+        &self.seller
+    }
+
+    /// Каноническое байтовое представление транзакции со всеми полями, включая подпись:
+    /// строки с префиксом длины (u32, big-endian), числа в big-endian
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = self.encode_unsigned();
+        self.write_witness(&mut bytes);
+        bytes
+    }
+
+    /// Подписываемая часть канонического представления: все поля, кроме подписей и разблокирующего скрипта
+    fn encode_unsigned(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for text in [&self.sender, &self.buyer, &self.seller, &self.message] {
+            write_bytes(&mut bytes, text.as_bytes());
+        }
+        bytes.extend_from_slice(&self.transfer.get_base_units().to_be_bytes());
+        write_outputs(&mut bytes, &self.outputs);
+        bytes.extend_from_slice(&self.fee.get_base_units().to_be_bytes());
+        bytes.extend_from_slice(&self.nonce.to_be_bytes());
+        write_lock(&mut bytes, self.valid_after);
+        write_lock(&mut bytes, self.valid_until);
+        self.kind.encode_into(&mut bytes);
+        match &self.multisig {
+            Some(policy) => {
+                bytes.push(1);
+                write_bytes(&mut bytes, &policy.encode());
+            }
+            None => bytes.push(0),
+        }
+        match &self.locking_script {
+            Some(script) => {
+                bytes.push(1);
+                write_bytes(&mut bytes, &script.encode());
+            }
+            None => bytes.push(0),
+        }
+        match &self.public_key {
+            Some(key) => {
+                bytes.push(1);
+                write_bytes(&mut bytes, key.as_bytes());
+            }
+            None => bytes.push(0),
+        }
+        bytes
+    }
+
+    /// Дописывает подпись, подписи участников и разблокирующий скрипт
+    fn write_witness(&self, bytes: &mut Vec<u8>) {
+        write_bytes(bytes, self.signature.as_bytes());
+        bytes.extend_from_slice(&(self.cosignatures.len() as u32).to_be_bytes());
+        for partial in &self.cosignatures {
+            write_bytes(bytes, partial.key.as_bytes());
+            write_bytes(bytes, partial.signature.as_bytes());
+        }
+        write_bytes(bytes, &self.unlocking_script.encode());
+    }
+
+    /// Идентификатор транзакции: SHA-256 канонического представления в hex, лист дерева Меркла блока
+    pub fn get_txid(&self) -> String {
+        merkle::to_hex(&Sha256::digest(self.encode()))
+    }
+
+    pub fn get_transfer(&self) -> Amount {
         self.transfer
     }
+
+    pub fn get_fee(&self) -> Amount {
+        self.fee
+    }
+
+    pub fn get_nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    /// Все получатели перевода: продавец и дополнительные получатели
+    pub fn get_outputs(&self) -> Vec<TransactionOutput> {
+        let mut outputs = vec![TransactionOutput { recipient: self.seller.clone(), amount: self.transfer }];
+        outputs.extend(self.outputs.iter().cloned());
+        outputs
+    }
+
+    /// Сколько списывается с отправителя: перевод всем получателям и комиссия,
+    /// а при закрытии депонирования или контракта — только комиссия.
+    /// `None` при переполнении
+    pub fn get_debit(&self) -> Option<Amount> {
+        match self.kind {
+            TransactionKind::Transfer | TransactionKind::EscrowLock { .. } | TransactionKind::HtlcLock { .. } => {
+                Amount::checked_sum(self.get_outputs().into_iter().map(|output| output.amount))?.checked_add(self.fee)
+            }
+            _ => Some(self.fee),
+        }
+    }
+
+    /// Можно ли включить транзакцию в блок с высотой `height` и временем `time`
+    pub fn check_validity_window(&self, height: u64, time: i64) -> Result<(), ValidityError> {
+        if let Some(valid_after) = self.valid_after
+            && !lock_reached(valid_after, height, time) {
+            return Err(ValidityError::NotYetValid { txid: self.get_txid(), valid_after });
+        }
+        if self.is_expired(height, time) {
+            return Err(ValidityError::Expired { txid: self.get_txid(), valid_until: self.valid_until.unwrap_or_default() });
+        }
+        Ok(())
+    }
+
+    /// Срок действия истёк: транзакция уже не попадёт ни в блок `height`, ни в последующие
+    pub fn is_expired(&self, height: u64, time: i64) -> bool {
+        match self.valid_until {
+            Some(valid_until) if valid_until < LOCK_TIME_THRESHOLD => height > valid_until,
+            Some(valid_until) => i64::try_from(valid_until).is_ok_and(|valid_until| time > valid_until),
+            None => false,
+        }
+    }
+
+    /// Размер канонического представления в байтах, по нему считается место в блоке
+    pub fn get_size(&self) -> usize {
+        self.encode().len()
+    }
+
+    /// Ключ приоритета в очереди; каноническое представление кодируется один раз
+    pub fn get_fee_rate_key(&self) -> FeeRateKey {
+        let mut bytes = self.encode_unsigned();
+        let signing_hash = Sha256::digest(&bytes).into();
+        self.write_witness(&mut bytes);
+        FeeRateKey {
+            fee: self.fee.get_base_units(),
+            size: bytes.len(),
+            signing_hash,
+        }
+    }
+}
+
+/// Приоритет транзакции в очереди: комиссия за байт, при равенстве — хеш подписываемой части.
+/// Подписи в сравнение не входят, поэтому добавленная подпись участника не меняет порядок.
+/// Вычисляется один раз при постановке в очередь, чтобы сравнения не кодировали транзакции заново
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeRateKey {
+    fee: u64,
+    size: usize,
+    signing_hash: [u8; 32],
+}
+
+impl FeeRateKey {
+    /// Размер канонического представления транзакции в байтах
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+}
+
+// Доли fee / size сравниваются перекрёстным умножением, без деления.
+// Равны только ключи транзакций с одинаковыми подписываемыми данными
+impl Ord for FeeRateKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let own = self.fee as u128 * other.size as u128;
+        let theirs = other.fee as u128 * self.size as u128;
+        own.cmp(&theirs)
+            .then_with(|| self.size.cmp(&other.size))
+            .then_with(|| self.signing_hash.cmp(&other.signing_hash))
+    }
+}
+
+impl PartialOrd for FeeRateKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 impl Eq for SerializedTransaction {}
 
+// Транзакции равны, если совпадают их канонические представления, то есть идентификаторы
 impl PartialEq for SerializedTransaction {
     fn eq(&self, other: &Self) -> bool {
-            self.sender == other.sender &&
-            self.message == other.message &&
-            self.transfer == other.transfer &&
-            self.signature == other.signature
+        self.encode() == other.encode()
     }
 }
 
-// Реализуем Ord для сортировки по приоритету
+// Приоритет в очереди определяется комиссией за байт, а не суммой перевода.
+// Для сортировки многих транзакций ключ лучше вычислить заранее: sort_by_cached_key(get_fee_rate_key).
+// Транзакции с одинаковыми подписываемыми данными упорядочиваются по подписям, как и сравниваются на равенство
 impl Ord for SerializedTransaction {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.transfer.partial_cmp(&other.transfer).unwrap_or(Ordering::Equal)
+        self.get_fee_rate_key()
+            .cmp(&other.get_fee_rate_key())
+            .then_with(|| self.encode().cmp(&other.encode()))
     }
 }
 
@@ -245,15 +839,18 @@ impl PartialOrd for SerializedTransaction {
 
 #[cfg(test)]
 mod transactionTests {
-    use base64::Engine;
     use super::*;
-    use rand::rngs::OsRng;
-    use rsa::{RsaPrivateKey, pkcs1::EncodeRsaPublicKey, RsaPublicKey};
+    use crate::coin::node::blockchain::crypto::{PrivateKey, SignatureAlgorithm};
+    use crate::coin::node::blockchain::script::Op;
+
+    const CHAIN_ID: u32 = 1;
 
-    fn generate_keys() -> (RsaPrivateKey, RsaPublicKey) {
-        let mut rng = OsRng;
-        let private_key = RsaPrivateKey::new(&mut rng, 2048).expect("не удалось сгенерировать ключ");
-        let public_key = private_key.to_public_key();
+    // Название поля и подмена его значения
+    type Tamper<'a> = (&'a str, Box<dyn Fn(&mut SerializedTransaction) + 'a>);
+
+    fn generate_keys() -> (PrivateKey, PublicKey) {
+        let private_key = PrivateKey::generate(SignatureAlgorithm::Ed25519);
+        let public_key = private_key.get_public_key();
         (private_key, public_key)
     }
 
@@ -263,14 +860,14 @@ mod transactionTests {
         let (_, buyer_pub) = generate_keys();
         let (_, seller_pub) = generate_keys();
 
-        let sender_b64 = base64::engine::general_purpose::STANDARD_NO_PAD.encode(sender_pub.to_pkcs1_der().unwrap());
-        let buyer_b64 = base64::engine::general_purpose::STANDARD_NO_PAD.encode(buyer_pub.to_pkcs1_der().unwrap());
-        let seller_b64 = base64::engine::general_purpose::STANDARD_NO_PAD.encode(seller_pub.to_pkcs1_der().unwrap());
+        let sender_b64 = sender_pub.encode();
+        let buyer_address = address::from_public_key(&buyer_pub);
+        let seller_address = address::from_public_key(&seller_pub);
 
-        let mut tx = Transaction::new(sender_b64, seller_b64, buyer_b64, "Test Message".to_string(), 42.0);
-        tx.sign(sender_priv.clone());
+        let mut tx = Transaction::new(sender_b64, seller_address, buyer_address, "Test Message".to_string(), Amount::from_coins(42));
+        tx.sign(&sender_priv, CHAIN_ID);
 
-        assert!(tx.verify(), "Подпись не прошла проверку!");
+        assert!(tx.verify(CHAIN_ID), "Подпись не прошла проверку!");
     }
 
     #[test]
@@ -279,39 +876,337 @@ mod transactionTests {
         let (_, buyer_pub) = generate_keys();
         let (_, seller_pub) = generate_keys();
 
-        let sender_b64 = base64::engine::general_purpose::STANDARD_NO_PAD.encode(sender_pub.to_pkcs1_der().unwrap());
-        let buyer_b64 = base64::engine::general_purpose::STANDARD_NO_PAD.encode(buyer_pub.to_pkcs1_der().unwrap());
-        let seller_b64 = base64::engine::general_purpose::STANDARD_NO_PAD.encode(seller_pub.to_pkcs1_der().unwrap());
+        let sender_b64 = sender_pub.encode();
+        let buyer_address = address::from_public_key(&buyer_pub);
+        let seller_address = address::from_public_key(&seller_pub);
 
-        let mut tx = Transaction::new(sender_b64.clone(), seller_b64.clone(), buyer_b64.clone(), "Hello".to_string(), 100.0);
-        tx.sign(sender_priv);
+        let mut tx = Transaction::new(sender_b64.clone(), seller_address.clone(), buyer_address.clone(), "Hello".to_string(), Amount::from_coins(100));
+        tx.sign(&sender_priv, CHAIN_ID);
 
         let json = tx.to_json();
         let deserialized = Transaction::from_json(&json).unwrap();
 
         assert_eq!(deserialized.get_message(), "Hello");
-        assert_eq!(deserialized.get_transfer(), 100.0);
-        assert!(deserialized.verify());
+        assert_eq!(deserialized.get_transfer(), Amount::from_coins(100));
+        assert!(deserialized.verify(CHAIN_ID));
+    }
+
+    #[test]
+    fn test_sign_with_every_algorithm() {
+        let (_, seller_pub) = generate_keys();
+        let algorithms = [SignatureAlgorithm::Rsa, SignatureAlgorithm::Ed25519, SignatureAlgorithm::Secp256k1];
+        let mut signatures = Vec::new();
+        for algorithm in algorithms {
+            let sender_priv = PrivateKey::generate(algorithm);
+            let sender_b64 = sender_priv.get_public_key().encode();
+            let sender_address = address::from_public_key(&sender_priv.get_public_key());
+            let mut tx = Transaction::new(sender_b64, address::from_public_key(&seller_pub), sender_address, "Algo".to_string(), Amount::from_coins(1));
+            tx.sign(&sender_priv, CHAIN_ID);
+            assert!(tx.signature.starts_with(algorithm.get_tag()));
+
+            let deserialized = Transaction::from_json(&tx.to_json()).unwrap();
+            assert!(deserialized.verify(CHAIN_ID), "Подпись {} не прошла проверку", algorithm);
+            signatures.push((tx, deserialized.signature));
+        }
+
+        // Подпись другого алгоритма к ключу не подходит
+        let (mut tx, _) = signatures.remove(0);
+        tx.signature = signatures[0].1.clone();
+        assert!(!tx.verify(CHAIN_ID));
+    }
+
+    #[test]
+    fn test_sender_key_must_match_address() {
+        let (sender_priv, sender_pub) = generate_keys();
+        let (_, other_pub) = generate_keys();
+        let sender_address = address::from_public_key(&sender_pub);
+
+        let mut tx = Transaction::new(sender_pub.encode(), sender_address.clone(), sender_address.clone(), "Reveal".to_string(), Amount::from_coins(1));
+        tx.sign(&sender_priv, CHAIN_ID);
+        let signed = tx.serialize();
+        assert_eq!(signed.sender, sender_address);
+        assert_eq!(signed.public_key, Some(sender_pub.encode()));
+
+        let mut hidden = signed.clone();
+        hidden.public_key = None;
+        assert!(Transaction::deserialize(hidden).is_err());
+
+        let mut foreign = signed.clone();
+        foreign.public_key = Some(other_pub.encode());
+        assert!(Transaction::deserialize(foreign).is_err());
+
+        // Опечатка в адресе продавца отвергается контрольной суммой
+        let mut typo = signed;
+        typo.seller = format!("{}1", sender_address);
+        assert!(Transaction::deserialize(typo).is_err());
+    }
+
+    #[test]
+    fn test_batched_transfer_is_smaller_than_separate_ones() {
+        let (sender_priv, sender_pub) = generate_keys();
+        let sender_address = address::from_public_key(&sender_pub);
+        let recipients: Vec<String> = (0..10).map(|_| address::from_public_key(&generate_keys().1)).collect();
+
+        let mut batch = Transaction::new(sender_pub.encode(), recipients[0].clone(), sender_address.clone(), "Payroll".to_string(), Amount::from_coins(1));
+        for recipient in &recipients[1..] {
+            batch.add_output(recipient.clone(), Amount::from_coins(1)).unwrap();
+        }
+        assert!(batch.add_output("typo".to_string(), Amount::from_coins(1)).is_err());
+        batch.sign(&sender_priv, CHAIN_ID);
+        let batch = batch.serialize();
+        assert_eq!(batch.get_outputs().len(), 10);
+        assert!(Transaction::deserialize(batch.clone()).unwrap().verify(CHAIN_ID));
+
+        let separate: usize = recipients
+            .iter()
+            .map(|recipient| {
+                let mut single = Transaction::new(sender_pub.encode(), recipient.clone(), sender_address.clone(), "Payroll".to_string(), Amount::from_coins(1));
+                single.sign(&sender_priv, CHAIN_ID);
+                single.serialize().get_size()
+            })
+            .sum();
+        assert!(batch.get_size() * 2 < separate);
+    }
+
+    #[test]
+    fn test_deserialize_rejects_invalid_key() {
+        let serialized = SerializedTransaction::new("not a key".into(), "seller".into(), "buyer".into(), "msg".into(), Amount::from_coins(1));
+        assert!(Transaction::deserialize(serialized).is_err());
     }
 
     #[test]
     fn test_transaction_ordering() {
-        let tx1 = SerializedTransaction::new("sender".into(), "seller".into(), "buyer".into(), "msg".into(), 50.0);
-        let tx2 = SerializedTransaction::new("sender".into(), "seller".into(), "buyer".into(), "msg".into(), 100.0);
+        let mut tx1 = SerializedTransaction::new("sender".into(), "seller".into(), "buyer".into(), "msg".into(), Amount::from_coins(500));
+        tx1.fee = "0.1".parse().unwrap();
+        let mut tx2 = SerializedTransaction::new("sender".into(), "seller".into(), "buyer".into(), "msg".into(), Amount::from_coins(1));
+        tx2.fee = "0.2".parse().unwrap();
 
-        assert!(tx1 < tx2);
+        assert!(tx1 < tx2, "Порядок определяется комиссией, а не суммой перевода");
         assert!(tx2 > tx1);
     }
 
+    #[test]
+    fn test_ordering_uses_fee_per_byte() {
+        let mut small = SerializedTransaction::new("sender".into(), "seller".into(), "buyer".into(), "msg".into(), Amount::from_coins(1));
+        small.fee = Amount::from_coins(1);
+        let mut large = SerializedTransaction::new("sender".into(), "seller".into(), "buyer".into(), "x".repeat(1000), Amount::from_coins(1));
+        large.fee = "1.5".parse().unwrap();
+
+        assert!(large.get_size() > small.get_size());
+        assert!(small > large, "Меньшая транзакция платит больше за байт");
+
+        let mut queue = std::collections::BinaryHeap::from(vec![large.clone(), small.clone()]);
+        assert_eq!(queue.pop(), Some(small));
+        assert_eq!(queue.pop(), Some(large));
+    }
+
+    #[test]
+    fn test_equal_fee_rate_ordering_matches_equality() {
+        let mut first = SerializedTransaction::new("sender".into(), "seller".into(), "buyer".into(), "msg".into(), Amount::from_coins(1));
+        first.fee = Amount::from_coins(1);
+        let mut second = first.clone();
+        second.transfer = Amount::from_coins(2);
+
+        // Одинаковая комиссия за байт, но транзакции разные: порядок задаёт хеш подписываемых данных
+        assert_eq!(first.get_size(), second.get_size());
+        assert_ne!(first.cmp(&second), Ordering::Equal);
+        assert_eq!(first.cmp(&first.clone()), Ordering::Equal);
+        assert_eq!(first.get_fee_rate_key().cmp(&second.get_fee_rate_key()), first.cmp(&second));
+
+        // Подпись не меняет ключ приоритета, но транзакции с разными подписями не равны
+        let (mut signed_a, mut signed_b) = (first.clone(), first.clone());
+        signed_a.signature = "aa".to_string();
+        signed_b.signature = "bb".to_string();
+        assert_eq!(signed_a.get_fee_rate_key(), signed_b.get_fee_rate_key());
+        assert_ne!(signed_a, signed_b);
+        assert_ne!(signed_a.cmp(&signed_b), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_signature_covers_every_field() {
+        let (sender_priv, sender_pub) = generate_keys();
+        let (_, buyer_pub) = generate_keys();
+        let (_, seller_pub) = generate_keys();
+        let (_, other_pub) = generate_keys();
+
+        let sender_b64 = sender_pub.encode();
+        let buyer_address = address::from_public_key(&buyer_pub);
+        let seller_address = address::from_public_key(&seller_pub);
+        let other_address = address::from_public_key(&other_pub);
+
+        let mut tx = Transaction::new(sender_b64, seller_address.clone(), buyer_address.clone(), "Tamper".to_string(), Amount::from_coins(10));
+        tx.set_fee("0.5".parse().unwrap());
+        tx.set_nonce(3);
+        tx.set_valid_after(Some(10));
+        tx.set_valid_until(Some(100));
+        tx.set_kind(TransactionKind::EscrowLock { timeout: 100 });
+        tx.sign(&sender_priv, CHAIN_ID);
+        let signed = tx.serialize();
+        assert!(Transaction::deserialize(signed.clone()).unwrap().verify(CHAIN_ID));
+
+        let tampers: Vec<Tamper> = vec![
+            ("sender", Box::new(|t| {
+                t.sender = other_address.clone();
+                t.public_key = Some(other_pub.encode());
+            })),
+            ("buyer", Box::new(|t| t.buyer = seller_address.clone())),
+            ("seller", Box::new(|t| t.seller = buyer_address.clone())),
+            ("message", Box::new(|t| t.message = "Tampered".to_string())),
+            ("transfer", Box::new(|t| t.transfer = Amount::from_coins(11))),
+            ("outputs", Box::new(|t| t.outputs.push(TransactionOutput { recipient: seller_address.clone(), amount: Amount::from_coins(1) }))),
+            ("fee", Box::new(|t| t.fee = Amount::ZERO)),
+            ("nonce", Box::new(|t| t.nonce = 4)),
+            ("valid_after", Box::new(|t| t.valid_after = None)),
+            ("valid_until", Box::new(|t| t.valid_until = None)),
+            ("kind", Box::new(|t| t.kind = TransactionKind::EscrowLock { timeout: 101 })),
+        ];
+        for (field, tamper) in tampers {
+            let mut tampered = signed.clone();
+            tamper(&mut tampered);
+            let tampered = Transaction::deserialize(tampered).unwrap();
+            assert!(!tampered.verify(CHAIN_ID), "Изменение поля {} должно ломать подпись", field);
+        }
+
+        let restored = Transaction::deserialize(signed).unwrap();
+        assert!(!restored.verify(CHAIN_ID + 1), "Подпись одной сети не должна подходить другой");
+    }
+
+    #[test]
+    fn test_validity_window_by_height_and_time() {
+        let mut tx = SerializedTransaction::new("a".to_string(), "b".to_string(), "c".to_string(), "Window".to_string(), Amount::from_coins(1));
+        assert_eq!(tx.check_validity_window(1, 0), Ok(()));
+
+        tx.valid_after = Some(5);
+        tx.valid_until = Some(7);
+        assert!(matches!(tx.check_validity_window(4, 0), Err(ValidityError::NotYetValid { valid_after: 5, .. })));
+        assert_eq!(tx.check_validity_window(5, 0), Ok(()));
+        assert_eq!(tx.check_validity_window(7, 0), Ok(()));
+        assert!(!tx.is_expired(7, 0));
+        assert!(tx.is_expired(8, 0));
+        assert!(matches!(tx.check_validity_window(8, 0), Err(ValidityError::Expired { valid_until: 7, .. })));
+
+        // Значения от LOCK_TIME_THRESHOLD сравниваются со временем, а не с высотой
+        let deadline = LOCK_TIME_THRESHOLD + 1_000;
+        tx.valid_after = None;
+        tx.valid_until = Some(deadline);
+        assert_eq!(tx.check_validity_window(1_000_000_000, deadline as i64), Ok(()));
+        assert!(tx.is_expired(1, deadline as i64 + 1));
+    }
+
+    #[test]
+    fn test_multisig_requires_threshold_signatures() {
+        let signers: Vec<(PrivateKey, PublicKey)> = (0..3).map(|_| generate_keys()).collect();
+        let keys: Vec<String> = signers
+            .iter()
+            .map(|(_, public_key)| public_key.encode())
+            .collect();
+        let policy = MultisigPolicy::new(2, keys.clone()).unwrap();
+
+        let recipient = address::from_public_key(&signers[0].1);
+        let mut tx = Transaction::new_multisig(policy.clone(), recipient.clone(), recipient, "Multisig".to_string(), Amount::from_coins(3)).unwrap();
+        tx.set_nonce(1);
+        tx.sign(&signers[0].0, CHAIN_ID);
+        assert!(!tx.verify(CHAIN_ID), "Одной подписи из двух недостаточно");
+        tx.sign(&signers[2].0, CHAIN_ID);
+        assert!(tx.verify(CHAIN_ID));
+
+        let serialized = tx.serialize();
+        assert_eq!(serialized.sender, policy.get_address());
+        assert!(serialized.signature.is_empty());
+        assert!(Transaction::deserialize(serialized.clone()).unwrap().verify(CHAIN_ID));
+
+        let mut changed = serialized.clone();
+        changed.nonce = 2;
+        assert!(!Transaction::deserialize(changed).unwrap().verify(CHAIN_ID));
+
+        // Описание счёта должно соответствовать адресу отправителя
+        let mut other_policy = serialized.clone();
+        other_policy.multisig = Some(MultisigPolicy::new(1, keys.clone()).unwrap());
+        assert!(Transaction::deserialize(other_policy).is_err());
+
+        let mut missing_policy = serialized;
+        missing_policy.multisig = None;
+        assert!(Transaction::deserialize(missing_policy).is_err());
+    }
+
+    #[test]
+    fn test_script_spend_checks_signature_in_script() {
+        let (private_key, public_key) = generate_keys();
+        let key = public_key.encode();
+        let locking = Script::from(vec![Op::Push(key.clone().into_bytes()), Op::CheckSig]);
+
+        let recipient = address::from_public_key(&public_key);
+        let mut serialized = SerializedTransaction::new(locking.get_address(), recipient.clone(), recipient, "Script".to_string(), Amount::from_coins(2));
+        serialized.locking_script = Some(locking.clone());
+        let payload = Transaction::deserialize(serialized.clone()).unwrap().signing_payload(CHAIN_ID);
+        serialized.unlocking_script = Script::from(vec![Op::Push(private_key.sign(&payload).into_bytes())]);
+
+        let tx = Transaction::deserialize(serialized.clone()).unwrap();
+        assert!(tx.execute_script(CHAIN_ID, 2, 0).is_ok());
+        assert!(!tx.verify(CHAIN_ID), "Скрипт-счёт проверяется только скриптами");
+        assert_eq!(tx.serialize(), serialized);
+
+        let mut changed = serialized.clone();
+        changed.nonce = 1;
+        assert_eq!(Transaction::deserialize(changed).unwrap().execute_script(CHAIN_ID, 2, 0), Err(ScriptError::Failed));
+
+        // Запирающий скрипт должен соответствовать адресу отправителя
+        let mut other_script = serialized.clone();
+        other_script.locking_script = Some(Script::from(vec![Op::PushNumber(1)]));
+        assert!(Transaction::deserialize(other_script).is_err());
+
+        let mut signed = serialized;
+        signed.signature = "signature".to_string();
+        assert!(Transaction::deserialize(signed).is_err());
+    }
+
+    #[test]
+    fn test_serialize_keeps_seller() {
+        let (_, buyer_pub) = generate_keys();
+        let (_, seller_pub) = generate_keys();
+        let buyer_address = address::from_public_key(&buyer_pub);
+        let seller_address = address::from_public_key(&seller_pub);
+
+        let tx = Transaction::new(buyer_pub.encode(), seller_address.clone(), buyer_address.clone(), "Seller".to_string(), Amount::from_coins(1));
+        let serialized = tx.serialize();
+        assert_eq!(serialized.seller, seller_address);
+        assert_eq!(serialized.buyer, buyer_address);
+    }
+
+    #[test]
+    fn test_txid_depends_on_every_field() {
+        let tx = SerializedTransaction::new(
+            "alice".to_string(),
+            "bob".to_string(),
+            "bob".to_string(),
+            "Txid".to_string(),
+            Amount::from_coins(1),
+        );
+        // Идентификатор не зависит от формата передачи
+        let restored: SerializedTransaction = serde_json::from_str(&serde_json::to_string(&tx).unwrap()).unwrap();
+        assert_eq!(restored.get_txid(), tx.get_txid());
+        assert_eq!(tx.get_txid().len(), 64);
+
+        let mut signed = tx.clone();
+        signed.signature = "signature".to_string();
+        let mut later = tx.clone();
+        later.nonce = 1;
+        assert_ne!(signed.get_txid(), tx.get_txid());
+        assert_ne!(later.get_txid(), tx.get_txid());
+    }
+
     #[test]
     fn test_transaction_display() {
         let (_, pub_key) = generate_keys();
-        let key_b64 = base64::engine::general_purpose::STANDARD_NO_PAD.encode(pub_key.to_pkcs1_der().unwrap());
+        let key_b64 = pub_key.encode();
+
+        let key_address = address::from_public_key(&pub_key);
 
-        let mut tx = Transaction::new(key_b64.clone(), key_b64.clone(), key_b64.clone(), "Display test".to_string(), 12.0);
+        let mut tx = Transaction::new(key_b64, key_address.clone(), key_address.clone(), "Display test".to_string(), Amount::from_coins(12));
         let display_output = format!("{}", tx);
 
         assert!(display_output.contains("Display test"));
+        assert!(display_output.starts_with(&key_address));
     }
 
     #[test]
@@ -320,18 +1215,18 @@ mod transactionTests {
         let (_, buyer_pub) = generate_keys();
         let (_, seller_pub) = generate_keys();
 
-        let sender_b64 = base64::engine::general_purpose::STANDARD_NO_PAD.encode(sender_pub.to_pkcs1_der().unwrap());
-        let buyer_b64 = base64::engine::general_purpose::STANDARD_NO_PAD.encode(buyer_pub.to_pkcs1_der().unwrap());
-        let seller_b64 = base64::engine::general_purpose::STANDARD_NO_PAD.encode(seller_pub.to_pkcs1_der().unwrap());
+        let sender_b64 = sender_pub.encode();
+        let buyer_address = address::from_public_key(&buyer_pub);
+        let seller_address = address::from_public_key(&seller_pub);
 
-        let mut tx = Transaction::new(sender_b64.clone(), seller_b64.clone(), buyer_b64.clone(), "JSON Test".to_string(), 77.7);
-        tx.sign(sender_priv);
+        let mut tx = Transaction::new(sender_b64.clone(), seller_address.clone(), buyer_address.clone(), "JSON Test".to_string(), "77.7".parse().unwrap());
+        tx.sign(&sender_priv, CHAIN_ID);
 
         let json = tx.to_json();
         let restored = Transaction::from_json(&json).unwrap();
 
         assert_eq!(tx.get_transfer(), restored.get_transfer());
         assert_eq!(tx.get_message(), restored.get_message());
-        assert!(restored.verify());
+        assert!(restored.verify(CHAIN_ID));
     }
 }
\ No newline at end of file
@@ -0,0 +1,373 @@
+use std::collections::HashSet;
+
+use thiserror::Error;
+
+use crate::coin::node::blockchain::address::{self, AddressError};
+use crate::coin::node::blockchain::amount::Amount;
+use crate::coin::node::blockchain::block::Block;
+use crate::coin::node::blockchain::difficulty;
+use crate::coin::node::blockchain::emission;
+use crate::coin::node::blockchain::ledger::LedgerError;
+use crate::coin::node::blockchain::script::ScriptError;
+use crate::coin::node::blockchain::transaction::{SerializedTransaction, Spender, Transaction, ValidityError};
+use crate::coin::params::ChainParams;
+
+/// Насколько время блока может опережать часы узла, секунды
+pub const MAX_FUTURE_DRIFT: i64 = 60 * difficulty::TARGET_BLOCK_TIME;
+/// По скольким последним блокам считается медианное время
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum BlockValidationError {
+    #[error("Цепочка пуста")]
+    EmptyChain,
+    #[error("Первый блок {actual} не совпадает с первым блоком сети {expected}")]
+    GenesisMismatch { expected: String, actual: String },
+    #[error("Блок ссылается на {actual}, а не на родителя {expected}")]
+    PreviousHashMismatch { expected: String, actual: String },
+    #[error("Блок {0} уже известен")]
+    AlreadyKnown(String),
+    #[error("Хеш блока не удовлетворяет цели")]
+    InsufficientWork,
+    #[error("Корень транзакций не совпадает с транзакциями блока")]
+    TransactionsRootMismatch,
+    #[error("Вес блока {weight} байт превышает {max}")]
+    TooHeavy { weight: usize, max: usize },
+    #[error("Время блока {time} опережает время узла {now} больше допустимого")]
+    TimeTooNew { time: i64, now: i64 },
+    #[error("Время блока {time} должно быть позже медианы последних блоков {median}")]
+    TimeTooOld { time: i64, median: i64 },
+    #[error("Высота блока {actual}, ожидалась {expected}")]
+    UnexpectedHeight { expected: usize, actual: usize },
+    #[error("Цель блока {actual:016x}, ожидалась {expected:016x}")]
+    UnexpectedTarget { expected: u64, actual: u64 },
+    #[error("Цель блока без родителя {actual:016x} легче текущей цели цепочки {required:016x}")]
+    OrphanTargetTooEasy { required: u64, actual: u64 },
+    #[error("В блоке нет coinbase-транзакции")]
+    MissingCoinbase,
+    #[error("Coinbase выплачивает {amount}, ожидалось {expected}")]
+    InvalidCoinbaseAmount { amount: Amount, expected: Amount },
+    #[error("Coinbase выплачивает награду на некорректный адрес: {0}")]
+    InvalidCoinbaseAddress(AddressError),
+    #[error("Транзакция {0} повторяется в блоке")]
+    DuplicateTransaction(String),
+    #[error("Неверная подпись транзакции {0}")]
+    InvalidSignature(String),
+    #[error("Скрипт транзакции {txid} не выполнен: {source}")]
+    Script { txid: String, source: ScriptError },
+    #[error("{0}")]
+    Validity(#[from] ValidityError),
+    #[error("Блок не применяется к балансам: {0}")]
+    Ledger(#[from] LedgerError),
+}
+
+/// Полная проверка блока, продолжающего `ancestors` (цепочку от первого блока до родителя).
+/// Этапы идут от дешёвых к дорогим: заголовок, связь с цепочкой, транзакции.
+/// Балансы проверяет `Ledger` при применении блока.
+pub fn validate_block(
+    block: &Block,
+    ancestors: &[Block],
+    params: &ChainParams,
+    now: i64,
+) -> Result<(), BlockValidationError> {
+    check_header(block, now)?;
+    check_context(block, ancestors, params)?;
+    check_transactions(block, params)
+}
+
+/// Проверки, не зависящие от цепочки: PoW, корень транзакций и время
+pub fn check_header(block: &Block, now: i64) -> Result<(), BlockValidationError> {
+    if !block.meets_target() {
+        return Err(BlockValidationError::InsufficientWork);
+    }
+    if !block.has_valid_transactions_root() {
+        return Err(BlockValidationError::TransactionsRootMismatch);
+    }
+
+    if block.get_datetime() > now + MAX_FUTURE_DRIFT {
+        return Err(BlockValidationError::TimeTooNew { time: block.get_datetime(), now });
+    }
+    Ok(())
+}
+
+/// Проверки относительно родительской цепочки: высота, цель и медианное время
+pub fn check_context(block: &Block, ancestors: &[Block], params: &ChainParams) -> Result<(), BlockValidationError> {
+    let parent = ancestors.last().ok_or(BlockValidationError::EmptyChain)?;
+    if block.get_previous_hash() != parent.get_hash() {
+        return Err(BlockValidationError::PreviousHashMismatch {
+            expected: parent.get_hash(),
+            actual: block.get_previous_hash(),
+        });
+    }
+
+    let expected_height = parent.get_id() + 1;
+    if block.get_id() != expected_height {
+        return Err(BlockValidationError::UnexpectedHeight { expected: expected_height, actual: block.get_id() });
+    }
+
+    let expected_target = difficulty::next_target(ancestors, params.max_target);
+    if block.get_target() != expected_target {
+        return Err(BlockValidationError::UnexpectedTarget { expected: expected_target, actual: block.get_target() });
+    }
+
+    let median = median_time_past(ancestors);
+    if block.get_datetime() <= median {
+        return Err(BlockValidationError::TimeTooOld { time: block.get_datetime(), median });
+    }
+    Ok(())
+}
+
+/// Правила транзакций: вес, coinbase, повторы, окна действия, подписи и скрипты.
+/// Окно действия и скрипты проверяются по высоте и времени самого блока
+pub fn check_transactions(block: &Block, params: &ChainParams) -> Result<(), BlockValidationError> {
+    let transactions = block.get_transactions();
+    let weight = block_weight(transactions);
+    if weight > params.max_block_weight {
+        return Err(BlockValidationError::TooHeavy { weight, max: params.max_block_weight });
+    }
+
+    let coinbase = match transactions.first() {
+        Some(transaction) if transaction.is_coinbase() => transaction,
+        _ => return Err(BlockValidationError::MissingCoinbase),
+    };
+    // Coinbase не проходит проверку подписи, поэтому адрес награды проверяется здесь
+    address::check_account(coinbase.get_seller()).map_err(BlockValidationError::InvalidCoinbaseAddress)?;
+    let expected = emission::coinbase_amount(block.get_id(), transactions);
+    if coinbase.get_transfer() != expected {
+        return Err(BlockValidationError::InvalidCoinbaseAmount { amount: coinbase.get_transfer(), expected });
+    }
+
+    let mut seen = HashSet::new();
+    for transaction in transactions {
+        let txid = transaction.get_txid();
+        if !seen.insert(txid.clone()) {
+            return Err(BlockValidationError::DuplicateTransaction(txid));
+        }
+    }
+
+    // Coinbase не подписывается, остальные coinbase отклонит Ledger
+    for transaction in transactions.iter().skip(1) {
+        let (height, time) = (block.get_id() as u64, block.get_datetime());
+        transaction.check_validity_window(height, time)?;
+        check_signature(transaction, params.chain_id, height, time)?;
+    }
+    Ok(())
+}
+
+/// Вес транзакций блока вместе с coinbase: сумма размеров их канонических представлений
+pub fn block_weight(transactions: &[SerializedTransaction]) -> usize {
+    transactions.iter().map(SerializedTransaction::get_size).sum()
+}
+
+/// Подпись транзакции сделана ключом отправителя для сети `chain_id`.
+/// Трату со скрипт-счёта подтверждают скрипты, выполненные в блоке с высотой `height` и временем `time`
+pub fn check_signature(transaction: &SerializedTransaction, chain_id: u32, height: u64, time: i64) -> Result<(), BlockValidationError> {
+    let deserialized = Transaction::deserialize(transaction.clone())
+        .map_err(|_| BlockValidationError::InvalidSignature(transaction.get_txid()))?;
+    if let Spender::Script(_) = deserialized.get_sender() {
+        return deserialized
+            .execute_script(chain_id, height, time)
+            .map(|_| ())
+            .map_err(|source| BlockValidationError::Script { txid: transaction.get_txid(), source });
+    }
+    if !deserialized.verify(chain_id) {
+        return Err(BlockValidationError::InvalidSignature(transaction.get_txid()));
+    }
+    Ok(())
+}
+
+/// Медиана времени последних `MEDIAN_TIME_SPAN` блоков
+pub fn median_time_past(chain: &[Block]) -> i64 {
+    let start = chain.len().saturating_sub(MEDIAN_TIME_SPAN);
+    let mut times: Vec<i64> = chain[start..].iter().map(|block| block.get_datetime()).collect();
+    if times.is_empty() {
+        return 0;
+    }
+    times.sort_unstable();
+    times[times.len() / 2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coin::node::blockchain::difficulty::INITIAL_TARGET;
+    use crate::coin::node::blockchain::script::{Op, Script};
+    use crate::coin::node::blockchain::crypto::{PrivateKey, SignatureAlgorithm, Signer};
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn chain_with_times(times: &[i64]) -> Vec<Block> {
+        times
+            .iter()
+            .enumerate()
+            .map(|(index, time)| Block::force_new(index + 1, *time, Vec::new(), "prev".to_string(), 0, INITIAL_TARGET))
+            .collect()
+    }
+
+    fn mine(mut block: Block) -> Block {
+        let mut nonce = 0;
+        while !block.meets_target() {
+            nonce += 1;
+            block.set_nonce(nonce);
+        }
+        block
+    }
+
+    fn child(parent: &Block, time: i64, transactions: Vec<SerializedTransaction>) -> Block {
+        mine(Block::force_new(parent.get_id() + 1, time, transactions, parent.get_hash(), 0, INITIAL_TARGET))
+    }
+
+    fn coinbase(height: usize) -> SerializedTransaction {
+        let miner = address::from_public_key(&PrivateKey::generate(SignatureAlgorithm::Ed25519).get_public_key());
+        emission::coinbase_transaction(&miner, height, &[])
+    }
+
+    #[test]
+    fn test_median_time_past() {
+        assert_eq!(median_time_past(&[]), 0);
+        assert_eq!(median_time_past(&chain_with_times(&[5, 1, 3])), 3);
+
+        let times: Vec<i64> = (0..20).collect();
+        assert_eq!(median_time_past(&chain_with_times(&times)), 14);
+    }
+
+    #[test]
+    fn test_valid_block_passes_all_stages() {
+        let chain = chain_with_times(&[NOW - 20, NOW - 10]);
+        let block = child(&chain[1], NOW, vec![coinbase(3)]);
+        assert_eq!(validate_block(&block, &chain, &ChainParams::main(), NOW), Ok(()));
+    }
+
+    #[test]
+    fn test_block_time_must_exceed_median() {
+        let chain = chain_with_times(&[NOW - 30, NOW - 20, NOW - 10]);
+        let median = median_time_past(&chain);
+
+        let at_median = child(&chain[2], median, vec![coinbase(4)]);
+        assert_eq!(
+            validate_block(&at_median, &chain, &ChainParams::main(), NOW),
+            Err(BlockValidationError::TimeTooOld { time: median, median })
+        );
+
+        let after_median = child(&chain[2], median + 1, vec![coinbase(4)]);
+        assert_eq!(validate_block(&after_median, &chain, &ChainParams::main(), NOW), Ok(()));
+    }
+
+    #[test]
+    fn test_rejects_wrong_height() {
+        let chain = chain_with_times(&[NOW - 10]);
+        let block = mine(Block::force_new(5, NOW, vec![coinbase(5)], chain[0].get_hash(), 0, INITIAL_TARGET));
+        assert_eq!(
+            validate_block(&block, &chain, &ChainParams::main(), NOW),
+            Err(BlockValidationError::UnexpectedHeight { expected: 2, actual: 5 })
+        );
+    }
+
+    #[test]
+    fn test_rejects_block_time_outside_window() {
+        let chain = chain_with_times(&[NOW - 30, NOW - 20, NOW - 10]);
+
+        let old = child(&chain[2], NOW - 25, vec![coinbase(4)]);
+        assert!(matches!(validate_block(&old, &chain, &ChainParams::main(), NOW), Err(BlockValidationError::TimeTooOld { .. })));
+
+        let future = child(&chain[2], NOW + MAX_FUTURE_DRIFT + 1, vec![coinbase(4)]);
+        assert!(matches!(validate_block(&future, &chain, &ChainParams::main(), NOW), Err(BlockValidationError::TimeTooNew { .. })));
+    }
+
+    #[test]
+    fn test_rejects_duplicate_transactions() {
+        let chain = chain_with_times(&[NOW - 10]);
+        let transaction = SerializedTransaction::new("a".into(), "b".into(), "c".into(), "msg".into(), Amount::from_coins(1));
+        let block = child(&chain[0], NOW, vec![coinbase(2), transaction.clone(), transaction]);
+        assert!(matches!(
+            validate_block(&block, &chain, &ChainParams::main(), NOW),
+            Err(BlockValidationError::DuplicateTransaction(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_unsigned_transaction() {
+        let chain = chain_with_times(&[NOW - 10]);
+        let transaction = SerializedTransaction::new("not a key".into(), "b".into(), "c".into(), "msg".into(), Amount::from_coins(1));
+        let block = child(&chain[0], NOW, vec![coinbase(2), transaction.clone()]);
+        assert_eq!(
+            validate_block(&block, &chain, &ChainParams::main(), NOW),
+            Err(BlockValidationError::InvalidSignature(transaction.get_txid()))
+        );
+    }
+
+    #[test]
+    fn test_rejects_transaction_outside_validity_window() {
+        let chain = chain_with_times(&[NOW - 10]);
+        let mut expired = SerializedTransaction::new("a".into(), "b".into(), "c".into(), "msg".into(), Amount::from_coins(1));
+        expired.valid_until = Some(1);
+        let block = child(&chain[0], NOW, vec![coinbase(2), expired.clone()]);
+        assert_eq!(
+            validate_block(&block, &chain, &ChainParams::main(), NOW),
+            Err(BlockValidationError::Validity(ValidityError::Expired { txid: expired.get_txid(), valid_until: 1 }))
+        );
+
+        // Граница по времени сверяется со временем блока
+        let mut early = expired.clone();
+        early.valid_until = None;
+        early.valid_after = Some(NOW as u64 + 1);
+        let block = child(&chain[0], NOW, vec![coinbase(2), early]);
+        assert!(matches!(
+            validate_block(&block, &chain, &ChainParams::main(), NOW),
+            Err(BlockValidationError::Validity(ValidityError::NotYetValid { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_script_spend_runs_against_block_height() {
+        let key = address::from_public_key(&PrivateKey::generate(SignatureAlgorithm::Ed25519).get_public_key());
+        // Тратить можно с высоты 3
+        let locking = Script::from(vec![Op::PushNumber(3), Op::CheckLockTime]);
+        let mut transaction = SerializedTransaction::new(locking.get_address(), key.clone(), key, "msg".into(), Amount::from_coins(1));
+        transaction.locking_script = Some(locking);
+
+        let chain = chain_with_times(&[NOW - 20]);
+        let early = child(&chain[0], NOW - 10, vec![coinbase(2), transaction.clone()]);
+        assert_eq!(
+            check_transactions(&early, &ChainParams::main()),
+            Err(BlockValidationError::Script { txid: transaction.get_txid(), source: ScriptError::LockTimeNotReached(3) })
+        );
+
+        let chain = chain_with_times(&[NOW - 20, NOW - 10]);
+        let block = child(&chain[1], NOW, vec![coinbase(3), transaction]);
+        assert_eq!(check_transactions(&block, &ChainParams::main()), Ok(()));
+    }
+
+    #[test]
+    fn test_rejects_block_over_weight_limit() {
+        let chain = chain_with_times(&[NOW - 10]);
+        let block = child(&chain[0], NOW, vec![coinbase(2)]);
+        let weight = block_weight(block.get_transactions());
+
+        let params = ChainParams { max_block_weight: weight, ..ChainParams::main() };
+        assert_eq!(validate_block(&block, &chain, &params, NOW), Ok(()));
+
+        let params = ChainParams { max_block_weight: weight - 1, ..ChainParams::main() };
+        assert_eq!(
+            validate_block(&block, &chain, &params, NOW),
+            Err(BlockValidationError::TooHeavy { weight, max: weight - 1 })
+        );
+    }
+
+    #[test]
+    fn test_rejects_coinbase_to_malformed_address() {
+        let chain = chain_with_times(&[NOW - 10]);
+        let block = child(&chain[0], NOW, vec![emission::coinbase_transaction("miner", 2, &[])]);
+        assert_eq!(
+            validate_block(&block, &chain, &ChainParams::main(), NOW),
+            Err(BlockValidationError::InvalidCoinbaseAddress(AddressError::InvalidLength("miner".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_rejects_missing_coinbase() {
+        let chain = chain_with_times(&[NOW - 10]);
+        let block = child(&chain[0], NOW, Vec::new());
+        assert_eq!(validate_block(&block, &chain, &ChainParams::main(), NOW), Err(BlockValidationError::MissingCoinbase));
+    }
+}
@@ -4,38 +4,39 @@ use std::path::Path;
 
 use base64::{Engine as _, engine::general_purpose::STANDARD_NO_PAD};
 use log::{error, info, warn};
-use rand::rngs::OsRng;
-use rsa::{RsaPrivateKey, RsaPublicKey};
-use rsa::pkcs1::{DecodeRsaPublicKey, EncodeRsaPublicKey};
-use rsa::pkcs8::{DecodePrivateKey, EncodePrivateKey};
+use rsa::RsaPrivateKey;
+use rsa::pkcs8::DecodePrivateKey;
 use serde::{Deserialize, Serialize};
 
+use crate::coin::node::blockchain::address;
+use crate::coin::node::blockchain::amount::Amount;
+use crate::coin::node::blockchain::crypto::{KeyError, PrivateKey, PublicKey, SignatureAlgorithm, Signer};
+use crate::coin::node::blockchain::ledger::Ledger;
+use crate::coin::node::blockchain::multisig::{MultisigError, MultisigPolicy, PartialSignature};
+use crate::coin::node::blockchain::transaction::Transaction;
+
 // Структура кошелька
 pub struct Wallet {
-    public_key: RsaPublicKey,
-    private_key: RsaPrivateKey,
-    amount: f64,
+    public_key: PublicKey,
+    private_key: PrivateKey,
+    amount: Amount,
 }
 
 impl Wallet {
-    // Создание нового кошелька
+    // Создание нового кошелька с алгоритмом подписи по умолчанию
     pub fn new() -> Wallet {
-        let mut rng = OsRng;
-        let private_key = match RsaPrivateKey::new(&mut rng, 2048) {
-            Ok(private_key) => {
-                info!("Private key successfully generated.");
-                private_key
-            }
-            Err(e) => {
-                error!("Unable to generate private key: {}", e);
-                panic!("Unable to generate private key");
-            }
-        };
+        Self::generate(SignatureAlgorithm::default())
+    }
 
-        let public_key = RsaPublicKey::from(&private_key);
+    // Создание нового кошелька с заданным алгоритмом подписи
+    pub fn generate(algorithm: SignatureAlgorithm) -> Wallet {
+        let private_key = PrivateKey::generate(algorithm);
+        info!("Private key ({}) successfully generated.", algorithm);
+
+        let public_key = private_key.get_public_key();
         info!("Public key successfully generated from private key.");
 
-        Wallet { public_key, private_key, amount: 0f64 }
+        Wallet { public_key, private_key, amount: Amount::ZERO }
     }
 
     // Сериализация кошелька
@@ -65,10 +66,7 @@ impl Wallet {
             Ok(serialized_wallet) => {
                 info!("Wallet successfully deserialized from JSON.");
 
-                let serialized_public_key = serialized_wallet.public_key.trim().to_string();
-                let public_key = match RsaPublicKey::from_pkcs1_der(
-                    &STANDARD_NO_PAD.decode(&serialized_public_key).unwrap()
-                ) {
+                let public_key = match PublicKey::decode_stored(&serialized_wallet.public_key) {
                     Ok(key) => key,
                     Err(e) => {
                         error!("Failed to decode public key: {}", e);
@@ -76,10 +74,7 @@ impl Wallet {
                     }
                 };
 
-                let serialized_private_key = serialized_wallet.private_key.trim().to_string();
-                let private_key = match RsaPrivateKey::from_pkcs8_der(
-                    &STANDARD_NO_PAD.decode(&serialized_private_key).unwrap()
-                ) {
+                let private_key = match decode_stored_private_key(&serialized_wallet.private_key) {
                     Ok(key) => key,
                     Err(e) => {
                         error!("Failed to decode private key: {}", e);
@@ -100,44 +95,85 @@ impl Wallet {
         }
     }
 
-    // Возвращает публичный ключ в формате Base64
+    // Возвращает публичный ключ в формате `<алгоритм>:<base64>`
     pub fn get_public_key_string(&self) -> String {
-        let public_key_der = self.public_key.to_pkcs1_der().unwrap();
         info!("Public key successfully converted to Base64.");
-        STANDARD_NO_PAD.encode(public_key_der.as_bytes())
+        self.public_key.encode()
+    }
+
+    // Адрес кошелька (Base58Check от хеша публичного ключа); его сообщают тем, кто переводит средства
+    pub fn get_address(&self) -> String {
+        address::from_public_key(&self.public_key)
     }
 
-    // Возвращает приватный ключ в формате Base64
+    // Возвращает приватный ключ в формате `<алгоритм>:<base64>`
     pub fn get_private_key_string(&self) -> String {
-        let private_key_der = self.private_key.to_pkcs8_der().unwrap();
         info!("Private key successfully converted to Base64.");
-        STANDARD_NO_PAD.encode(private_key_der.as_bytes())
+        self.private_key.encode()
     }
 
     // Получить публичный ключ
-    pub fn get_public_key(&self) -> RsaPublicKey {
+    pub fn get_public_key(&self) -> PublicKey {
         self.public_key.clone()
     }
 
     // Получить приватный ключ
-    pub fn get_private_key(&self) -> RsaPrivateKey {
+    pub fn get_private_key(&self) -> PrivateKey {
         self.private_key.clone()
     }
 
+    // Алгоритм подписи кошелька
+    pub fn get_algorithm(&self) -> SignatureAlgorithm {
+        self.private_key.get_algorithm()
+    }
+
     // Получить баланс кошелька
-    pub fn get_amount(&self) -> f64 {
+    pub fn get_amount(&self) -> Amount {
         self.amount
     }
 
     // Установить баланс кошелька
-    pub fn set_amount(&mut self, amount: f64) {
-        if amount < 0f64 {
-            error!("Attempted to set a negative balance: {}", amount);
-            panic!("Balance cannot be negative");
-        } else {
-            info!("Wallet balance updated to {}", amount);
-            self.amount = amount;
+    pub fn set_amount(&mut self, amount: Amount) {
+        info!("Wallet balance updated to {}", amount);
+        self.amount = amount;
+    }
+
+    // Обновить баланс кошелька по реестру цепочки
+    pub fn sync_amount(&mut self, ledger: &Ledger) {
+        self.amount = ledger.get_balance(&self.get_address());
+        info!("Wallet balance synchronized with ledger: {}", self.amount);
+    }
+
+    // Перевод нескольким получателям (адрес и сумма) одной транзакцией с одной подписью:
+    // первый получатель становится продавцом, остальные — дополнительными получателями
+    pub fn create_payment(&self, payments: Vec<(String, Amount)>, message: String) -> Result<Transaction, String> {
+        let mut payments = payments.into_iter();
+        let (seller, transfer) = payments.next().ok_or("Не указан ни один получатель")?;
+        address::check_account(&seller).map_err(|e| e.to_string())?;
+
+        let mut transaction = Transaction::new(self.get_public_key_string(), seller, self.get_address(), message, transfer);
+        for (recipient, amount) in payments {
+            transaction.add_output(recipient, amount)?;
         }
+        Ok(transaction)
+    }
+
+    // Multisig-счёт из ключа кошелька и ключей остальных участников (base64)
+    pub fn create_multisig(&self, threshold: usize, cosigners: Vec<String>) -> Result<MultisigPolicy, MultisigError> {
+        let mut keys = cosigners;
+        keys.push(self.get_public_key_string());
+        MultisigPolicy::new(threshold, keys)
+    }
+
+    // Подпись участника для транзакции multisig-счёта; отправляется тому, кто собирает подписи
+    pub fn sign_partial(&self, transaction: &Transaction, chain_id: u32) -> PartialSignature {
+        transaction.sign_partial(&self.private_key, chain_id)
+    }
+
+    // Добавить подпись этого кошелька к собираемой транзакции multisig-счёта
+    pub fn cosign(&self, transaction: &mut Transaction, chain_id: u32) -> Result<(), MultisigError> {
+        let partial = self.sign_partial(transaction, chain_id);
+        transaction.add_partial_signature(partial, chain_id)
     }
 
     // Загрузка кошелька из файла
@@ -200,7 +236,36 @@ impl Wallet {
 struct SerializedWallet {
     public_key: String,    // Публичный ключ в формате Base64
     private_key: String,   // Приватный ключ в формате Base64
-    amount: f64,
+    #[serde(deserialize_with = "deserialize_amount")]
+    amount: Amount,
+}
+
+// Ключ в файле записан с меткой алгоритма; в старых файлах — RSA без метки
+fn decode_stored_private_key(stored: &str) -> Result<PrivateKey, KeyError> {
+    PrivateKey::decode(stored).or_else(|e| {
+        STANDARD_NO_PAD
+            .decode(stored.trim())
+            .ok()
+            .and_then(|der| RsaPrivateKey::from_pkcs8_der(&der).ok())
+            .map(PrivateKey::Rsa)
+            .ok_or(e)
+    })
+}
+
+// Баланс в файле: базовые единицы или, в старых файлах, монеты числом с плавающей точкой
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredAmount {
+    Units(Amount),
+    Coins(f64),
+}
+
+fn deserialize_amount<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Amount, D::Error> {
+    match StoredAmount::deserialize(deserializer)? {
+        StoredAmount::Units(amount) => Ok(amount),
+        StoredAmount::Coins(coins) => Amount::from_coins_f64(coins)
+            .ok_or_else(|| serde::de::Error::custom(format!("Некорректный баланс кошелька: {}", coins))),
+    }
 }
 
 #[cfg(test)]
@@ -215,19 +280,22 @@ mod tests {
     fn test_wallet_creation() {
         let wallet = Wallet::new();
         // После создания баланс должен быть равен 0
-        assert_eq!(wallet.get_amount(), 0f64);
+        assert_eq!(wallet.get_amount(), Amount::ZERO);
         // Строковые представления ключей не должны быть пустыми.
         let public_key_str = wallet.get_public_key_string();
         let private_key_str = wallet.get_private_key_string();
         assert!(!public_key_str.is_empty());
         assert!(!private_key_str.is_empty());
+        // Адрес короче ключа и проходит проверку контрольной суммы
+        assert!(wallet.get_address().len() < public_key_str.len());
+        assert_eq!(address::check_account(&wallet.get_address()), Ok(()));
     }
 
     /// Тест сериализации и десериализации кошелька через JSON.
     #[test]
     fn test_wallet_serialization_deserialization() {
         let mut wallet = Wallet::new();
-        wallet.set_amount(150.0);
+        wallet.set_amount(Amount::from_coins(150));
         let public_key_before = wallet.get_public_key_string();
         let private_key_before = wallet.get_private_key_string();
 
@@ -245,16 +313,106 @@ mod tests {
     #[test]
     fn test_set_amount() {
         let mut wallet = Wallet::new();
-        wallet.set_amount(200.0);
-        assert_eq!(wallet.get_amount(), 200.0);
+        wallet.set_amount(Amount::from_coins(200));
+        assert_eq!(wallet.get_amount(), Amount::from_coins(200));
     }
 
-    /// Тест установки отрицательного баланса (ожидается паника).
+    /// Тест чтения старого файла кошелька с балансом в монетах.
     #[test]
-    #[should_panic(expected = "Balance cannot be negative")]
-    fn test_set_negative_amount() {
+    fn test_legacy_float_amount() {
+        let wallet = Wallet::new();
+        let json = format!(
+            r#"{{"public_key":"{}","private_key":"{}","amount":12.5}}"#,
+            wallet.get_public_key_string(),
+            wallet.get_private_key_string()
+        );
+
+        let restored = Wallet::from_json(&json);
+        assert_eq!(restored.get_public_key_string(), wallet.get_public_key_string());
+        assert_eq!(restored.get_amount(), "12.5".parse().unwrap());
+    }
+
+    /// Тест чтения старого файла кошелька с RSA-ключами без метки алгоритма.
+    #[test]
+    fn test_legacy_rsa_wallet() {
+        use rsa::pkcs1::EncodeRsaPublicKey;
+        use rsa::pkcs8::EncodePrivateKey;
+
+        let PrivateKey::Rsa(private_key) = PrivateKey::generate(SignatureAlgorithm::Rsa) else {
+            unreachable!()
+        };
+        let json = format!(
+            r#"{{"public_key":"{}","private_key":"{}","amount":0}}"#,
+            STANDARD_NO_PAD.encode(private_key.to_public_key().to_pkcs1_der().unwrap().as_bytes()),
+            STANDARD_NO_PAD.encode(private_key.to_pkcs8_der().unwrap().as_bytes())
+        );
+
+        let restored = Wallet::from_json(&json);
+        assert_eq!(restored.get_algorithm(), SignatureAlgorithm::Rsa);
+        // После загрузки ключи записываются уже с меткой
+        assert!(restored.get_public_key_string().starts_with("rsa:"));
+        assert!(restored.get_private_key_string().starts_with("rsa:"));
+    }
+
+    /// Тест синхронизации баланса с реестром цепочки.
+    #[test]
+    fn test_sync_amount_from_ledger() {
         let mut wallet = Wallet::new();
-        wallet.set_amount(-50.0);
+        let mut ledger = Ledger::new();
+        ledger.credit(&wallet.get_address(), Amount::from_coins(75)).unwrap();
+
+        wallet.sync_amount(&ledger);
+        assert_eq!(wallet.get_amount(), Amount::from_coins(75));
+    }
+
+    /// Тест сбора подписей участников multisig-счёта 2 из 3.
+    #[test]
+    fn test_collect_multisig_signatures() {
+        let chain_id = 1;
+        let coordinator = Wallet::new();
+        let cosigner = Wallet::new();
+        let absent = Wallet::new();
+        let policy = coordinator
+            .create_multisig(2, vec![cosigner.get_public_key_string(), absent.get_public_key_string()])
+            .unwrap();
+        assert_eq!(policy.get_keys().len(), 3);
+
+        let recipient = coordinator.get_address();
+        let mut transaction = Transaction::new_multisig(policy, recipient.clone(), recipient, "Treasury".to_string(), Amount::from_coins(5)).unwrap();
+        coordinator.cosign(&mut transaction, chain_id).unwrap();
+        assert!(!transaction.verify(chain_id));
+
+        // Подпись участника приходит отдельно и добавляется собирающим
+        let partial = cosigner.sign_partial(&transaction, chain_id);
+        transaction.add_partial_signature(partial, chain_id).unwrap();
+        assert!(transaction.verify(chain_id));
+
+        let outsider = Wallet::new();
+        assert!(matches!(outsider.cosign(&mut transaction, chain_id), Err(MultisigError::UnknownKey(_))));
+    }
+
+    /// Тест перевода нескольким получателям одной подписанной транзакцией.
+    #[test]
+    fn test_create_payment_to_several_recipients() {
+        let chain_id = 1;
+        let wallet = Wallet::new();
+        let recipients: Vec<String> = (0..3).map(|_| Wallet::new().get_address()).collect();
+        let payments: Vec<(String, Amount)> = recipients.iter().map(|recipient| (recipient.clone(), Amount::from_coins(10))).collect();
+
+        let mut transaction = wallet.create_payment(payments.clone(), "Payroll".to_string()).unwrap();
+        transaction.set_fee(Amount::from_coins(1));
+        transaction.sign(&wallet.get_private_key(), chain_id);
+        assert!(transaction.verify(chain_id));
+
+        let serialized = transaction.serialize();
+        assert_eq!(serialized.get_debit(), Some(Amount::from_coins(31)));
+        let paid: Vec<String> = serialized.get_outputs().into_iter().map(|output| output.recipient).collect();
+        assert_eq!(paid, recipients);
+
+        assert!(wallet.create_payment(Vec::new(), "Empty".to_string()).is_err());
+        let mut typo = payments;
+        typo[2].0.push('x');
+        assert!(wallet.create_payment(typo, "Typo".to_string()).is_err());
     }
 
     /// Тест сохранения кошелька в файл и последующей загрузки.
@@ -272,7 +430,7 @@ mod tests {
 
         // Создаём кошелёк и устанавливаем баланс
         let mut wallet = Wallet::new();
-        wallet.set_amount(500.0);
+        wallet.set_amount(Amount::from_coins(500));
         // Сохраняем кошелёк
         wallet.save_to_file(file_path);
         // Загружаем кошелёк из файла
@@ -1,7 +1,12 @@
 use crate::coin::node::blockchain::transaction::SerializedTransaction;
 
 pub enum TransactionMessage{
-    AddTransaction(SerializedTransaction),
+    // Транзакция в куче: она намного больше остальных сообщений
+    AddTransaction(Box<SerializedTransaction>),
     GetTransaction(),
+    // Добыть блок по команде, даже если очередь пуста
+    GenerateBlock(),
+    // Основная цепочка изменилась, очередь нужно перепроверить
+    ChainUpdated(),
     TransactionVec(Vec<SerializedTransaction>),
 }
\ No newline at end of file
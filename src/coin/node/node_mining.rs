@@ -1,19 +1,25 @@
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{Receiver, Sender};
 use std::time::Duration;
+use chrono::Utc;
 use log::{debug, error, warn};
 
 use crate::coin::node::blockchain::block::Block;
 use crate::coin::node::blockchain::blockchain::Blockchain;
+use crate::coin::node::blockchain::{emission, validation};
+use crate::coin::node::blockchain::ledger::LedgerError;
+use crate::coin::node::blockchain::validation::BlockValidationError;
 use crate::coin::node::blockchain::transaction::SerializedTransaction;
 use crate::coin::node::node_message::TransactionMessage;
-use crate::coin::node::node_message::TransactionMessage::{AddTransaction, GetTransaction};
+use crate::coin::node::node_message::TransactionMessage::{AddTransaction, ChainUpdated, GetTransaction};
 
 pub struct NodeMining {
     tx_transactions:Sender<TransactionMessage>,
     rx_transactions: Receiver<TransactionMessage>,
     tx_external: Sender<Block>,
     blockchain: Arc<Mutex<Blockchain>>,
+    // Адрес, на который выплачивается награда за блок
+    miner_address: String,
 }
 
 impl NodeMining {
@@ -21,13 +27,15 @@ impl NodeMining {
                tx_transactions:Sender<TransactionMessage>,
                rx_transactions: Receiver<TransactionMessage>,
                tx_external: Sender<Block>,
-               blockchain: Arc<Mutex<Blockchain>>
+               blockchain: Arc<Mutex<Blockchain>>,
+               miner_address: String,
     ) -> Self{
         NodeMining {
             tx_transactions,
             rx_transactions,
             tx_external,
             blockchain,
+            miner_address,
         }
     }
     pub fn run(&mut self){
@@ -42,8 +50,11 @@ impl NodeMining {
                 Err(err) => {
                     match err {
                         RecvTimeoutError=> {
-                            self.tx_transactions.send(GetTransaction()).unwrap();
-                            debug!("Request transactions in mining");
+                            // В сетях с добычей по команде блоки создаются только через GenerateBlock
+                            if !self.is_mine_on_demand() {
+                                self.tx_transactions.send(GetTransaction()).unwrap();
+                                debug!("Request transactions in mining");
+                            }
                         }
                         _ => {
                             error!("Mining | Unknown message type: {}", err);
@@ -58,22 +69,62 @@ impl NodeMining {
         debug!("Mining new block!");
 
         // Захватываем блокировку один раз для получения последнего блока.
-        let last_block = {
+        let (last_block, target, time, transactions) = {
             let mut blockchain = self.blockchain.lock().unwrap_or_else(|e| {
                 error!("Mutex poisoned: {}", e);
                 panic!("Critical error with blockchain lock")
             });
 
-            match blockchain.get_last_block() {
+            let last_block = match blockchain.get_last_block() {
                 Ok(block) => block.clone(),
                 Err(_) => {
                     warn!("Creating new chain, generating first block");
                     blockchain.create_first_block();
                     blockchain.get_last_block().expect("Newly created block should exist")
                 }
-            }
+            };
+
+            // Время блока должно быть позже медианы последних блоков, даже если они добыты в ту же секунду.
+            // Шаблон проверяется на это же время, иначе блок может не пройти проверку окна действия
+            let median = validation::median_time_past(&blockchain.chain);
+            let time = Utc::now().timestamp().max(median + 1);
+
+            // Шаблон уже упорядочен по номерам отправителей, порядок сохраняется.
+            // Не прошедшие проверку транзакции возвращаются в очередь: она перепроверит их
+            // по текущей цепочке и оставит те, что ещё можно включить в следующий блок
+            let mut ledger = blockchain.get_ledger().clone();
+            let chain_id = blockchain.get_params().chain_id;
+            let height = last_block.get_id() as u64 + 1;
+            let (transactions, rejected): (Vec<SerializedTransaction>, Vec<SerializedTransaction>) = transactions
+                .into_iter()
+                .partition(|transaction| {
+                    let result = transaction.check_validity_window(height, time)
+                        .map_err(Into::into)
+                        .and_then(|_| validation::check_signature(transaction, chain_id, height, time))
+                        .and_then(|_| ledger.apply_transaction(transaction, height, time).map_err(Into::into));
+                    match result {
+                        Ok(()) => true,
+                        Err(BlockValidationError::Ledger(LedgerError::NonceTooHigh { .. })) => {
+                            debug!("Transaction waits for previous nonce");
+                            false
+                        }
+                        Err(e) => {
+                            warn!("Transaction returned to queue for revalidation: {}", e);
+                            false
+                        }
+                    }
+                });
+            self.return_transactions(&rejected);
+            (last_block, blockchain.get_next_target(), time, transactions)
         };
 
+        // Награда майнеру идёт первой транзакцией блока
+        let height = last_block.get_id() + 1;
+        let mut block_transactions = vec![emission::coinbase_transaction(&self.miner_address, height, &transactions)];
+        block_transactions.extend(transactions.iter().cloned());
+
+        // Корень транзакций считается один раз, дальше перебирается только nonce заголовка
+        let mut new_block = Block::force_new(height, time, block_transactions, last_block.get_hash(), 0, target);
         let mut nonce = 0;
 
         loop {
@@ -95,31 +146,27 @@ impl NodeMining {
             };
 
             // Если последний блок изменился, значит другой поток уже обновил цепочку
-            if current_last_block.get_id() != last_block.get_id() {
+            if current_last_block.get_hash() != last_block.get_hash() {
                 drop(blockchain); // Освобождаем блокировку
-                // Отправляем транзакции обратно
-                for transaction in transactions.clone() {
-                    if let Err(e) = self.tx_transactions.send(AddTransaction(transaction)) {
-                        error!("Failed to send transaction: {}", e);
-                    }
-                }
+                self.return_transactions(&transactions);
                 break;
             }
 
-            // Создаем новый блок с текущим nonce
-            let new_block = Block::new(
-                current_last_block.get_id() + 1,
-                transactions.clone(),
-                current_last_block.get_hash(),
-                nonce,
-            );
+            // Обновляем nonce кандидата
+            new_block.set_nonce(nonce);
 
             if Blockchain::is_valid_block(&new_block) {
                 debug!("New block found with nonce: {}", nonce);
                 // Пытаемся добавить блок
                 if let Err(e) = blockchain.add_block(new_block.clone()) {
                     error!("Failed to add valid block: {}", e);
+                    // Шаблон уже изъят из очереди, иначе его транзакции потеряются
+                    self.return_transactions(&transactions);
                 } else {
+                    // Очередь убирает транзакции, ставшие недействительными после нового блока
+                    if let Err(e) = self.tx_transactions.send(ChainUpdated()) {
+                        error!("Failed to notify transaction queue: {}", e);
+                    }
                     match self.tx_external.send(new_block)
                     {
                         Ok(()) => { /* всё ок */ }
@@ -145,6 +192,19 @@ impl NodeMining {
     }
 
 
+    /// Возвращает транзакции не добавленного блока в очередь
+    fn return_transactions(&self, transactions: &[SerializedTransaction]) {
+        for transaction in transactions {
+            if let Err(e) = self.tx_transactions.send(AddTransaction(Box::new(transaction.clone()))) {
+                error!("Failed to send transaction: {}", e);
+            }
+        }
+    }
+
+    fn is_mine_on_demand(&self) -> bool {
+        self.blockchain.lock().expect("Error lock blockchain node").get_params().mine_on_demand
+    }
+
     pub fn get_blockchain(&self)-> Arc<Mutex<Blockchain>> {
         self.blockchain.clone()
     }
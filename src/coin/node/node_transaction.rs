@@ -1,26 +1,59 @@
-use std::collections::BinaryHeap;
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::collections::{BTreeMap, BinaryHeap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
 use std::time::Duration;
 
-use log::error;
+use chrono::Utc;
+use log::{debug, error, warn};
+use thiserror::Error;
 
-use crate::coin::node::blockchain::transaction::SerializedTransaction;
+use crate::coin::node::blockchain::blockchain::Blockchain;
+use crate::coin::node::blockchain::{emission, validation};
+use crate::coin::node::blockchain::ledger::LedgerError;
+use crate::coin::node::blockchain::validation::BlockValidationError;
+use crate::coin::node::blockchain::transaction::{FeeRateKey, SerializedTransaction, ValidityError};
 use crate::coin::node::node_message::TransactionMessage;
 
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum MempoolError {
+    #[error("Транзакция {0} уже в очереди")]
+    Duplicate(String),
+    #[error("Очередь заполнена, комиссии транзакции {0} не хватает, чтобы вытеснить другие")]
+    Full(String),
+    #[error("{0}")]
+    Invalid(#[from] BlockValidationError),
+    #[error("{0}")]
+    Ledger(#[from] LedgerError),
+    #[error("{0}")]
+    Validity(#[from] ValidityError),
+}
+
+/// Транзакция в очереди и её приоритет, вычисленный при постановке в очередь
+struct MempoolEntry {
+    transaction: SerializedTransaction,
+    key: FeeRateKey,
+}
+
 pub struct NodeTransaction{
-    transaction_queue: BinaryHeap<SerializedTransaction>,
+    // Ожидающие транзакции по идентификатору
+    transactions: BTreeMap<String, MempoolEntry>,
+    // Суммарный размер ожидающих транзакций, байт
+    size: usize,
     tx: Sender<TransactionMessage>,
     rx: Receiver<TransactionMessage>,
     external_tx: Sender<TransactionMessage>,
+    blockchain: Arc<Mutex<Blockchain>>,
 }
 
 impl NodeTransaction{
-    pub fn new(external_tx:Sender<TransactionMessage>) -> Self{
+    pub fn new(external_tx:Sender<TransactionMessage>, blockchain: Arc<Mutex<Blockchain>>) -> Self{
         let (tx, rx) = channel();
         NodeTransaction{
-            transaction_queue: BinaryHeap::new(),
+            transactions: BTreeMap::new(),
+            size: 0,
             tx, rx,
-            external_tx
+            external_tx,
+            blockchain,
         }
     }
 
@@ -30,7 +63,9 @@ impl NodeTransaction{
                 Ok(message) => {
                     match message {
                         TransactionMessage::AddTransaction(transaction) => {
-                            self.transaction_queue.push(transaction);
+                            if let Err(e) = self.add_transaction(*transaction) {
+                                warn!("Transaction rejected: {}", e);
+                            }
                         }
                         TransactionMessage::GetTransaction() => {
                             let chain = self.get_transactions();
@@ -38,28 +73,231 @@ impl NodeTransaction{
                                 self.external_tx.send(TransactionMessage::TransactionVec(chain)).unwrap();
                             }
                         }
+                        TransactionMessage::GenerateBlock() => {
+                            let chain = self.get_transactions();
+                            self.external_tx.send(TransactionMessage::TransactionVec(chain)).unwrap();
+                        }
+                        TransactionMessage::ChainUpdated() => self.revalidate(),
                         _ => ()
                     }
                 },
-                Err(err) => {
-                    match err {
-                        RecvTimeoutError=> {}
-                        _ => {
-                            error!("Unknown transaction message type: {}", err);
-                        }
-                    }
+                Err(RecvTimeoutError::Timeout) => self.evict_expired(),
+                Err(RecvTimeoutError::Disconnected) => {
+                    error!("Transaction channel disconnected, stopping transaction node");
+                    break;
                 }
             }
         }
     }
 
+    /// Принимает транзакцию в очередь, если её ещё нет в очереди, её можно включить
+    /// в следующий блок, подпись верна, отправитель может её оплатить с учётом уже
+    /// ожидающих в очереди транзакций и её номер идёт следом за ними.
+    /// Если очередь заполнена, транзакция вытесняет менее выгодные
+    pub fn add_transaction(&mut self, transaction: SerializedTransaction) -> Result<(), MempoolError> {
+        let key = transaction.get_fee_rate_key();
+        self.admit(transaction.get_txid(), MempoolEntry { transaction, key }, true)
+    }
+
+    /// Принимает транзакцию с уже вычисленными идентификатором и приоритетом.
+    /// Подпись не зависит от состояния цепочки, поэтому при перепроверке очереди её можно не проверять заново
+    fn admit(&mut self, txid: String, entry: MempoolEntry, verify_signature: bool) -> Result<(), MempoolError> {
+        if self.transactions.contains_key(&txid) {
+            return Err(MempoolError::Duplicate(txid));
+        }
+
+        let transaction = &entry.transaction;
+        let sender = transaction.get_sender();
+        let pending: Vec<SerializedTransaction> = self.transactions
+            .values()
+            .filter(|entry| entry.transaction.sender == sender)
+            .map(|entry| entry.transaction.clone())
+            .collect();
+
+        let blockchain = self.blockchain.lock().expect("Error lock blockchain node");
+        let (height, now) = (blockchain.get_next_height() as u64, Utc::now().timestamp());
+        let (chain_id, max_size) = (blockchain.get_params().chain_id, blockchain.get_params().max_mempool_size);
+        transaction.check_validity_window(height, now)?;
+        if verify_signature {
+            validation::check_signature(transaction, chain_id, height, now)?;
+        }
+        blockchain.get_ledger().check_spend(transaction, &pending, height, now)?;
+        drop(blockchain);
+
+        self.make_room(&entry, max_size)?;
+        self.insert(txid, entry);
+        Ok(())
+    }
+
+    /// Освобождает место под транзакцию, вытесняя транзакции с меньшей комиссией за байт.
+    /// Вместе с вытесненной уходят следующие по номеру транзакции её отправителя,
+    /// а транзакции отправителя новой не трогаются: она может от них зависеть
+    fn make_room(&mut self, entry: &MempoolEntry, max_size: usize) -> Result<(), MempoolError> {
+        let required = entry.key.get_size();
+        if self.size + required <= max_size {
+            return Ok(());
+        }
+
+        let sender = &entry.transaction.sender;
+        let mut candidates: Vec<&MempoolEntry> = Vec::new();
+        let mut by_sender: HashMap<&str, Vec<(&String, &MempoolEntry)>> = HashMap::new();
+        for (txid, candidate) in &self.transactions {
+            if candidate.transaction.sender != *sender {
+                candidates.push(candidate);
+                by_sender.entry(&candidate.transaction.sender).or_default().push((txid, candidate));
+            }
+        }
+        candidates.sort_by(|a, b| a.key.cmp(&b.key));
+
+        // Наименьший вытесненный номер каждого отправителя: транзакции с большими номерами уже вытеснены
+        let mut evicted_from: HashMap<&str, u64> = HashMap::new();
+        let mut evicted = Vec::new();
+        let mut freed = 0;
+        for candidate in candidates {
+            if self.size - freed + required <= max_size || candidate.key >= entry.key {
+                break;
+            }
+            let candidate_sender = candidate.transaction.sender.as_str();
+            let nonce = candidate.transaction.get_nonce();
+            let evicted_before = evicted_from.get(candidate_sender).copied();
+            if evicted_before.is_some_and(|from| from <= nonce) {
+                continue;
+            }
+            for (txid, descendant) in &by_sender[candidate_sender] {
+                let descendant_nonce = descendant.transaction.get_nonce();
+                if descendant_nonce >= nonce && evicted_before.is_none_or(|from| descendant_nonce < from) {
+                    freed += descendant.key.get_size();
+                    evicted.push((*txid).clone());
+                }
+            }
+            evicted_from.insert(candidate_sender, nonce);
+        }
+        if self.size - freed + required > max_size {
+            return Err(MempoolError::Full(entry.transaction.get_txid()));
+        }
+
+        for txid in evicted {
+            debug!("Transaction {} evicted from full queue", txid);
+            self.remove(&txid);
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, txid: String, entry: MempoolEntry) {
+        self.size += entry.key.get_size();
+        self.transactions.insert(txid, entry);
+    }
+
+    fn remove(&mut self, txid: &str) -> Option<SerializedTransaction> {
+        let entry = self.transactions.remove(txid)?;
+        self.size -= entry.key.get_size();
+        Some(entry.transaction)
+    }
+
+    /// Удаляет из очереди транзакции, которые уже не попадут в следующий блок из-за истёкшего срока
+    pub fn evict_expired(&mut self) {
+        let height = self.blockchain.lock().expect("Error lock blockchain node").get_next_height() as u64;
+        let now = Utc::now().timestamp();
+        let expired: Vec<String> = self.transactions
+            .iter()
+            .filter(|(_, entry)| entry.transaction.is_expired(height, now))
+            .map(|(txid, _)| txid.clone())
+            .collect();
+        for txid in &expired {
+            self.remove(txid);
+        }
+        if !expired.is_empty() {
+            debug!("Evicted {} expired transactions", expired.len());
+        }
+    }
+
+    /// Перепроверяет очередь после подключения блока или реорганизации.
+    /// Транзакции заново принимаются по порядку номеров: попавшие в блок и ставшие
+    /// недействительными отсеиваются, как при первом приёме. Подписи проверены при приёме
+    /// и ещё раз проверяются при сборке шаблона, поэтому здесь не проверяются
+    pub fn revalidate(&mut self) {
+        let mut entries: Vec<(String, MempoolEntry)> = std::mem::take(&mut self.transactions).into_iter().collect();
+        self.size = 0;
+        entries.sort_by_key(|(_, entry)| entry.transaction.get_nonce());
+
+        let before = entries.len();
+        for (txid, entry) in entries {
+            if let Err(e) = self.admit(txid, entry, false) {
+                debug!("Transaction removed from queue: {}", e);
+            }
+        }
+        let removed = before - self.transactions.len();
+        if removed > 0 {
+            debug!("Removed {} transactions after chain update", removed);
+        }
+    }
+
+    /// Собирает транзакции для шаблона блока: жадно берёт самую выгодную по комиссии за байт,
+    /// пока она помещается в предельный вес блока за вычетом места под coinbase.
+    /// Транзакции отправителя идут по порядку номеров: в очереди выбора стоит только следующая
+    /// по номеру транзакция каждого отправителя, её преемник попадает туда после её выбора.
+    /// Недействительные транзакции удаляются из очереди, а не поместившиеся и ещё не доступные
+    /// остаются в ней до следующего блока вместе с преемниками
     pub fn get_transactions(&mut self) -> Vec<SerializedTransaction> {
+        self.evict_expired();
+        let blockchain = self.blockchain.lock().expect("Error lock blockchain node");
+        let chain_id = blockchain.get_params().chain_id;
+        let max_weight = blockchain.get_params().max_block_weight.saturating_sub(emission::COINBASE_RESERVED_WEIGHT);
+        let (height, now) = (blockchain.get_next_height() as u64, Utc::now().timestamp());
+        let mut ledger = blockchain.get_ledger().clone();
+        drop(blockchain);
+
+        // Транзакции каждого отправителя по возрастанию номера
+        let mut by_sender: HashMap<&str, Vec<(&String, &MempoolEntry)>> = HashMap::new();
+        for (txid, entry) in &self.transactions {
+            by_sender.entry(&entry.transaction.sender).or_default().push((txid, entry));
+        }
+        let mut senders: Vec<VecDeque<(String, FeeRateKey, SerializedTransaction)>> = by_sender
+            .into_values()
+            .map(|mut entries| {
+                entries.sort_by_key(|(_, entry)| entry.transaction.get_nonce());
+                entries
+                    .into_iter()
+                    .map(|(txid, entry)| (txid.clone(), entry.key.clone(), entry.transaction.clone()))
+                    .collect()
+            })
+            .collect();
+        let mut queue: BinaryHeap<(FeeRateKey, usize)> = senders
+            .iter()
+            .enumerate()
+            .filter_map(|(index, pending)| pending.front().map(|(_, key, _)| (key.clone(), index)))
+            .collect();
+
         let mut transactions = Vec::new();
-        for _ in 0..4 {
-            if let Some(t) = self.transaction_queue.pop() {
-                transactions.push(t);
-            } else {
-                break; // Нет элементов — выходим из цикла
+        let mut weight = 0;
+        while let Some((_, index)) = queue.pop() {
+            let Some((txid, key, transaction)) = senders[index].pop_front() else {
+                continue;
+            };
+            if weight + key.get_size() > max_weight {
+                continue;
+            }
+            let result = transaction.check_validity_window(height, now)
+                .map_err(BlockValidationError::from)
+                .and_then(|_| ledger.check_transaction(&transaction, height, now).map_err(Into::into))
+                .and_then(|_| validation::check_signature(&transaction, chain_id, height, now))
+                .and_then(|_| ledger.apply_transaction(&transaction, height, now).map_err(Into::into));
+            match result {
+                Ok(()) => {
+                    weight += key.get_size();
+                    self.remove(&txid);
+                    transactions.push(transaction);
+                    // Выбранная транзакция открывает следующую по номеру
+                    if let Some((_, next_key, _)) = senders[index].front() {
+                        queue.push((next_key.clone(), index));
+                    }
+                }
+                Err(BlockValidationError::Ledger(LedgerError::NonceTooHigh { .. }))
+                | Err(BlockValidationError::Validity(ValidityError::NotYetValid { .. })) => (),
+                Err(e) => {
+                    warn!("Transaction dropped from queue: {}", e);
+                    self.remove(&txid);
+                }
             }
         }
         transactions
@@ -68,4 +306,211 @@ impl NodeTransaction{
     pub fn get_sender(&self) -> Sender<TransactionMessage> {
         return self.tx.clone();
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coin::node::blockchain::address;
+    use crate::coin::node::blockchain::amount::Amount;
+    use crate::coin::node::blockchain::block::Block;
+    use crate::coin::node::blockchain::crypto::{PrivateKey, SignatureAlgorithm, Signer};
+    use crate::coin::node::blockchain::genesis::GenesisConfig;
+    use crate::coin::node::blockchain::transaction::Transaction;
+    use crate::coin::params::ChainParams;
+
+    fn node() -> NodeTransaction {
+        let mut blockchain = Blockchain::with_params(ChainParams::regtest());
+        blockchain.create_first_block();
+        NodeTransaction::new(channel().0, Arc::new(Mutex::new(blockchain)))
+    }
+
+    fn key_address(key: &PrivateKey) -> String {
+        address::from_public_key(&key.get_public_key())
+    }
+
+    /// Узел, в первом блоке которого каждому ключу выделено по 100 монет
+    fn funded_node(keys: &[&PrivateKey], params: ChainParams) -> NodeTransaction {
+        let genesis = GenesisConfig {
+            allocations: keys.iter().map(|key| (key_address(key), Amount::from_coins(100))).collect(),
+            ..params.genesis.clone()
+        };
+        let mut blockchain = Blockchain::with_params(ChainParams { genesis, ..params });
+        blockchain.create_first_block();
+        NodeTransaction::new(channel().0, Arc::new(Mutex::new(blockchain)))
+    }
+
+    fn payment(key: &PrivateKey, nonce: u64, fee: &str) -> SerializedTransaction {
+        let recipient = key_address(&PrivateKey::generate(SignatureAlgorithm::Ed25519));
+        let mut transaction = Transaction::new(key.get_public_key().encode(), recipient.clone(), recipient, "Pay".to_string(), Amount::from_coins(1));
+        transaction.set_nonce(nonce);
+        transaction.set_fee(fee.parse().unwrap());
+        transaction.sign(key, ChainParams::regtest().chain_id);
+        transaction.serialize()
+    }
+
+    fn transaction(valid_after: Option<u64>, valid_until: Option<u64>) -> SerializedTransaction {
+        let key = PrivateKey::generate(SignatureAlgorithm::Ed25519);
+        let mut transaction = Transaction::new(
+            key.get_public_key().encode(),
+            key_address(&key),
+            key_address(&key),
+            "Window".to_string(),
+            Amount::ZERO,
+        );
+        transaction.set_valid_after(valid_after);
+        transaction.set_valid_until(valid_until);
+        transaction.sign(&key, ChainParams::regtest().chain_id);
+        transaction.serialize()
+    }
+
+    fn queued(node: &NodeTransaction) -> Vec<SerializedTransaction> {
+        let mut entries: Vec<&MempoolEntry> = node.transactions.values().collect();
+        entries.sort_by(|a, b| b.key.cmp(&a.key));
+        entries.into_iter().map(|entry| entry.transaction.clone()).collect()
+    }
+
+    #[test]
+    fn test_rejects_transaction_outside_window() {
+        let mut node = node();
+        // Следующий блок имеет высоту 2
+        assert!(matches!(
+            node.add_transaction(transaction(Some(3), None)),
+            Err(MempoolError::Validity(ValidityError::NotYetValid { .. }))
+        ));
+        assert!(matches!(
+            node.add_transaction(transaction(None, Some(1))),
+            Err(MempoolError::Validity(ValidityError::Expired { .. }))
+        ));
+        assert_eq!(node.add_transaction(transaction(Some(2), Some(2))), Ok(()));
+    }
+
+    #[test]
+    fn test_evicts_expired_transactions() {
+        let mut node = node();
+        node.add_transaction(transaction(None, Some(2))).unwrap();
+        assert_eq!(node.transactions.len(), 1);
+
+        // Блок на высоте 2 добыт без транзакции, срок её действия истёк
+        {
+            let mut blockchain = node.blockchain.lock().unwrap();
+            let genesis = blockchain.get_last_block().unwrap();
+            let coinbase = emission::coinbase_transaction(&key_address(&PrivateKey::generate(SignatureAlgorithm::Ed25519)), 2, &[]);
+            let block = Block::new(2, vec![coinbase], genesis.get_hash(), 0, blockchain.get_next_target());
+            blockchain.add_block(block).unwrap();
+        }
+        node.evict_expired();
+        assert!(node.transactions.is_empty());
+    }
+
+    #[test]
+    fn test_template_fills_by_fee_rate_up_to_weight() {
+        let keys: Vec<PrivateKey> = (0..3).map(|_| PrivateKey::generate(SignatureAlgorithm::Ed25519)).collect();
+        let payments = [payment(&keys[0], 0, "0.1"), payment(&keys[1], 0, "0.3"), payment(&keys[2], 0, "0.2")];
+        // Места хватает только на две транзакции
+        let weight = emission::COINBASE_RESERVED_WEIGHT + payments.iter().map(|t| t.get_size()).max().unwrap() * 2;
+        let mut node = funded_node(&keys.iter().collect::<Vec<_>>(), ChainParams { max_block_weight: weight, ..ChainParams::regtest() });
+        for transaction in payments.iter().cloned() {
+            node.add_transaction(transaction).unwrap();
+        }
+
+        assert_eq!(node.get_transactions(), vec![payments[1].clone(), payments[2].clone()]);
+        assert_eq!(queued(&node), vec![payments[0].clone()]);
+    }
+
+    #[test]
+    fn test_template_keeps_sender_nonce_order() {
+        let (alice, bob) = (PrivateKey::generate(SignatureAlgorithm::Ed25519), PrivateKey::generate(SignatureAlgorithm::Ed25519));
+        let mut node = funded_node(&[&alice, &bob], ChainParams::regtest());
+        // Вторая транзакция alice выгоднее первой, но не может её опередить
+        let first = payment(&alice, 0, "0.1");
+        let second = payment(&alice, 1, "0.5");
+        let other = payment(&bob, 0, "0.3");
+        for transaction in [first.clone(), second.clone(), other.clone()] {
+            node.add_transaction(transaction).unwrap();
+        }
+
+        assert_eq!(node.get_transactions(), vec![other, first, second]);
+        assert!(node.transactions.is_empty());
+    }
+
+    #[test]
+    fn test_template_skips_invalid_transactions() {
+        let (alice, bob) = (PrivateKey::generate(SignatureAlgorithm::Ed25519), PrivateKey::generate(SignatureAlgorithm::Ed25519));
+        let mut node = funded_node(&[&alice, &bob], ChainParams::regtest());
+        let mut forged = payment(&alice, 0, "0.5");
+        forged.message = "Forged".to_string();
+        let valid = payment(&bob, 0, "0.1");
+        // Подделка попадает в очередь в обход проверок при приёме
+        node.insert(forged.get_txid(), MempoolEntry { key: forged.get_fee_rate_key(), transaction: forged });
+        node.add_transaction(valid.clone()).unwrap();
+
+        // Транзакция с неверной подписью не мешает остальным и удаляется из очереди
+        assert_eq!(node.get_transactions(), vec![valid]);
+        assert!(node.transactions.is_empty());
+    }
+
+    #[test]
+    fn test_rejects_duplicate_and_forged_transactions() {
+        let alice = PrivateKey::generate(SignatureAlgorithm::Ed25519);
+        let mut node = funded_node(&[&alice], ChainParams::regtest());
+        let transaction = payment(&alice, 0, "0.1");
+        node.add_transaction(transaction.clone()).unwrap();
+        assert_eq!(node.add_transaction(transaction.clone()), Err(MempoolError::Duplicate(transaction.get_txid())));
+
+        let mut forged = payment(&alice, 1, "0.1");
+        forged.message = "Forged".to_string();
+        assert_eq!(
+            node.add_transaction(forged.clone()),
+            Err(MempoolError::Invalid(BlockValidationError::InvalidSignature(forged.get_txid())))
+        );
+        assert_eq!(queued(&node), vec![transaction]);
+    }
+
+    #[test]
+    fn test_full_queue_evicts_lowest_fee_rate() {
+        let keys: Vec<PrivateKey> = (0..4).map(|_| PrivateKey::generate(SignatureAlgorithm::Ed25519)).collect();
+        let cheap = payment(&keys[0], 0, "0.1");
+        let cheap_next = payment(&keys[0], 1, "0.5");
+        let middle = payment(&keys[1], 0, "0.3");
+        let better = payment(&keys[2], 0, "0.2");
+        let cheapest = payment(&keys[3], 0, "0.05");
+        // В очередь помещаются только три транзакции
+        let max_mempool_size = [&cheap, &cheap_next, &middle, &better].iter().map(|t| t.get_size()).max().unwrap() * 3;
+        let mut node = funded_node(&keys.iter().collect::<Vec<_>>(), ChainParams { max_mempool_size, ..ChainParams::regtest() });
+        for transaction in [cheap, cheap_next, middle.clone()] {
+            node.add_transaction(transaction).unwrap();
+        }
+
+        // Вместе с самой дешёвой уходит следующая за ней транзакция того же отправителя
+        node.add_transaction(better.clone()).unwrap();
+        assert_eq!(queued(&node), vec![middle.clone(), better.clone()]);
+        assert_eq!(node.size, middle.get_size() + better.get_size());
+
+        node.add_transaction(payment(&keys[1], 1, "0.4")).unwrap();
+        assert_eq!(node.add_transaction(cheapest.clone()), Err(MempoolError::Full(cheapest.get_txid())));
+        assert_eq!(node.transactions.len(), 3);
+    }
+
+    #[test]
+    fn test_revalidates_after_block() {
+        let alice = PrivateKey::generate(SignatureAlgorithm::Ed25519);
+        let mut node = funded_node(&[&alice], ChainParams::regtest());
+        let first = payment(&alice, 0, "0.1");
+        let second = payment(&alice, 1, "0.1");
+        node.add_transaction(first.clone()).unwrap();
+        node.add_transaction(second.clone()).unwrap();
+
+        // Первую транзакцию добыл другой узел
+        {
+            let mut blockchain = node.blockchain.lock().unwrap();
+            let parent = blockchain.get_last_block().unwrap();
+            let transactions = vec![emission::coinbase_transaction(&key_address(&alice), 2, std::slice::from_ref(&first)), first];
+            let block = Block::new(2, transactions, parent.get_hash(), 0, blockchain.get_next_target());
+            blockchain.add_block(block).unwrap();
+        }
+        node.revalidate();
+        assert_eq!(queued(&node), vec![second.clone()]);
+        assert_eq!(node.size, second.get_size());
+    }
+}
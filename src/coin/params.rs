@@ -0,0 +1,165 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::coin::node::blockchain::difficulty;
+use crate::coin::node::blockchain::genesis::GenesisConfig;
+
+/// Сеть, к которой подключается узел
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    Main,
+    Test,
+    Regtest,
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.trim().to_lowercase().as_str() {
+            "main" | "mainnet" => Ok(Network::Main),
+            "test" | "testnet" => Ok(Network::Test),
+            "regtest" => Ok(Network::Regtest),
+            other => Err(format!("Неизвестная сеть: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Network::Main => "main",
+            Network::Test => "test",
+            Network::Regtest => "regtest",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Параметры цепочки и узла для выбранной сети
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainParams {
+    pub network: Network,
+    /// Входит в подпись транзакций, поэтому транзакция одной сети недействительна в другой
+    pub chain_id: u32,
+    /// Порт P2P-сервера по умолчанию
+    pub default_port: u16,
+    /// Каталог для БД и кошелька
+    pub data_dir: PathBuf,
+    /// Самая лёгкая цель, до которой может опуститься сложность
+    pub max_target: u64,
+    /// Предельный вес блока: суммарный размер канонических представлений его транзакций, байт
+    pub max_block_weight: usize,
+    /// Предельный суммарный размер транзакций в очереди узла, байт
+    pub max_mempool_size: usize,
+    /// Блоки добываются только по команде, а не непрерывно
+    pub mine_on_demand: bool,
+    pub genesis: GenesisConfig,
+}
+
+impl ChainParams {
+    pub fn main() -> ChainParams {
+        ChainParams {
+            network: Network::Main,
+            chain_id: 1,
+            default_port: 7878,
+            data_dir: PathBuf::from("cache/main"),
+            max_target: difficulty::MAX_TARGET,
+            max_block_weight: 1_000_000,
+            max_mempool_size: 10_000_000,
+            mine_on_demand: false,
+            genesis: GenesisConfig::default(),
+        }
+    }
+
+    /// Общая тестовая сеть: правила основной сети, но свой первый блок и порт
+    pub fn test() -> ChainParams {
+        ChainParams {
+            network: Network::Test,
+            chain_id: 2,
+            default_port: 17878,
+            data_dir: PathBuf::from("cache/test"),
+            genesis: GenesisConfig {
+                message: "Test network".to_string(),
+                ..GenesisConfig::default()
+            },
+            ..ChainParams::main()
+        }
+    }
+
+    /// Локальная сеть для разработки: любой хеш проходит цель, блоки добываются по команде
+    pub fn regtest() -> ChainParams {
+        ChainParams {
+            network: Network::Regtest,
+            chain_id: 3,
+            default_port: 27878,
+            data_dir: PathBuf::from("cache/regtest"),
+            max_target: u64::MAX,
+            max_block_weight: 1_000_000,
+            max_mempool_size: 10_000_000,
+            mine_on_demand: true,
+            genesis: GenesisConfig {
+                message: "Regtest network".to_string(),
+                target: u64::MAX,
+                ..GenesisConfig::default()
+            },
+        }
+    }
+
+    pub fn for_network(network: Network) -> ChainParams {
+        match network {
+            Network::Main => ChainParams::main(),
+            Network::Test => ChainParams::test(),
+            Network::Regtest => ChainParams::regtest(),
+        }
+    }
+
+    pub fn get_db_path(&self) -> PathBuf {
+        self.data_dir.join("chain.db")
+    }
+
+    pub fn get_wallet_path(&self) -> PathBuf {
+        self.data_dir.join("wallet.json")
+    }
+}
+
+impl Default for ChainParams {
+    fn default() -> Self {
+        ChainParams::main()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_from_str() {
+        assert_eq!("main".parse(), Ok(Network::Main));
+        assert_eq!("Testnet".parse(), Ok(Network::Test));
+        assert_eq!(" regtest ".parse(), Ok(Network::Regtest));
+        assert!("other".parse::<Network>().is_err());
+    }
+
+    #[test]
+    fn test_profiles_are_separated() {
+        let profiles = [ChainParams::main(), ChainParams::test(), ChainParams::regtest()];
+        for (index, params) in profiles.iter().enumerate() {
+            for other in &profiles[index + 1..] {
+                assert_ne!(params.chain_id, other.chain_id);
+                assert_ne!(params.default_port, other.default_port);
+                assert_ne!(params.get_db_path(), other.get_db_path());
+                assert_ne!(params.genesis.get_hash(), other.genesis.get_hash());
+            }
+        }
+    }
+
+    #[test]
+    fn test_regtest_accepts_any_hash() {
+        let params = ChainParams::regtest();
+        let genesis = params.genesis.build();
+        assert!(difficulty::hash_meets_target(&[0xfe; 64], genesis.get_target()));
+        assert!(params.mine_on_demand);
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use std::io::{Error, ErrorKind, Write};
-use std::net::{SocketAddr, TcpStream};
+use std::net::{Shutdown, SocketAddr, TcpStream};
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{Receiver, Sender};
 use std::time::{Duration, Instant};
@@ -62,6 +62,15 @@ impl ConnectionPool {
         }
     }
 
+    // Принудительное закрытие соединения с пиром
+    fn disconnect_peer(&mut self, addr: &SocketAddr) {
+        if let Some(peer) = self.connections.get(addr)
+            && let Ok(stream) = peer.stream.lock() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        self.remove_connection(addr);
+    }
+
     // Получение списка всех адресов пиров
     pub fn get_peer_addresses(&self) -> Vec<SocketAddr> {
         self.connections.keys().cloned().collect()
@@ -140,6 +149,14 @@ impl ConnectionPool {
                 Ok(PoolMessage::PeerMessage(addr, message)) => {
                     self.handle_peer_message(addr, message);
                 },
+                Ok(PoolMessage::DisconnectPeer(addr)) => {
+                    self.disconnect_peer(&addr);
+                },
+                Ok(PoolMessage::SendToPeer(addr, message)) => {
+                    if let Err(e) = self.send_to_peer(&addr, &message) {
+                        warn!("Не удалось отправить сообщение пиру {}: {}", addr, e);
+                    }
+                },
                 Err(err) => {
                     warn!("Error timeout pool: {}", err);
                     // Таймаут - чистим неактивные соединения
@@ -173,7 +190,7 @@ impl ConnectionPool {
         if let Some(messages) = messages {
             for message in messages {
                 // self.broadcast(&message);
-                self.protocol_tx.send(Message::RawMessage(message))
+                self.protocol_tx.send(Message::PeerRawMessage(addr, message))
                     .unwrap_or_else(|_| debug!("Ошибка отправки"));
 
                 // Обновляем время активности после broadcast
@@ -294,6 +311,23 @@ mod tests {
 
 
 
+    #[test]
+    fn test_disconnect_peer_closes_stream() {
+        let (tx_pool, rx_pool) = mpsc::channel();
+        let (tx_proto, _rx_proto) = mpsc::channel();
+        let mut pool = ConnectionPool::new(10, tx_pool, rx_pool, tx_proto);
+
+        let mut addr = "127.0.0.1:0".parse().unwrap();
+        let mut client = setup_connection(&mut pool, &mut addr);
+
+        pool.disconnect_peer(&addr);
+        assert!(pool.get_peer_addresses().is_empty());
+
+        // Клиент видит закрытие соединения
+        let mut buffer = [0u8; 8];
+        assert_eq!(client.read(&mut buffer).unwrap(), 0);
+    }
+
     #[test]
     fn test_cleanup_inactive() {
         // timeout = 0, чтобы сразу считать всех неактивными
@@ -323,10 +357,11 @@ mod tests {
         // теперь буфер для addr содержит "partial"
         pool.handle_peer_message(addr, "1\n".to_string());
 
-        // Должны получить 3 RawMessage: "msg1", "msg2", "partial1"
+        // Должны получить 3 PeerRawMessage: "msg1", "msg2", "partial1" с адресом пира
         let mut collected = Vec::new();
         for _ in 0..3 {
-            if let Ok(Message::RawMessage(m)) = rx_proto.recv_timeout(Duration::from_secs(1)) {
+            if let Ok(Message::PeerRawMessage(peer, m)) = rx_proto.recv_timeout(Duration::from_secs(1)) {
+                assert_eq!(peer, addr);
                 collected.push(m);
             }
         }
@@ -9,4 +9,6 @@ pub enum PoolMessage {
     BroadcastMessage(String),
     GetPeers(Sender<Vec<SocketAddr>>),
     PeerMessage(SocketAddr, String),
+    SendToPeer(SocketAddr, String),
+    DisconnectPeer(SocketAddr),
 }
\ No newline at end of file
@@ -50,14 +50,75 @@ impl BlocksBeforeMessage {
     }
 }
 
+// Запрос доказательства включения транзакции в блок
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MerkleProofRequestMessage {
+    id: u64,
+    transaction_hash: String,
+    time_stamp: i64,
+}
+impl MerkleProofRequestMessage {
+    pub fn new(transaction_hash: String) -> MerkleProofRequestMessage {
+        MerkleProofRequestMessage { id: 0, transaction_hash, time_stamp: Utc::now().timestamp() }
+    }
+
+    pub fn get_id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn set_id(&mut self, id: u64) {
+        self.id = id;
+    }
+
+    pub fn get_transaction_hash(&self) -> String {
+        self.transaction_hash.clone()
+    }
+}
+
+// Запрос блока по хешу у конкретного пира (например, родителя блока-сироты)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlockByHashMessage {
+    id: u64,
+    hash: String,
+    time_stamp: i64,
+}
+impl BlockByHashMessage {
+    pub fn new(hash: String) -> BlockByHashMessage {
+        BlockByHashMessage { id: 0, hash, time_stamp: Utc::now().timestamp() }
+    }
+
+    pub fn get_id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn set_id(&mut self, id: u64) {
+        self.id = id;
+    }
+
+    pub fn get_hash(&self) -> String {
+        self.hash.clone()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MessageFirstInfo {
     id: u64,
     time_stamp: i64,
+    // Хеш первого блока сети отправителя
+    #[serde(default)]
+    genesis_hash: String,
 }
 impl MessageFirstInfo {
     pub fn new() -> MessageFirstInfo {
-        MessageFirstInfo { id: 0, time_stamp: Utc::now().timestamp()}
+        MessageFirstInfo { id: 0, time_stamp: Utc::now().timestamp(), genesis_hash: String::new() }
+    }
+
+    pub fn get_genesis_hash(&self) -> String {
+        self.genesis_hash.clone()
+    }
+
+    pub fn set_genesis_hash(&mut self, genesis_hash: String) {
+        self.genesis_hash = genesis_hash;
     }
 
     pub fn get_id(&self) -> u64 {
@@ -1,7 +1,8 @@
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 
-use crate::coin::node::blockchain::block::Block;
+use crate::coin::node::blockchain::block::{Block, BlockHeader};
+use crate::coin::node::blockchain::merkle::MerkleProof;
 use crate::coin::node::blockchain::transaction::SerializedTransaction;
 
 // Пример структуры BlockMessage с флагом force
@@ -104,11 +105,22 @@ impl TextMessage {
 pub struct MessageAnswerFirstInfo {
     id: u64,
     time_stamp: i64,
+    // Хеш первого блока сети отвечающего узла
+    #[serde(default)]
+    genesis_hash: String,
 }
 
 impl MessageAnswerFirstInfo {
     pub fn new() -> MessageAnswerFirstInfo {
-        MessageAnswerFirstInfo{ id: 0, time_stamp: Utc::now().timestamp(),}
+        MessageAnswerFirstInfo{ id: 0, time_stamp: Utc::now().timestamp(), genesis_hash: String::new() }
+    }
+
+    pub fn get_genesis_hash(&self) -> String {
+        self.genesis_hash.clone()
+    }
+
+    pub fn set_genesis_hash(&mut self, genesis_hash: String) {
+        self.genesis_hash = genesis_hash;
     }
 
     pub fn get_id(&self) -> u64 {
@@ -170,3 +182,43 @@ impl PeerMessage {
         self.peer_address
     }
 }
+
+// Заголовок блока и доказательство включения транзакции; proof пуст, если транзакция не найдена
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MerkleProofMessage {
+    id: u64,
+    transaction_hash: String,
+    header: Option<BlockHeader>,
+    proof: Option<MerkleProof>,
+    time_stamp: i64,
+}
+
+impl MerkleProofMessage {
+    pub fn new(transaction_hash: String, found: Option<(BlockHeader, MerkleProof)>) -> MerkleProofMessage {
+        let (header, proof) = match found {
+            Some((header, proof)) => (Some(header), Some(proof)),
+            None => (None, None),
+        };
+        MerkleProofMessage { id: 0, transaction_hash, header, proof, time_stamp: Utc::now().timestamp() }
+    }
+
+    pub fn get_id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn set_id(&mut self, id: u64) {
+        self.id = id;
+    }
+
+    /// Проверяет доказательство по присланному заголовку
+    pub fn is_verified(&self) -> bool {
+        match (&self.header, &self.proof) {
+            (Some(header), Some(proof)) => header.verify_transaction(&self.transaction_hash, proof),
+            _ => false,
+        }
+    }
+
+    pub fn get_transaction_hash(&self) -> String {
+        self.transaction_hash.clone()
+    }
+}
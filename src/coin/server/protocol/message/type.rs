@@ -1,3 +1,5 @@
+use std::net::SocketAddr;
+
 use serde::{Deserialize, Serialize};
 use serde_json;
 use crate::coin::server::protocol::message::{request, response};
@@ -5,19 +7,29 @@ use crate::coin::server::protocol::message::{request, response};
 // Обобщённый тип сообщения, содержащий разные варианты
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type", content = "content")] // Добавляем тег для типа сообщения
+// Имя варианта — это тег сообщения в протоколе, суффикс Message его часть
+#[allow(clippy::enum_variant_names)]
 pub enum Message {
     RawMessage(String),
+    // Сообщение от конкретного пира, адрес нужен для прямого ответа
+    PeerRawMessage(SocketAddr, String),
 
     ResponseBlockMessage(response::BlockMessage),
-    ResponseTransactionMessage(response::TransactionMessage),
+    // Транзакция в куче: она намного больше остальных сообщений
+    ResponseTransactionMessage(Box<response::TransactionMessage>),
     ResponseTextMessage(response::TextMessage),
     ResponseMessageInfo(response::MessageAnswerFirstInfo),
     ResponseChainMessage(response::ChainMessage),
     ResponsePeerMessage(response::PeerMessage),
+    ResponseMerkleProofMessage(response::MerkleProofMessage),
+    // Ответ на RequestBlockByHashMessage, отправляется только запросившему пиру
+    ResponseBlockByHashMessage(response::BlockMessage),
 
     RequestLastNBlocksMessage(request::LastNBlocksMessage),
     RequestBlocksBeforeMessage(request::BlocksBeforeMessage),
     RequestMessageInfo(request::MessageFirstInfo),
+    RequestMerkleProofMessage(request::MerkleProofRequestMessage),
+    RequestBlockByHashMessage(request::BlockByHashMessage),
 }
 
 impl Message {
@@ -33,6 +45,7 @@ impl Message {
     pub fn get_id(&self) -> u64 {
         match self {
             Message::RawMessage(msg) => 0,
+            Message::PeerRawMessage(_, _) => 0,
 
             Message::ResponseBlockMessage(msg) => msg.get_id(),
             Message::ResponseTransactionMessage(msg) => msg.get_id(),
@@ -40,16 +53,21 @@ impl Message {
             Message::ResponseMessageInfo(msg) => msg.get_id(),
             Message::ResponseChainMessage(msg) => msg.get_id(),
             Message::ResponsePeerMessage(msg) => msg.get_id(),
+            Message::ResponseMerkleProofMessage(msg) => msg.get_id(),
+            Message::ResponseBlockByHashMessage(msg) => msg.get_id(),
 
             Message::RequestLastNBlocksMessage(msg) => msg.get_id(),
             Message::RequestBlocksBeforeMessage(msg) => msg.get_id(),
             Message::RequestMessageInfo(msg) => msg.get_id(),
+            Message::RequestMerkleProofMessage(msg) => msg.get_id(),
+            Message::RequestBlockByHashMessage(msg) => msg.get_id(),
         }
     }
 
     pub fn set_id(&mut self, id: u64) {
         match self {
             Message::RawMessage(msg) => (),
+            Message::PeerRawMessage(_, _) => (),
 
             Message::ResponseBlockMessage(msg) => msg.set_id(id),
             Message::ResponseTransactionMessage(msg) => msg.set_id(id),
@@ -57,10 +75,14 @@ impl Message {
             Message::ResponseMessageInfo(msg) => msg.set_id(id),
             Message::ResponseChainMessage(msg) => msg.set_id(id),
             Message::ResponsePeerMessage(msg) => msg.set_id(id),
+            Message::ResponseMerkleProofMessage(msg) => msg.set_id(id),
+            Message::ResponseBlockByHashMessage(msg) => msg.set_id(id),
 
             Message::RequestLastNBlocksMessage(msg) => msg.set_id(id),
             Message::RequestBlocksBeforeMessage(msg) => msg.set_id(id),
             Message::RequestMessageInfo(msg) => msg.set_id(id),
+            Message::RequestMerkleProofMessage(msg) => msg.set_id(id),
+            Message::RequestBlockByHashMessage(msg) => msg.set_id(id),
         }
     }
 }
\ No newline at end of file
@@ -1,3 +1,5 @@
+use std::collections::VecDeque;
+use std::net::SocketAddr;
 use std::sync::mpsc::{Receiver, Sender};
 use std::time::Duration;
 use chrono::{TimeZone, Utc};
@@ -5,12 +7,14 @@ use log::{debug, error, info, warn};
 
 use crate::coin::app_state::AppState;
 use crate::coin::node::blockchain::block::Block;
+use crate::coin::node::blockchain::blockchain::ChainUpdate;
+use crate::coin::node::blockchain::orphan::MAX_ORPHANS;
 use crate::coin::server::pool::pool_message::PoolMessage;
 use crate::coin::server::pool::pool_message::PoolMessage::BroadcastMessage;
 use crate::coin::server::protocol::message::response;
 use crate::coin::server::protocol::message::r#type::Message;
-use crate::coin::server::protocol::message::request::{BlocksBeforeMessage, LastNBlocksMessage};
-use crate::coin::server::protocol::message::response::{BlockMessage, ChainMessage, PeerMessage, TransactionMessage};
+use crate::coin::server::protocol::message::request::{BlockByHashMessage, BlocksBeforeMessage, LastNBlocksMessage, MerkleProofRequestMessage};
+use crate::coin::server::protocol::message::response::{BlockMessage, ChainMessage, MerkleProofMessage, PeerMessage, TransactionMessage};
 
 pub struct P2PProtocol{
     //Каналы для коммуникации с потоком протокола
@@ -21,6 +25,9 @@ pub struct P2PProtocol{
     pool_tx: Sender<PoolMessage>,
 
     last_message_id: u64,
+    // Блоки, запрошенные у пиров по хешу: прямой ответ принимается только на них.
+    // Запросов без ответа хранится не больше, чем сирот, старые вытесняются
+    requested_blocks: VecDeque<(SocketAddr, String)>,
     app_state: AppState,
 }
 
@@ -29,6 +36,7 @@ impl P2PProtocol{
         P2PProtocol{
             tx, rx, pool_tx,
             last_message_id: 0,
+            requested_blocks: VecDeque::new(),
             app_state,
         }
     }
@@ -42,16 +50,15 @@ impl P2PProtocol{
             match self.rx.recv_timeout(Duration::from_secs(1)) {
                 // input from other nodes
                 Ok(Message::RawMessage(message_json)) => {
-                    let message_json = message_json.trim_end_matches('\0');
-                    debug!("P2P protocol: message json: {}", message_json);
-                    match Message::from_json(message_json) {
-                        Ok(message) => {
-                            self.process_message(message);
-                        },
-                        Err(e) => {
-                            warn!("Failed to deserialize response_message: {}, {}", e, message_json);
-                        }
-                    };
+                    self.process_raw_message(None, &message_json);
+                },
+                Ok(Message::PeerRawMessage(peer, message_json)) => {
+                    self.process_raw_message(Some(peer), &message_json);
+                },
+                // Рукопожатие с новым пиром несёт хеш первого блока сети
+                Ok(Message::RequestMessageInfo(mut message_info)) => {
+                    message_info.set_genesis_hash(self.app_state.get_genesis_hash());
+                    self.send_message(Message::RequestMessageInfo(message_info));
                 },
                 // input from this server
                 Ok(message) => {
@@ -71,17 +78,48 @@ impl P2PProtocol{
         }
     }
 
-    /// Входящие сообщения
-    fn process_message(&mut self, message: Message){
+    fn process_raw_message(&mut self, peer: Option<SocketAddr>, message_json: &str) {
+        let message_json = message_json.trim_end_matches('\0');
+        debug!("P2P protocol: message json: {}", message_json);
+        match Message::from_json(message_json) {
+            Ok(message) => {
+                self.process_peer_message(peer, message);
+            },
+            Err(e) => {
+                warn!("Failed to deserialize response_message: {}, {}", e, message_json);
+            }
+        };
+    }
+
+    /// Входящие сообщения от пира `peer` (если адрес известен)
+    fn process_peer_message(&mut self, peer: Option<SocketAddr>, message: Message){
         match message{
-            Message::RequestMessageInfo(_) => {
+            // Прямые запрос и ответ идут мимо счётчика сообщений и не рассылаются дальше
+            Message::RequestBlockByHashMessage(msg) => {
+                self.send_block_by_hash(peer, msg);
+                return
+            }
+            Message::ResponseBlockByHashMessage(msg) => {
+                if self.take_requested_block(peer, &msg.get_block().get_hash()) {
+                    self.process_block(peer, msg);
+                } else {
+                    warn!("Block {} from {:?} was not requested", msg.get_block().get_hash(), peer);
+                }
+                return
+            }
+            Message::RequestMessageInfo(msg) => {
                 info!("Type:RequestMessageInfo get");
-                self.send_first_message();
+                if self.check_genesis(peer, &msg.get_genesis_hash()) {
+                    self.send_first_message();
+                }
 
                 return
             }
             Message::ResponseMessageInfo(msg) => {
                 info!("Type:ResponseMessageInfo get");
+                if !self.check_genesis(peer, &msg.get_genesis_hash()) {
+                    return
+                }
                 let message_id = msg.get_id();
                 if self.last_message_id < message_id {
                     self.last_message_id = message_id;
@@ -103,13 +141,17 @@ impl P2PProtocol{
         self.pool_tx.send(PoolMessage::BroadcastMessage(message.to_json())).expect("TODO: panic message");
 
         match message {
-            Message::ResponseTransactionMessage(msg) =>self.process_transaction(msg),
-            Message::ResponseBlockMessage(msg )=>self.process_block(msg),
+            Message::ResponseTransactionMessage(msg) =>self.process_transaction(*msg),
+            Message::ResponseBlockMessage(msg )=>self.process_block(peer, msg),
             Message::ResponseChainMessage(msg)=>self.process_chain(msg),
             Message::ResponsePeerMessage(msg)=>self.process_peer(msg),
 
             Message::RequestLastNBlocksMessage(msg) => self.send_last_n_locks(msg),
             Message::RequestBlocksBeforeMessage(msg) => self.send_block_before(msg),
+            Message::RequestMerkleProofMessage(msg) => self.send_merkle_proof(msg),
+            Message::ResponseMerkleProofMessage(msg) => {
+                info!("Merkle proof for {}: verified={}", msg.get_transaction_hash(), msg.is_verified());
+            },
             Message::ResponseTextMessage(msg) => {
                 info!("Get text message: {}", msg.get_text());
             },
@@ -130,12 +172,57 @@ impl P2PProtocol{
         self.pool_tx.send(broadcast_message).unwrap();
     }
 
-    fn process_block(&self, msg:BlockMessage) {
-        let is_force_block = msg.is_force();
+    fn process_block(&mut self, peer: Option<SocketAddr>, msg:BlockMessage) {
         let new_block = msg.get_block();
         debug!("Get new block: {}", new_block.get_id());
+        if msg.is_force() {
+            debug!("Force flag ignored, block {} is validated by fork choice", new_block.get_id());
+        }
+
+        match self.app_state.add_block(new_block) {
+            Ok(ChainUpdate::Orphan { missing_parent }) => self.request_block(peer, missing_parent),
+            Ok(_) => (),
+            Err(e) => warn!("Block rejected: {}", e),
+        }
+    }
+
+    /// Запрашивает недостающий блок у пира, приславшего его потомка
+    fn request_block(&mut self, peer: Option<SocketAddr>, hash: String) {
+        let peer = match peer {
+            Some(peer) => peer,
+            None => {
+                warn!("Parent {} is missing, but the sender is unknown", hash);
+                return;
+            }
+        };
+        debug!("Request block {} from {}", hash, peer);
+        if self.requested_blocks.len() >= MAX_ORPHANS {
+            self.requested_blocks.pop_front();
+        }
+        self.requested_blocks.push_back((peer, hash.clone()));
+        let json_message = Message::RequestBlockByHashMessage(BlockByHashMessage::new(hash)).to_json();
+        self.pool_tx.send(PoolMessage::SendToPeer(peer, json_message)).unwrap();
+    }
 
-        self.app_state.add_block(new_block, is_force_block); //TODO(обработать ошибки)
+    /// Снимает запрос блока `hash` у пира `peer`; `false`, если такого запроса не было
+    fn take_requested_block(&mut self, peer: Option<SocketAddr>, hash: &str) -> bool {
+        let position = self.requested_blocks.iter().position(|(requested_peer, requested_hash)| {
+            Some(*requested_peer) == peer && requested_hash == hash
+        });
+        position.and_then(|index| self.requested_blocks.remove(index)).is_some()
+    }
+
+    fn send_block_by_hash(&self, peer: Option<SocketAddr>, msg:BlockByHashMessage) {
+        let hash = msg.get_hash();
+        let (peer, block) = match (peer, self.app_state.get_block_by_hash(&hash)) {
+            (Some(peer), Some(block)) => (peer, block),
+            _ => {
+                debug!("Can't answer block request {}", hash);
+                return;
+            }
+        };
+        let json_message = Message::ResponseBlockByHashMessage(BlockMessage::new(block, false)).to_json();
+        self.pool_tx.send(PoolMessage::SendToPeer(peer, json_message)).unwrap();
     }
 
     fn process_transaction(&self, msg:TransactionMessage) {
@@ -156,10 +243,23 @@ impl P2PProtocol{
         self.app_state.connect(peer);
     }
 
+    /// Пир из другой сети (с другим первым блоком) отключается
+    fn check_genesis(&self, peer: Option<SocketAddr>, genesis_hash: &str) -> bool {
+        if genesis_hash == self.app_state.get_genesis_hash() {
+            return true;
+        }
+        warn!("Peer {:?} has different genesis {}", peer, genesis_hash);
+        if let Some(peer) = peer {
+            self.pool_tx.send(PoolMessage::DisconnectPeer(peer)).unwrap();
+        }
+        false
+    }
+
     fn send_first_message(&mut self){
         self.last_message_id += 1;
         let mut message_info = response::MessageAnswerFirstInfo::new();
         message_info.set_id(self.last_message_id);
+        message_info.set_genesis_hash(self.app_state.get_genesis_hash());
         let response_message = Message::ResponseMessageInfo(message_info);
 
         let json_message = response_message.to_json();
@@ -185,6 +285,19 @@ impl P2PProtocol{
         self.send_chain(chain);
     }
 
+    fn send_merkle_proof(&mut self, msg:MerkleProofRequestMessage){
+        let transaction_hash = msg.get_transaction_hash();
+        debug!("Request merkle proof: {}", transaction_hash);
+        let found = self.app_state.get_merkle_proof(&transaction_hash);
+
+        self.last_message_id += 1;
+        let mut proof_message = MerkleProofMessage::new(transaction_hash, found);
+        proof_message.set_id(self.last_message_id);
+
+        let json_message = Message::ResponseMerkleProofMessage(proof_message).to_json();
+        self.pool_tx.send(BroadcastMessage(json_message)).unwrap();
+    }
+
     fn send_chain(&mut self, chain:Vec<Block>){
         self.last_message_id += 1;
         let mut chain_message = ChainMessage::new(chain);
@@ -209,7 +322,10 @@ mod tests {
     use crate::coin::server::pool::pool_message::PoolMessage::BroadcastMessage;
     use crate::coin::server::protocol::message::r#type::Message;
     use crate::coin::server::protocol::message::request;
-    use crate::coin::server::protocol::message::request::{LastNBlocksMessage, BlocksBeforeMessage};
+    use crate::coin::node::blockchain::blockchain::Blockchain;
+    use crate::coin::node::blockchain::{address, difficulty, emission};
+    use crate::coin::node::blockchain::crypto::{PrivateKey, SignatureAlgorithm, Signer};
+    use crate::coin::server::protocol::message::request::{LastNBlocksMessage, BlocksBeforeMessage, MerkleProofRequestMessage};
     use crate::coin::server::protocol::message::response::{MessageAnswerFirstInfo, TextMessage};
 
     /// Вспомогалка: создаёт P2PProtocol с пустым AppState и новыми каналами.
@@ -258,9 +374,11 @@ mod tests {
     fn test_process_request_message_info() {
         let (mut proto, rx_pool) = make_protocol();
         // отправляем запрос первой информации
-        let mut req = Message::RequestMessageInfo(request::MessageFirstInfo::new());
+        let mut info = request::MessageFirstInfo::new();
+        info.set_genesis_hash(proto.app_state.get_genesis_hash());
+        let mut req = Message::RequestMessageInfo(info);
         // process_message ждёт, что req.id не важен, он не сравнивается
-        proto.process_message(req);
+        proto.process_peer_message(None, req);
 
         // Должно уйти два BroadcastMessage:
         // 1) ответ на RequestMessageInfo (ResponseMessageInfo)
@@ -292,9 +410,11 @@ mod tests {
         proto.last_message_id = 5;
 
         // отправляем ResponseMessageInfo с id = 10
-        let mut ans = Message::ResponseMessageInfo(MessageAnswerFirstInfo::new());
+        let mut info = MessageAnswerFirstInfo::new();
+        info.set_genesis_hash(proto.app_state.get_genesis_hash());
+        let mut ans = Message::ResponseMessageInfo(info);
         ans.set_id(10);
-        proto.process_message(ans);
+        proto.process_peer_message(None, ans);
 
         // pool_tx не должен ничего получить (мы только обновляем last_message_id)
         assert!(rx_pool.recv_timeout(Duration::from_millis(50))
@@ -304,6 +424,23 @@ mod tests {
         assert_eq!(proto.last_message_id, 10);
     }
 
+    #[test]
+    fn test_peer_with_other_genesis_disconnected() {
+        let (mut proto, rx_pool) = make_protocol();
+        let peer: SocketAddr = "127.0.0.1:7878".parse().unwrap();
+
+        let mut info = request::MessageFirstInfo::new();
+        info.set_genesis_hash("other network".to_string());
+        proto.process_peer_message(Some(peer), Message::RequestMessageInfo(info));
+
+        match rx_pool.recv_timeout(Duration::from_secs(1)).unwrap() {
+            PoolMessage::DisconnectPeer(addr) => assert_eq!(addr, peer),
+            other => panic!("ожидали DisconnectPeer, получено {:?}", other),
+        }
+        // Цепочка чужой сети не отправляется
+        assert!(rx_pool.recv_timeout(Duration::from_millis(50)).is_err());
+    }
+
     #[test]
     fn test_process_message_ignores_old() {
         let (mut proto, rx_pool) = make_protocol();
@@ -312,7 +449,7 @@ mod tests {
         let mut old = Message::ResponseTextMessage(TextMessage::new("old".to_string()));
         old.set_id(3);
 
-        proto.process_message(old);
+        proto.process_peer_message(None, old);
         // Ничего не приходит
         assert!(rx_pool.recv_timeout(Duration::from_millis(50))
             .err()
@@ -328,7 +465,7 @@ mod tests {
 
         let mut txt = Message::ResponseTextMessage(TextMessage::new("world".to_string()));
         txt.set_id(10);
-        proto.process_message(txt);
+        proto.process_peer_message(None, txt);
 
         let got = rx_pool.recv_timeout(Duration::from_secs(1)).unwrap();
         if let BroadcastMessage(json) = got {
@@ -348,7 +485,7 @@ mod tests {
         // создаём запрос последних 3 блоков
         let mut req = LastNBlocksMessage::new(3);
         req.set_id(7);
-        proto.process_message(Message::RequestLastNBlocksMessage(req));
+        proto.process_peer_message(None, Message::RequestLastNBlocksMessage(req));
 
         // 1) эхо самого запроса
         let echo = rx_pool.recv_timeout(Duration::from_secs(1)).unwrap();
@@ -369,6 +506,109 @@ mod tests {
         assert_eq!(proto.last_message_id, 8);
     }
 
+    #[test]
+    fn test_process_request_merkle_proof_unknown_transaction() {
+        let (mut proto, rx_pool) = make_protocol();
+
+        let mut req = MerkleProofRequestMessage::new("ab".repeat(32));
+        req.set_id(3);
+        proto.process_peer_message(None, Message::RequestMerkleProofMessage(req));
+
+        // 1) эхо запроса
+        let _echo = rx_pool.recv_timeout(Duration::from_secs(1)).unwrap();
+        // 2) ответ без доказательства
+        let answer = rx_pool.recv_timeout(Duration::from_secs(1)).unwrap();
+
+        if let BroadcastMessage(json) = answer {
+            assert!(json.contains("ResponseMerkleProofMessage"));
+            assert!(json.contains("\"proof\":null"));
+            assert!(json.contains("\"id\":4"));
+        } else { panic!("ожидали BroadcastMessage с доказательством"); }
+    }
+
+    /// Пустой блок с coinbase поверх `parent`, удовлетворяющий начальной цели
+    fn mine_child(parent: &Block) -> Block {
+        let height = parent.get_id() + 1;
+        let miner = address::from_public_key(&PrivateKey::generate(SignatureAlgorithm::Ed25519).get_public_key());
+        let transactions = vec![emission::coinbase_transaction(&miner, height, &[])];
+        // Время растёт с высотой, чтобы блок был позже медианы предков
+        let mut block = Block::force_new(height, Utc::now().timestamp() + height as i64, transactions, parent.get_hash(), 0, difficulty::INITIAL_TARGET);
+        let mut nonce = 0;
+        while !block.meets_target() {
+            nonce += 1;
+            block.set_nonce(nonce);
+        }
+        block
+    }
+
+    /// Протокол с цепочкой из одного первого блока
+    fn make_protocol_with_genesis() -> (P2PProtocol, std::sync::mpsc::Receiver<PoolMessage>, Block) {
+        let (mut proto, rx_pool) = make_protocol();
+        let mut blockchain = Blockchain::new();
+        blockchain.create_first_block();
+        let genesis = blockchain.get_last_block().unwrap();
+        proto.app_state.set_blockchain(channel().0, Arc::new(Mutex::new(blockchain)));
+        (proto, rx_pool, genesis)
+    }
+
+    #[test]
+    fn test_orphan_block_requests_parent_from_sender() {
+        let (mut proto, rx_pool, genesis) = make_protocol_with_genesis();
+        let peer: SocketAddr = "127.0.0.1:7878".parse().unwrap();
+        let parent = mine_child(&genesis);
+        let orphan = mine_child(&parent);
+
+        let mut msg = Message::ResponseBlockMessage(BlockMessage::new(orphan.clone(), false));
+        msg.set_id(1);
+        proto.process_peer_message(Some(peer), msg);
+
+        // 1) рассылка блока дальше
+        let _echo = rx_pool.recv_timeout(Duration::from_secs(1)).unwrap();
+        // 2) прямой запрос родителя у отправителя
+        match rx_pool.recv_timeout(Duration::from_secs(1)).unwrap() {
+            PoolMessage::SendToPeer(addr, json) => {
+                assert_eq!(addr, peer);
+                assert!(json.contains("RequestBlockByHashMessage"));
+                assert!(json.contains(&parent.get_hash()));
+            }
+            other => panic!("ожидали SendToPeer, получено {:?}", other),
+        }
+
+        // Прямой ответ с родителем подключает и сироту, несмотря на старый id
+        proto.process_peer_message(Some(peer), Message::ResponseBlockByHashMessage(BlockMessage::new(parent, false)));
+        assert_eq!(proto.app_state.get_last_n_blocks(3).len(), 3);
+        assert!(proto.app_state.get_block_by_hash(&orphan.get_hash()).is_some());
+    }
+
+    #[test]
+    fn test_unrequested_block_by_hash_ignored() {
+        let (mut proto, _rx_pool, genesis) = make_protocol_with_genesis();
+        let peer: SocketAddr = "127.0.0.1:7878".parse().unwrap();
+        let block = mine_child(&genesis);
+
+        // Прямой ответ минует счётчик сообщений, поэтому без запроса он не принимается
+        proto.process_peer_message(Some(peer), Message::ResponseBlockByHashMessage(BlockMessage::new(block.clone(), false)));
+        assert!(proto.app_state.get_block_by_hash(&block.get_hash()).is_none());
+    }
+
+    #[test]
+    fn test_block_by_hash_answered_to_requesting_peer() {
+        let (mut proto, rx_pool, genesis) = make_protocol_with_genesis();
+        let peer: SocketAddr = "127.0.0.1:7878".parse().unwrap();
+
+        let request = BlockByHashMessage::new(genesis.get_hash());
+        proto.process_peer_message(Some(peer), Message::RequestBlockByHashMessage(request));
+
+        match rx_pool.recv_timeout(Duration::from_secs(1)).unwrap() {
+            PoolMessage::SendToPeer(addr, json) => {
+                assert_eq!(addr, peer);
+                assert!(json.contains("ResponseBlockByHashMessage"));
+            }
+            other => panic!("ожидали SendToPeer, получено {:?}", other),
+        }
+        assert_eq!(proto.last_message_id, 0, "Прямой ответ не меняет счётчик сообщений");
+    }
+
     #[test]
     fn test_process_request_blocks_before() {
         let (mut proto, rx_pool) = make_protocol();
@@ -377,7 +617,7 @@ mod tests {
         // запрос блоков до UNIX-времени 1_600_000_000
         let mut req = BlocksBeforeMessage::new(1_600_000_000);
         req.set_id(5);
-        proto.process_message(Message::RequestBlocksBeforeMessage(req));
+        proto.process_peer_message(None, Message::RequestBlocksBeforeMessage(req));
 
         // 1) эхо запроса
         let echo = rx_pool.recv_timeout(Duration::from_secs(1)).unwrap();
@@ -5,14 +5,23 @@ use std::io::Write;
 use log::{debug, error, info, warn};
 use coin::app_state::AppState;
 use crate::coin::db::BlockDatabase;
+use crate::coin::node::blockchain::address;
+use crate::coin::node::blockchain::amount::Amount;
 use crate::coin::node::blockchain::block::Block;
 use crate::coin::node::blockchain::blockchain::Blockchain;
-use crate::coin::node::blockchain::transaction::{SerializedTransaction, Transaction};
+use crate::coin::node::blockchain::crypto::SignatureAlgorithm;
+use crate::coin::node::blockchain::htlc::hashlock;
+use crate::coin::node::blockchain::merkle;
+use crate::coin::node::blockchain::multisig::MultisigPolicy;
+use crate::coin::node::blockchain::transaction::{decode_public_key, TransactionKind};
 use crate::coin::node::blockchain::wallet::Wallet;
+use crate::coin::node::node_message;
 use crate::coin::node::node_mining::NodeMining;
 use crate::coin::node::node_transaction::NodeTransaction;
+use crate::coin::params::{ChainParams, Network};
 use crate::coin::server::pool::connection_pool::ConnectionPool;
 use crate::coin::server::protocol::message::r#type::Message;
+use crate::coin::server::protocol::message::request::MerkleProofRequestMessage;
 use crate::coin::server::protocol::message::response::{BlockMessage, TextMessage, TransactionMessage};
 use crate::coin::server::protocol::p2p_protocol::P2PProtocol;
 use crate::coin::server::server::Server;
@@ -36,16 +45,46 @@ fn initialize_server(mut app_state:AppState) -> (ConnectionPool, P2PProtocol, Se
     (pool, protocol, server)
 }
 
-fn initialise_nodes(app_state: &mut AppState, tx_external: Sender<Block>,) -> (NodeTransaction, NodeMining, Arc<Mutex<Blockchain>>) {
+/// Сеть узла: переменная окружения Network (main, test, regtest), по умолчанию основная
+fn get_chain_params() -> ChainParams {
+    match std::env::var("Network") {
+        Ok(name) => match name.parse::<Network>() {
+            Ok(network) => ChainParams::for_network(network),
+            Err(e) => panic!("{}", e),
+        },
+        Err(_) => ChainParams::main(),
+    }
+}
+
+/// Адрес для награды за блоки: переменная окружения MinerAddress или адрес локального кошелька
+fn get_miner_address(params: &ChainParams) -> String {
+    match std::env::var("MinerAddress") {
+        Ok(miner_address) => {
+            let miner_address = miner_address.trim().to_string();
+            if let Err(e) = address::check_account(&miner_address) {
+                panic!("MinerAddress: {}", e);
+            }
+            miner_address
+        }
+        Err(_) => {
+            let wallet_path = params.get_wallet_path();
+            Wallet::load_from_file(wallet_path.to_str().expect("wallet path is not UTF-8")).get_address()
+        }
+    }
+}
+
+fn initialise_nodes(app_state: &mut AppState, tx_external: Sender<Block>, params: ChainParams) -> (NodeTransaction, NodeMining, Arc<Mutex<Blockchain>>) {
     let(transaction_tx, transaction_rx) = channel();
 
-    let node_transaction = NodeTransaction::new(transaction_tx);
+    let miner_address = get_miner_address(&params);
+    let mutex_blockchain = Arc::new(Mutex::new(Blockchain::with_params(params)));
+
+    let node_transaction = NodeTransaction::new(transaction_tx, mutex_blockchain.clone());
 
-    let mutex_blockchain = Arc::new(Mutex::new(Blockchain::new()));
     let transaction_tx = node_transaction.get_sender();
 
     app_state.set_blockchain(transaction_tx.clone(), mutex_blockchain.clone());
-    let node_mining = NodeMining::new(transaction_tx, transaction_rx, tx_external, mutex_blockchain.clone());
+    let node_mining = NodeMining::new(transaction_tx, transaction_rx, tx_external, mutex_blockchain.clone(), miner_address);
 
     (node_transaction, node_mining,mutex_blockchain)
 }
@@ -63,12 +102,18 @@ fn get_input_text(info_text: &str) -> String {
     }
 }
 
-fn command_input(protocol_sender: Sender<Message>){
+fn command_input(protocol_sender: Sender<Message>, transaction_sender: Sender<node_message::TransactionMessage>, database: Arc<Mutex<BlockDatabase>>, chain_id: u32){
     loop {
         println!("\nДоступные команды:");
         println!("- Подключиться к другому серверу (connect <IP>:<port>)");
         println!("- Вещать сообщение всем пирами (broadcast <сообщение>)");
         println!("- Создать транзакцию (transaction)");
+        println!("- Запросить доказательство включения транзакции (proof <хеш транзакции>)");
+        println!("- Адрес multisig-счёта (multisig <порог> <ключи участников...>)");
+        println!("- Адрес для публичного ключа (address <ключ>)");
+        println!("- Найти транзакцию в сохранённых блоках (lookup <txid>)");
+        println!("- Хешлок HTLC для атомарного обмена (hashlock <секрет>)");
+        println!("- Добыть блок из очереди транзакций (generate)");
         println!("- Выйти (exit)");
 
         match get_input_text("Введите команду").split_whitespace().collect::<Vec<&str>>().as_slice() {
@@ -78,27 +123,89 @@ fn command_input(protocol_sender: Sender<Message>){
             }
             ["transaction", message @ ..] if !message.is_empty() => {
                 let message = message.join(" ");
-                let wallet = Wallet::new();
-                let sender_key = wallet.get_public_key_string();
-
-                let response_transaction =
-                    SerializedTransaction::new(sender_key.clone(), sender_key.clone(), sender_key.clone(), message, 12.0);
-
-                let mut signed_transaction = response_transaction.clone();
-                let transaction = Transaction::deserialize(response_transaction);
+                // Алгоритм подписи нового ключа: rsa, ed25519 или secp256k1, пустой ввод — по умолчанию
+                let algorithm = get_input_text("Алгоритм подписи").parse::<SignatureAlgorithm>().unwrap_or_default();
+                let wallet = Wallet::generate(algorithm);
+                // Адрес продавца, пустой ввод — перевод самому себе
+                let seller_address = match get_input_text("Адрес продавца") {
+                    seller_address if seller_address.is_empty() => wallet.get_address(),
+                    seller_address => seller_address,
+                };
+                let mut payments = vec![(seller_address, Amount::from_coins(12))];
+                // Другие получатели той же транзакции: <адрес>=<сумма> через пробел
+                for payment in get_input_text("Другие получатели").split_whitespace() {
+                    match payment.split_once('=').and_then(|(recipient, amount)| Some((recipient.to_string(), amount.parse::<Amount>().ok()?))) {
+                        Some(payment) => payments.push(payment),
+                        None => println!("Пропущен получатель {}: ожидалось <адрес>=<сумма>", payment),
+                    }
+                }
 
-                match transaction {
+                match wallet.create_payment(payments, message) {
                     Ok(mut transaction) => {
-                        transaction.sign(wallet.get_private_key());
-                        signed_transaction = transaction.serialize();
+                        // Комиссия входит в подпись, по ней транзакция встаёт в очередь майнера
+                        let fee = get_input_text("Комиссия").parse::<Amount>().unwrap_or(Amount::ZERO);
+                        transaction.set_fee(fee);
+                        // Окно действия: высота блока или время Unix, пустой ввод — без ограничения
+                        transaction.set_valid_after(get_input_text("Действует с").parse::<u64>().ok());
+                        transaction.set_valid_until(get_input_text("Действует до").parse::<u64>().ok());
+                        // Срок депонирования: средства уходят продавцу только после подтверждения покупателя
+                        if let Ok(timeout) = get_input_text("Срок депонирования").parse::<u64>() {
+                            transaction.set_kind(TransactionKind::EscrowLock { timeout });
+                        }
+                        // Хешлок HTLC для атомарного обмена: средства получит тот, кто раскроет прообраз
+                        let htlc_hashlock = get_input_text("Хешлок HTLC");
+                        if !htlc_hashlock.is_empty()
+                            && let Ok(timeout) = get_input_text("Срок HTLC").parse::<u64>() {
+                            transaction.set_kind(TransactionKind::HtlcLock { hashlock: htlc_hashlock, timeout });
+                        }
+                        transaction.sign(&wallet.get_private_key(), chain_id);
+                        println!("Подпись {} создана", wallet.get_algorithm());
+                        let response_message = Message::ResponseTransactionMessage(Box::new(TransactionMessage::new(transaction.serialize())));
+                        protocol_sender.send(response_message).unwrap();
                     }
                     Err(e) => {
                         warn!("{}", e);
                     }
                 }
-                println!("Подпись создана");
-                let response_message = Message::ResponseTransactionMessage(TransactionMessage::new(signed_transaction));
-                protocol_sender.send(response_message).unwrap();
+            }
+            ["address", key] => {
+                match decode_public_key(key) {
+                    Ok(key) => println!("Адрес: {}", address::from_public_key(&key)),
+                    Err(e) => println!("Некорректный ключ: {}", e),
+                }
+            }
+            ["hashlock", secret] => {
+                println!("Прообраз: {}", merkle::to_hex(secret.as_bytes()));
+                println!("Хешлок: {}", hashlock(secret.as_bytes()));
+            }
+            ["proof", transaction_hash] => {
+                let request = MerkleProofRequestMessage::new(transaction_hash.to_string());
+                protocol_sender.send(Message::RequestMerkleProofMessage(request)).unwrap();
+            }
+            ["multisig", threshold, keys @ ..] if !keys.is_empty() => {
+                let keys = keys.iter().map(|key| key.to_string()).collect();
+                match threshold.parse::<usize>().map_err(|e| e.to_string())
+                    .and_then(|threshold| MultisigPolicy::new(threshold, keys).map_err(|e| e.to_string())) {
+                    Ok(policy) => println!(
+                        "Адрес счёта {} из {}: {}",
+                        policy.get_threshold(), policy.get_keys().len(), policy.get_address()
+                    ),
+                    Err(e) => println!("Некорректный multisig-счёт: {}", e),
+                }
+            }
+            ["lookup", txid] => {
+                match database.lock().unwrap().find_transaction(txid) {
+                    Ok(Some(lookup)) => println!(
+                        "Перевод {} (комиссия {}) в блоке {} ({}), подтверждений: {}",
+                        lookup.transaction.get_transfer(), lookup.transaction.get_fee(),
+                        lookup.block.get_id(), lookup.block.get_hash(), lookup.confirmations
+                    ),
+                    Ok(None) => println!("Транзакция не найдена"),
+                    Err(e) => error!("Ошибка поиска транзакции {}: {}", txid, e),
+                }
+            }
+            ["generate"] => {
+                transaction_sender.send(node_message::TransactionMessage::GenerateBlock()).unwrap();
             }
             ["exit"] => {
                 println!("Выход из программы.");
@@ -118,15 +225,24 @@ fn main() {
     //
     // // Пример логгирования сообщений с разным уровнем
     info!("Program run");
+    let params = get_chain_params();
+    info!("Network: {}", params.network);
+    std::fs::create_dir_all(&params.data_dir).expect("Can't create data directory");
+    let server_address = format!("0.0.0.0:{}", params.default_port);
+    let connect_port = params.default_port;
+    let chain_id = params.chain_id;
     // initialize database
-    let database = BlockDatabase::new("test.db").expect("error open file db");
+    let db_path = params.get_db_path();
+    let database = BlockDatabase::new(db_path.to_str().expect("db path is not UTF-8")).expect("error open file db");
     //TODO "Поправить нейминг"
     let mutexDatabase = Arc::new(Mutex::new(database));
     let mutexDatabaseThread = mutexDatabase.clone();
+    let lookup_database = mutexDatabase.clone();
     let mut app_state = AppState::new(mutexDatabase);
 
     let (tx, rx) = channel();
-    let (mut nt, mut nm, mutex_blockchain) = initialise_nodes(&mut app_state, tx);
+    let (mut nt, mut nm, mutex_blockchain) = initialise_nodes(&mut app_state, tx, params);
+    let transaction_sender = nt.get_sender();
     let (mut cp, mut p2p, mut server) = initialize_server(app_state);
 
     let protocol_sender = p2p.get_sender_protocol();
@@ -136,9 +252,14 @@ fn main() {
         nt.run();
     });
 
+    // Цепочка начинается с первого блока сети, сохранённые блоки проверяются заново
+    //TODO нормально обработать ошибки
+    let stored_chain = mutexDatabaseThread.lock().unwrap().get_all_blocks().unwrap();
+    if let Err(e) = mutex_blockchain.lock().unwrap().load_chain(stored_chain) {
+        error!("Stored chain truncated at invalid block: {}", e);
+    }
+
     if is_mining_pool {
-        //TODO нормально обработать ошибки
-        mutex_blockchain.lock().unwrap().chain = mutexDatabaseThread.lock().unwrap().get_all_blocks().unwrap();
         let node_mining_thread = thread::spawn(move || {
             nm.run();
         });
@@ -184,21 +305,21 @@ fn main() {
     if !is_container {
         let server_copy = Server::new(server.get_pool_sender());
         let server_thread = thread::spawn(move || {
-            server.run("0.0.0.0:7878").expect("Can't run server thread");
+            server.run(&server_address).expect("Can't run server thread");
         });
 
         let server = server_copy;
 
         // server.connect(format!("localhost:{}", 7879)).expect("Connect to ");
         //UserNode
-        command_input(protocol_sender);
+        command_input(protocol_sender, transaction_sender, lookup_database, chain_id);
         server_thread.join().unwrap();
     } else {
         match std::env::var("ConnectAddr") {
-            Ok(val) => server.connect(format!("{}:7878", val)).unwrap(),
+            Ok(val) => server.connect(format!("{}:{}", val, connect_port)).unwrap(),
             Err(err) => info!("Error read env: {}", err)
         }
-        server.run("0.0.0.0:7878").expect("Can't run server thread");
+        server.run(&server_address).expect("Can't run server thread");
     }
     protocol_thread.join().unwrap();
     connection_pool_thread.join().unwrap();